@@ -1,7 +1,9 @@
 use super::lit::Lit;
 use super::op::{BinOp, UnOp};
+use super::pattern::Arm;
 use super::region::Region;
-use super::stmt::Block;
+use super::stmt::{Block, Param};
+use super::ty::Type;
 
 use crate::name::Name;
 use crate::token::{Token, TokenKind};
@@ -16,14 +18,50 @@ pub enum ExprKind {
     Variable(Name),
     Unary(UnOp, Box<Expr>),
     Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// call -> expr '(' arguments? ')'
+    /// The callee is the name of a function or any expression whose value is a function
     #[allow(clippy::vec_box)]
-    Call(Name, Vec<Box<Expr>>),
+    Call(Box<Expr>, Vec<Box<Expr>>),
+    /// method_call -> expr '.' IDENTIFIER '(' arguments? ')'
+    /// The receiver, the method's name and the arguments that follow `self`
+    #[allow(clippy::vec_box)]
+    MethodCall(Box<Expr>, Name, Vec<Box<Expr>>),
     Cond(Box<Expr>, Block, Option<Block>),
-    Assign(Name, Box<Expr>),
+    /// assignment -> place '=' expression
+    /// The target is always a place expression (see [`Expr::is_place`])
+    Assign(Box<Expr>, Box<Expr>),
+    /// cast -> expr "as" type
+    Cast(Box<Expr>, Type),
+    /// struct_lit -> IDENTIFIER '{' ( IDENTIFIER ':' expression ),* '}'
+    #[allow(clippy::vec_box)]
+    StructLit(Name, Vec<(Name, Box<Expr>)>),
+    /// field -> expr '.' IDENTIFIER
+    Field(Box<Expr>, Name),
+    /// tuple -> '(' expression ( ',' expression )+ ')'
+    #[allow(clippy::vec_box)]
+    Tuple(Vec<Box<Expr>>),
+    /// array_lit -> '[' expression,* ']'
+    #[allow(clippy::vec_box)]
+    Array(Vec<Box<Expr>>),
+    /// index -> expr '[' expression ']'
+    Index(Box<Expr>, Box<Expr>),
+    /// variant -> IDENTIFIER "::" IDENTIFIER ( '(' arguments? ')' )?
+    /// A value of an enum, the enum's name, the variant's name and its payload
+    #[allow(clippy::vec_box)]
+    Variant(Name, Name, Vec<Box<Expr>>),
+    /// match -> "match" logic_or '{' arm* '}'
+    Match(Box<Expr>, Vec<Arm>),
+    /// lambda -> "func" '(' parameter_list? ')' type_annotation? block
+    /// An anonymous function, it captures the values of the local variables it uses when it's
+    /// created
+    Lambda(Vec<Param>, Type, Block),
+    /// An expression with a syntax error that the parser could keep going after, like an
+    /// assignment to something that isn't a place. The parser has already reported it
+    Error,
 }
 
-impl From<i64> for ExprKind {
-    fn from(value: i64) -> Self {
+impl From<u64> for ExprKind {
+    fn from(value: u64) -> Self {
         Self::Lit(Lit::Int(value))
     }
 }
@@ -36,7 +74,10 @@ impl fmt::Display for ExprKind {
             ExprKind::Variable(name) => write!(f, "Variable(\"{}\")", name),
             ExprKind::Unary(op, rhs) => write!(f, "Unary({}, {})", op, rhs),
             ExprKind::Binary(op, lhs, rhs) => write!(f, "Binary({}, {}, {})", op, lhs, rhs),
-            ExprKind::Call(fn_name, args) => write!(f, "Call({}, {:?})", fn_name, args),
+            ExprKind::Call(callee, args) => write!(f, "Call({}, {:?})", callee, args),
+            ExprKind::MethodCall(receiver, method, args) => {
+                write!(f, "MethodCall({}, {}, {:?})", receiver, method, args)
+            }
             ExprKind::Cond(condition, then_branch, None) => {
                 write!(f, "Cond({}, {:?})", condition, then_branch)
             }
@@ -45,7 +86,21 @@ impl fmt::Display for ExprKind {
                 "Cond({}, {:?}, {:?})",
                 condition, then_branch, else_branch
             ),
-            ExprKind::Assign(var_name, rhs) => write!(f, "Assign({}, {})", var_name, rhs),
+            ExprKind::Assign(target, rhs) => write!(f, "Assign({}, {})", target, rhs),
+            ExprKind::Cast(value, ty) => write!(f, "Cast({}, {})", value, ty),
+            ExprKind::StructLit(name, fields) => write!(f, "StructLit({}, {:?})", name, fields),
+            ExprKind::Field(base, field) => write!(f, "Field({}, {})", base, field),
+            ExprKind::Tuple(elems) => write!(f, "Tuple({:?})", elems),
+            ExprKind::Array(elems) => write!(f, "Array({:?})", elems),
+            ExprKind::Index(base, index) => write!(f, "Index({}, {})", base, index),
+            ExprKind::Variant(enum_name, variant, args) => {
+                write!(f, "Variant({}, {}, {:?})", enum_name, variant, args)
+            }
+            ExprKind::Match(scrutinee, arms) => write!(f, "Match({}, {:?})", scrutinee, arms),
+            ExprKind::Lambda(params, ret, body) => {
+                write!(f, "Lambda({:?}, {}, {:?})", params, ret, body)
+            }
+            ExprKind::Error => "Error".fmt(f),
         }
     }
 }
@@ -77,9 +132,48 @@ impl Expr {
         }
     }
 
-    /// Returns `true` if the expr_kind is [`Cond`].
+    /// Returns `true` if the expr_kind is [`Cond`] or [`Match`], which end with a block and don't
+    /// need a semicolon after them.
     pub fn is_cond(&self) -> bool {
-        matches!(self.kind, ExprKind::Cond(..))
+        matches!(self.kind, ExprKind::Cond(..) | ExprKind::Match(..))
+    }
+
+    /// Returns `true` if the expression refers to a location that can be assigned to, like a
+    /// variable, a field of a variable, an element of an array or what a pointer points to
+    pub fn is_place(&self) -> bool {
+        match &self.kind {
+            ExprKind::Variable(_) | ExprKind::Unary(UnOp::Deref, _) => true,
+            ExprKind::Field(base, _) | ExprKind::Index(base, _) => base.is_place(),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if evaluating the expression can change the value of a variable
+    pub fn has_side_effects(&self) -> bool {
+        match &self.kind {
+            // creating a closure only copies the variables it captures
+            ExprKind::Lit(_) | ExprKind::Variable(_) | ExprKind::Lambda(..) | ExprKind::Error => {
+                false
+            }
+            ExprKind::Unary(_, rhs) | ExprKind::Cast(rhs, _) | ExprKind::Field(rhs, _) => {
+                rhs.has_side_effects()
+            }
+            ExprKind::Binary(_, lhs, rhs) | ExprKind::Index(lhs, rhs) => {
+                lhs.has_side_effects() || rhs.has_side_effects()
+            }
+            ExprKind::Tuple(elems) | ExprKind::Array(elems) | ExprKind::Variant(_, _, elems) => {
+                elems.iter().any(|elem| elem.has_side_effects())
+            }
+            ExprKind::StructLit(_, fields) => {
+                fields.iter().any(|(_, value)| value.has_side_effects())
+            }
+            // calls and conditionals can run any statement
+            ExprKind::Call(..)
+            | ExprKind::MethodCall(..)
+            | ExprKind::Cond(..)
+            | ExprKind::Match(..)
+            | ExprKind::Assign(..) => true,
+        }
     }
 
     #[inline]
@@ -1,29 +1,69 @@
 use crate::name::Name;
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Lit {
-    Int(i64),
+    Int(u64),
+    Float(f64),
     Str(Name),
     Char(char),
+    Bool(bool),
 }
 
 impl fmt::Display for Lit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Int(num) => num.fmt(f),
-            Self::Str(string) => write!(f, "\"{}\"", string),
-            Self::Char(c) => c.fmt(f),
+            // `{:?}` always includes a decimal point or an exponent
+            Self::Float(num) => write!(f, "{num:?}"),
+            Self::Str(string) => write!(f, "\"{}\"", escape(string)),
+            Self::Char(c) => write!(f, "'{}'", escape(c.encode_utf8(&mut [0; 4]))),
+            Self::Bool(b) => b.fmt(f),
         }
     }
 }
 
-impl From<i64> for Lit {
-    fn from(int: i64) -> Self {
+/// Escapes a string so that it can be written between quotes in kip source code or intermediate
+/// code, control characters become escape sequences and everything else is kept as is
+pub fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' | '"' | '\'' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02x}", c as u8)),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+impl From<u64> for Lit {
+    fn from(int: u64) -> Self {
         Self::Int(int)
     }
 }
 
+impl From<f64> for Lit {
+    fn from(float: f64) -> Self {
+        Self::Float(float)
+    }
+}
+
+impl From<bool> for Lit {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
 impl From<&str> for Lit {
     fn from(string: &str) -> Self {
         Self::Str(Name::from(string))
@@ -89,9 +89,15 @@ impl fmt::Display for BinOp {
 pub enum UnOp {
     Not,
     Neg,
-    // TODO:
-    //  Move,
-    //  Clone
+    /// `&x`, the address of a place
+    Ref,
+    /// `*p`, the place a pointer points to
+    Deref,
+    /// `move x`, gives away the value of a variable, which can't be used again until it's
+    /// assigned a new value
+    Move,
+    /// `clone x`, a copy of a value that is owned separately from the original
+    Clone,
 }
 
 impl fmt::Display for UnOp {
@@ -99,6 +105,10 @@ impl fmt::Display for UnOp {
         match self {
             Self::Not => '!'.fmt(f),
             Self::Neg => '-'.fmt(f),
+            Self::Ref => '&'.fmt(f),
+            Self::Deref => '*'.fmt(f),
+            Self::Move => "move ".fmt(f),
+            Self::Clone => "clone ".fmt(f),
         }
     }
 }
@@ -0,0 +1,101 @@
+//! Patterns, which the arms of a `match` compare a value against
+
+use super::lit::Lit;
+use super::region::Region;
+use super::stmt::Block;
+use crate::name::Name;
+
+use std::fmt;
+
+/// pattern -> '_' | IDENTIFIER | '-'? literal | IDENTIFIER "::" IDENTIFIER ( '(' pattern,* ')' )?
+///          | '(' pattern ( ',' pattern )+ ')'
+#[derive(Debug, PartialEq)]
+pub enum PatternKind {
+    /// `_` matches any value
+    Wildcard,
+    /// matches any value and binds it to a new variable
+    Binding(Name),
+    /// matches values equal to the literal, `negative` is `true` for negated literals (`-1`)
+    Lit { lit: Lit, negative: bool },
+    /// matches a variant of an enum whose payload matches the patterns
+    /// example: 'Shape::Rect(w, _)'
+    Variant(Name, Name, Vec<Pattern>),
+    /// matches a tuple whose elements match the patterns
+    /// example: '(0, b)'
+    Tuple(Vec<Pattern>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    // the source code region where the pattern appears
+    pub region: Region,
+}
+
+impl Pattern {
+    /// Calls `f` with every variable the pattern binds, in the order they appear
+    pub fn for_each_binding(&self, f: &mut impl FnMut(Name)) {
+        match &self.kind {
+            PatternKind::Binding(name) => f(*name),
+            PatternKind::Variant(_, _, fields) | PatternKind::Tuple(fields) => {
+                fields.iter().for_each(|field| field.for_each_binding(f))
+            }
+            PatternKind::Wildcard | PatternKind::Lit { .. } => {}
+        }
+    }
+
+    /// Returns `true` if the pattern matches every value of its type, only bindings, `_` and
+    /// tuples of them do
+    pub fn is_irrefutable(&self) -> bool {
+        match &self.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => true,
+            PatternKind::Tuple(elems) => elems.iter().all(Pattern::is_irrefutable),
+            PatternKind::Lit { .. } | PatternKind::Variant(..) => false,
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            PatternKind::Wildcard => "_".fmt(f),
+            PatternKind::Binding(name) => name.fmt(f),
+            PatternKind::Lit { lit, negative } => {
+                let sign = if *negative { "-" } else { "" };
+                write!(f, "{sign}{lit}")
+            }
+            PatternKind::Variant(enum_name, variant, fields) => {
+                write!(f, "{enum_name}::{variant}")?;
+                if !fields.is_empty() {
+                    "(".fmt(f)?;
+                    for (idx, field) in fields.iter().enumerate() {
+                        if idx > 0 {
+                            ", ".fmt(f)?;
+                        }
+                        field.fmt(f)?;
+                    }
+                    ")".fmt(f)?;
+                }
+                Ok(())
+            }
+            PatternKind::Tuple(elems) => {
+                "(".fmt(f)?;
+                for (idx, elem) in elems.iter().enumerate() {
+                    if idx > 0 {
+                        ", ".fmt(f)?;
+                    }
+                    elem.fmt(f)?;
+                }
+                ")".fmt(f)
+            }
+        }
+    }
+}
+
+/// arm -> pattern "=>" block ','?
+#[derive(Debug, PartialEq)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub body: Block,
+    pub region: Region,
+}
@@ -15,7 +15,7 @@ pub struct LineColumn {
 }
 
 /// A region of a source file
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Region {
     start: usize,
     len: usize,
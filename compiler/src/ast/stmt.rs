@@ -1,6 +1,7 @@
 use super::expr::Expr;
+use super::pattern::Pattern;
 use super::region::Region;
-use super::ty::Type;
+use super::ty::{self, Type};
 use crate::name::Name;
 
 pub type Block = Vec<Box<Stmt>>;
@@ -13,17 +14,35 @@ pub type Module = Vec<Box<Stmt>>;
 pub enum StmtKind {
     /// expr_stmt -> expression ';'?
     Expr(Box<Expr>),
-    /// ret_stmt -> "ret" expression ';'
-    Ret(Box<Expr>),
+    /// ret_stmt -> "ret" expression? ';'
+    Ret(Option<Box<Expr>>),
     /// var_decl -> "var" IDENTIFIER ( '=' expression)? ';'
     Var(Name, Box<Expr>),
+    /// var_decl -> "var" pattern '=' expression ';'
+    /// Declares the variables that a tuple pattern binds, the pattern always matches
+    Destructure(Pattern, Box<Expr>),
+    /// const_decl -> "const" IDENTIFIER type_annotation '=' expression ';'
+    Const(Name, Type, Box<Expr>),
     /// block -> '{' declaration* '}'
-    Block(Vec<Box<Stmt>>),
-    /// func ->
+    Block(Block),
+    /// func -> "func" func_proto block
     Func(FuncProto, Block),
     /// extern_func ->
     Extern(FuncProto),
+    /// struct_decl -> "struct" IDENTIFIER type_params? '{' field_list? '}'
+    Struct(StructDef),
+    /// enum_decl -> "enum" IDENTIFIER '{' variant,* '}'
+    Enum(EnumDef),
+    /// type_decl -> ( "type" | "newtype" ) IDENTIFIER '=' type ';'
+    /// The parser replaces the name with the type it stands for wherever it's used in the module:
+    /// the aliased type, or a [`Type::Newtype`]. The region is the region of the type
+    Type(Name, Type, Region),
+    /// impl_block -> "impl" IDENTIFIER '{' func_decl* '}'
+    /// The methods of a type, every statement is a [`StmtKind::Func`] named with [`method_name`]
+    Impl(Name, Block),
     Impt(Name),
+    /// A declaration with a syntax error, the parser has reported it and skipped its tokens
+    Error,
 }
 
 #[derive(Debug, PartialEq)]
@@ -40,18 +59,120 @@ impl Stmt {
 }
 
 /// A function prototype.
-/// Captures the function's name and its parameters
+/// Captures the function's name, its type parameters and its parameters
 #[derive(Debug, PartialEq, Clone)]
 pub struct FuncProto {
     pub name: Name,
+    pub generics: Vec<Name>,
     pub params: Vec<Param>,
+    /// `true` if any number of arguments can follow the parameters (`...`), only extern functions
+    /// can be variadic
+    pub variadic: bool,
     pub ret: Type,
+    /// where the return type is written, the ')' of a function that returns nothing
+    pub ret_region: Region,
     pub region: Region,
 }
 
+/// The name of a function in an `impl` block, `Point::len` for `len` in `impl Point`.
+/// Methods are called like any other function by that name, with `self` as their first parameter
+pub fn method_name(ty: Name, method: Name) -> Name {
+    crate::name::name(&format!("{ty}::{method}"))
+}
+
+/// A struct declaration.
+/// Captures the struct's name, its type parameters and its fields in the order they were declared
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructDef {
+    pub name: Name,
+    pub generics: Vec<Name>,
+    pub fields: Vec<Field>,
+}
+
+impl FuncProto {
+    /// The prototype of an instance of a generic function, which is called `name` and has the
+    /// type parameters replaced by `args`
+    pub fn instantiate(&self, name: Name, args: &[Type]) -> Self {
+        let args = ty::bind(&self.generics, args);
+        Self {
+            name,
+            generics: Vec::new(),
+            params: self
+                .params
+                .iter()
+                .map(|param| Param {
+                    ty: param.ty.subst(&args),
+                    ..*param
+                })
+                .collect(),
+            variadic: self.variadic,
+            ret: self.ret.subst(&args),
+            ret_region: self.ret_region,
+            region: self.region,
+        }
+    }
+}
+
+impl StructDef {
+    /// Finds the field with the given name
+    pub fn field(&self, name: Name) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// The struct with its type parameters replaced by `args`
+    pub fn instantiate(&self, args: &[Type]) -> Self {
+        let args = ty::bind(&self.generics, args);
+        Self {
+            name: self.name,
+            generics: Vec::new(),
+            fields: self
+                .fields
+                .iter()
+                .map(|field| Field {
+                    name: field.name,
+                    ty: field.ty.subst(&args),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// An enum declaration (a tagged union).
+/// Captures the enum's name and its variants in the order they were declared, a variant's tag is
+/// its index
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumDef {
+    pub name: Name,
+    pub variants: Vec<Variant>,
+}
+
+impl EnumDef {
+    /// Finds the index of the variant with the given name
+    pub fn variant(&self, name: Name) -> Option<usize> {
+        self.variants
+            .iter()
+            .position(|variant| variant.name == name)
+    }
+}
+
+/// variant -> IDENTIFIER ( '(' type,* ')' )?
+#[derive(Debug, PartialEq, Clone)]
+pub struct Variant {
+    pub name: Name,
+    pub fields: Vec<Type>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Field {
+    pub name: Name,
+    pub ty: Type,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Param {
     pub name: Name,
     // 'type' is a reserved word in rust :(
     pub ty: Type,
+    /// where the type is written, the name of a bare `self`
+    pub ty_region: Region,
 }
@@ -1,38 +1,302 @@
 use crate::name::Name;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Mutex;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Type {
     // Primitve Types
     /// Integer types
     /// examples: 'u8', 's16', 'u32', 's64'
     Int { signed: bool, size: IntSize },
+    /// Floating-point types (IEEE 754)
+    /// examples: 'float32', 'float64'
+    Float(FloatSize),
     /// Boolean type (true and false)
     Bool,
+    /// A Unicode scalar value, stored as its code point in 4 bytes. Strings are UTF-8, so a
+    /// `char` can take up more than one byte of a string
+    Char,
+    /// String type, the type of string literals
+    Str,
     /// 'void' (nothing/empty)
     Void,
+    /// A fixed number of values of the same type, stored one after the other
+    /// example: '[int32; 4]'
+    Array(&'static Type, u64),
+    /// A view into an array whose length is only known at runtime, a pointer to the first element
+    /// and the number of elements
+    /// example: '[int32]'
+    Slice(&'static Type),
+    /// The address of a value
+    /// example: '*int32'
+    Ptr(&'static Type),
+
+    /// Two or more values of any types, laid out like the fields of a struct
+    /// example: '(int32, bool)'
+    Tuple(&'static [Type]),
+
+    /// A function or a closure, the types of its parameters and its return type
+    /// example: 'func(int32, bool): int32'
+    Func(&'static [Type], &'static Type),
+
+    /// A generic struct and the types it's instantiated with
+    /// example: 'Pair<int32, bool>'
+    Generic(Name, &'static [Type]),
 
-    /// Other types
+    /// A distinct type declared with `newtype`, that has the values of the scalar type it wraps
+    /// but only converts to and from it with `as`
+    /// example: 'UserId' for 'newtype UserId = int64;'
+    Newtype(Name, &'static Type),
+
+    /// Other types, and the type parameters of generic functions and structs
     Name(Name),
 }
 
 impl Type {
-    // Returns true if the type is a primitive type
-    // Primitve types include void, integers, and booleans
-    fn is_primitive(&self) -> bool {
-        !matches!(self, Self::Name(_))
+    /// The type of integer literals that aren't constrained by how they are used
+    pub const DEFAULT_INT: Type = Type::Int {
+        signed: true,
+        size: IntSize(32),
+    };
+
+    /// The type of bytes
+    pub const UINT8: Type = Type::Int {
+        signed: false,
+        size: IntSize(8),
+    };
+
+    /// The type of code points, which `char`s are converted to and from
+    pub const UINT32: Type = Type::Int {
+        signed: false,
+        size: IntSize(32),
+    };
+
+    /// The type of indices and lengths of arrays and slices
+    pub const UINT64: Type = Type::Int {
+        signed: false,
+        size: IntSize(64),
+    };
+
+    /// The type of float literals that aren't constrained by how they are used
+    pub const DEFAULT_FLOAT: Type = Type::Float(FloatSize::F64);
+
+    /// Returns `true` if the type is [`Int`](Type::Int).
+    pub fn is_int(&self) -> bool {
+        matches!(self, Self::Int { .. })
+    }
+
+    /// Returns `true` if the type is [`Float`](Type::Float).
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::Float(_))
+    }
+
+    /// Returns `true` if arithmetic can be performed on the type
+    pub fn is_number(&self) -> bool {
+        self.is_int() || self.is_float()
+    }
+
+    /// Returns `true` if a value of this type can be converted to `ty` with `as`
+    pub fn can_cast_to(&self, ty: &Type) -> bool {
+        matches!(
+            (self, ty),
+            (Self::Int { .. } | Self::Bool | Self::Char, Self::Int { .. })
+                | (Self::Int { .. } | Self::Float(_), Self::Float(_))
+                | (Self::Float(_), Self::Int { .. })
+                | (Self::Char, Self::Char)
+        ) || (matches!(*self, Self::UINT8 | Self::UINT32) && *ty == Self::Char)
+            || matches!(
+                (self, ty),
+                (Self::Newtype(_, inner), other) | (other, Self::Newtype(_, inner)) if **inner == *other
+            )
+    }
+
+    /// Returns `true` if a value of the type fits in a single variable, the types that newtypes
+    /// can wrap
+    pub fn is_scalar(&self) -> bool {
+        matches!(
+            self,
+            Self::Int { .. }
+                | Self::Float(_)
+                | Self::Bool
+                | Self::Char
+                | Self::Str
+                | Self::Ptr(_)
+                | Self::Newtype(..)
+        )
+    }
+
+    /// Replaces the type parameters in the type with the types they stand for
+    pub fn subst(self, args: &HashMap<Name, Type>) -> Type {
+        match self {
+            _ if args.is_empty() => self,
+            Self::Name(name) => args.get(&name).copied().unwrap_or(self),
+            Self::Array(elem, len) => Self::Array(intern(elem.subst(args)), len),
+            Self::Slice(elem) => Self::Slice(intern(elem.subst(args))),
+            Self::Ptr(pointee) => Self::Ptr(intern(pointee.subst(args))),
+            Self::Tuple(elems) => {
+                Self::Tuple(intern_list(elems.iter().map(|ty| ty.subst(args)).collect()))
+            }
+            Self::Func(params, ret) => Self::Func(
+                intern_list(params.iter().map(|ty| ty.subst(args)).collect()),
+                intern(ret.subst(args)),
+            ),
+            Self::Generic(name, tys) => Self::Generic(
+                name,
+                intern_list(tys.iter().map(|ty| ty.subst(args)).collect()),
+            ),
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int { signed: true, size } => write!(f, "int{}", size.bits()),
+            Self::Int {
+                signed: false,
+                size,
+            } => write!(f, "uint{}", size.bits()),
+            Self::Float(size) => write!(f, "float{}", size.bits()),
+            Self::Bool => "bool".fmt(f),
+            Self::Char => "char".fmt(f),
+            Self::Str => "str".fmt(f),
+            Self::Void => "void".fmt(f),
+            Self::Array(elem, len) => write!(f, "[{elem}; {len}]"),
+            Self::Slice(elem) => write!(f, "[{elem}]"),
+            Self::Ptr(pointee) => write!(f, "*{pointee}"),
+            Self::Tuple(elems) => {
+                "(".fmt(f)?;
+                for (idx, elem) in elems.iter().enumerate() {
+                    if idx > 0 {
+                        ", ".fmt(f)?;
+                    }
+                    elem.fmt(f)?;
+                }
+                ")".fmt(f)
+            }
+            Self::Func(params, ret) => {
+                "func(".fmt(f)?;
+                for (idx, param) in params.iter().enumerate() {
+                    if idx > 0 {
+                        ", ".fmt(f)?;
+                    }
+                    param.fmt(f)?;
+                }
+                ")".fmt(f)?;
+                match ret {
+                    Self::Void => Ok(()),
+                    _ => write!(f, ": {ret}"),
+                }
+            }
+            Self::Generic(name, args) => {
+                write!(f, "{name}<")?;
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        ", ".fmt(f)?;
+                    }
+                    arg.fmt(f)?;
+                }
+                ">".fmt(f)
+            }
+            Self::Name(name) | Self::Newtype(name, _) => name.fmt(f),
+        }
+    }
+}
+
+lazy_static! {
+    static ref TYPES: Mutex<HashSet<&'static Type>> = Mutex::new(HashSet::new());
+    static ref TYPE_LISTS: Mutex<HashSet<&'static [Type]>> = Mutex::new(HashSet::new());
+}
+
+/// Interns a type so that types containing other types (like arrays) can still be `Copy`.
+/// Interned types live for the rest of the program, like interned names
+pub fn intern(ty: Type) -> &'static Type {
+    let mut types = TYPES.lock().unwrap();
+    match types.get(&ty) {
+        Some(ty) => ty,
+        None => {
+            let ty = Box::leak(Box::new(ty));
+            types.insert(ty);
+            ty
+        }
+    }
+}
+
+/// Pairs the type parameters of a generic function or struct with the types they are instantiated
+/// with
+pub fn bind(params: &[Name], args: &[Type]) -> HashMap<Name, Type> {
+    params.iter().copied().zip(args.iter().copied()).collect()
+}
+
+/// Interns a list of types, like the type arguments of a generic struct
+pub fn intern_list(tys: Vec<Type>) -> &'static [Type] {
+    let mut lists = TYPE_LISTS.lock().unwrap();
+    match lists.get(tys.as_slice()) {
+        Some(tys) => tys,
+        None => {
+            let tys = Vec::leak(tys);
+            lists.insert(tys);
+            tys
+        }
     }
 }
 
 /// can be 8, 16, 32, and 64
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct IntSize(u8);
 
 impl IntSize {
-    pub fn new(size: u8) -> Self {
-        if [8, 16, 32, 64].contains(&size) {
-            Self(size)
-        } else {
-            panic!("invalid integer size")
+    /// Returns `None` if `bits` isn't a valid integer size
+    pub fn new(bits: u8) -> Option<Self> {
+        [8, 16, 32, 64].contains(&bits).then_some(Self(bits))
+    }
+
+    /// Get the integer size in bits.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FloatSize {
+    F32,
+    F64,
+}
+
+impl FloatSize {
+    /// Returns `None` if `bits` isn't a valid float size
+    pub fn new(bits: u8) -> Option<Self> {
+        match bits {
+            32 => Some(Self::F32),
+            64 => Some(Self::F64),
+            _ => None,
         }
     }
+
+    /// Get the float size in bits.
+    pub fn bits(self) -> u8 {
+        match self {
+            Self::F32 => 32,
+            Self::F64 => 64,
+        }
+    }
+}
+
+/// Returns `true` if an integer literal fits in the integer type.
+/// `negative` is `true` if the literal is negated (`-128`), because a signed integer can hold
+/// one more negative value than it can hold positive values
+pub fn int_lit_fits(value: u64, negative: bool, signed: bool, size: IntSize) -> bool {
+    let bits = u32::from(size.bits());
+    let max = match (signed, negative) {
+        (false, false) => u64::MAX >> (64 - bits),
+        // the only unsigned value that can be negated is zero
+        (false, true) => 0,
+        (true, false) => (1 << (bits - 1)) - 1,
+        (true, true) => 1 << (bits - 1),
+    };
+
+    value <= max
 }
@@ -4,7 +4,9 @@
 use super::expr::ExprKind;
 use super::lit::Lit;
 use super::op::{BinOp, UnOp};
-use super::stmt::{Block, FuncProto, StmtKind};
+use super::pattern::{Arm, Pattern};
+use super::stmt::{Block, EnumDef, FuncProto, Param, StmtKind, StructDef};
+use super::ty::Type;
 use super::Region;
 use super::{expr::Expr, stmt::Stmt};
 use crate::name::Name;
@@ -14,7 +16,14 @@ pub trait ExprVisitor<T> {
     fn visit_variable_expr(&mut self, name: Name, region: Region) -> T;
     fn visit_unary_expr(&mut self, op: UnOp, rhs: &Expr, region: Region) -> T;
     fn visit_binary_expr(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, region: Region) -> T;
-    fn visit_call_expr(&mut self, func_name: Name, params: &[Box<Expr>], region: Region) -> T;
+    fn visit_call_expr(&mut self, callee: &Expr, args: &[Box<Expr>], region: Region) -> T;
+    fn visit_method_call_expr(
+        &mut self,
+        receiver: &Expr,
+        method: Name,
+        args: &[Box<Expr>],
+        region: Region,
+    ) -> T;
     fn visit_cond_expr(
         &mut self,
         condition: &Expr,
@@ -22,17 +31,46 @@ pub trait ExprVisitor<T> {
         else_branch: Option<&Block>,
         region: Region,
     ) -> T;
-    fn visit_assign_expr(&mut self, var_name: Name, value: &Expr, region: Region) -> T;
+    fn visit_assign_expr(&mut self, target: &Expr, value: &Expr, region: Region) -> T;
+    fn visit_cast_expr(&mut self, value: &Expr, ty: Type, region: Region) -> T;
+    fn visit_struct_lit_expr(
+        &mut self,
+        name: Name,
+        fields: &[(Name, Box<Expr>)],
+        region: Region,
+    ) -> T;
+    fn visit_field_expr(&mut self, base: &Expr, field: Name, region: Region) -> T;
+    fn visit_tuple_expr(&mut self, elems: &[Box<Expr>], region: Region) -> T;
+    fn visit_array_expr(&mut self, elems: &[Box<Expr>], region: Region) -> T;
+    fn visit_index_expr(&mut self, base: &Expr, index: &Expr, region: Region) -> T;
+    fn visit_variant_expr(
+        &mut self,
+        enum_name: Name,
+        variant: Name,
+        args: &[Box<Expr>],
+        region: Region,
+    ) -> T;
+    fn visit_match_expr(&mut self, scrutinee: &Expr, arms: &[Arm], region: Region) -> T;
+    fn visit_lambda_expr(&mut self, params: &[Param], ret: Type, body: &Block, region: Region)
+        -> T;
+    fn visit_error_expr(&mut self, region: Region) -> T;
 }
 
 pub trait StmtVisitor<T> {
     fn visit_expr_stmt(&mut self, expr: &Expr, region: Region) -> T;
-    fn visit_ret_stmt(&mut self, value: &Expr, region: Region) -> T;
+    fn visit_ret_stmt(&mut self, value: Option<&Expr>, region: Region) -> T;
     fn visit_var_stmt(&mut self, name: Name, init: &Expr, region: Region) -> T;
+    fn visit_destructure_stmt(&mut self, pattern: &Pattern, init: &Expr, region: Region) -> T;
+    fn visit_const(&mut self, name: Name, ty: Type, value: &Expr, region: Region) -> T;
     fn visit_block(&mut self, stmts: &[Box<Stmt>]) -> T;
     fn visit_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>], region: Region) -> T;
     fn visit_extern(&mut self, proto: &FuncProto, region: Region) -> T;
+    fn visit_struct(&mut self, def: &StructDef, region: Region) -> T;
+    fn visit_enum(&mut self, def: &EnumDef, region: Region) -> T;
+    fn visit_type(&mut self, name: Name, ty: Type, region: Region) -> T;
+    fn visit_impl(&mut self, ty: Name, methods: &[Box<Stmt>], region: Region) -> T;
     fn visit_impt(&mut self, symbol: Name, region: Region) -> T;
+    fn visit_error_stmt(&mut self, region: Region) -> T;
 }
 
 pub fn walk_expr<T>(v: &mut impl ExprVisitor<T>, e: &Expr) -> T {
@@ -41,22 +79,44 @@ pub fn walk_expr<T>(v: &mut impl ExprVisitor<T>, e: &Expr) -> T {
         ExprKind::Variable(sym) => v.visit_variable_expr(*sym, e.region),
         ExprKind::Unary(op, rhs) => v.visit_unary_expr(*op, rhs, e.region),
         ExprKind::Binary(op, lhs, rhs) => v.visit_binary_expr(*op, lhs, rhs, e.region),
-        ExprKind::Call(sym, args) => v.visit_call_expr(*sym, args, e.region),
+        ExprKind::Call(callee, args) => v.visit_call_expr(callee, args, e.region),
+        ExprKind::MethodCall(receiver, method, args) => {
+            v.visit_method_call_expr(receiver, *method, args, e.region)
+        }
         ExprKind::Cond(condition, then_branch, else_branch) => {
             v.visit_cond_expr(condition, then_branch, else_branch.as_ref(), e.region)
         }
-        ExprKind::Assign(variable, expr) => v.visit_assign_expr(*variable, expr, e.region),
+        ExprKind::Assign(target, expr) => v.visit_assign_expr(target, expr, e.region),
+        ExprKind::Cast(value, ty) => v.visit_cast_expr(value, *ty, e.region),
+        ExprKind::StructLit(name, fields) => v.visit_struct_lit_expr(*name, fields, e.region),
+        ExprKind::Field(base, field) => v.visit_field_expr(base, *field, e.region),
+        ExprKind::Tuple(elems) => v.visit_tuple_expr(elems, e.region),
+        ExprKind::Array(elems) => v.visit_array_expr(elems, e.region),
+        ExprKind::Index(base, index) => v.visit_index_expr(base, index, e.region),
+        ExprKind::Variant(enum_name, variant, args) => {
+            v.visit_variant_expr(*enum_name, *variant, args, e.region)
+        }
+        ExprKind::Match(scrutinee, arms) => v.visit_match_expr(scrutinee, arms, e.region),
+        ExprKind::Lambda(params, ret, body) => v.visit_lambda_expr(params, *ret, body, e.region),
+        ExprKind::Error => v.visit_error_expr(e.region),
     }
 }
 
 pub fn walk_stmt<T>(v: &mut impl StmtVisitor<T>, s: &Stmt) -> T {
     match &s.kind {
         StmtKind::Expr(expr) => v.visit_expr_stmt(expr, s.region),
-        StmtKind::Ret(value) => v.visit_expr_stmt(value, s.region),
+        StmtKind::Ret(value) => v.visit_ret_stmt(value.as_deref(), s.region),
         StmtKind::Var(var, init) => v.visit_var_stmt(*var, init, s.region),
+        StmtKind::Destructure(pattern, init) => v.visit_destructure_stmt(pattern, init, s.region),
+        StmtKind::Const(name, ty, value) => v.visit_const(*name, *ty, value, s.region),
         StmtKind::Block(b) => v.visit_block(b),
         StmtKind::Func(proto, body) => v.visit_func(proto, body, s.region),
         StmtKind::Extern(proto) => v.visit_extern(proto, s.region),
+        StmtKind::Struct(def) => v.visit_struct(def, s.region),
+        StmtKind::Enum(def) => v.visit_enum(def, s.region),
+        StmtKind::Type(name, ty, region) => v.visit_type(*name, *ty, *region),
+        StmtKind::Impl(ty, methods) => v.visit_impl(*ty, methods, s.region),
         StmtKind::Impt(symbol) => v.visit_impt(*symbol, s.region),
+        StmtKind::Error => v.visit_error_stmt(s.region),
     }
 }
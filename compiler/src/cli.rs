@@ -10,7 +10,25 @@ pub struct Options {
     /// Where to output the intermediate code
     #[clap(short, long)]
     pub output: Option<PathBuf>,
-    // whether or not to optimize the code
-    #[clap(short = 'O', long, default_value_t = false)]
-    pub optimize: bool,
+    /// How much to optimize the intermediate code (`-O0`, `-O1` or `-O2`), a bare `-O` is `-O1`
+    #[clap(
+        short = 'O',
+        long = "opt-level",
+        alias = "optimize",
+        default_value_t = 0,
+        num_args = 0..=1,
+        default_missing_value = "1",
+        value_parser = clap::value_parser!(u8).range(0..=2)
+    )]
+    pub opt_level: u8,
+    /// Run these optimization passes in order instead of the ones picked by `-O`
+    #[clap(long, value_delimiter = ',')]
+    pub passes: Option<Vec<String>>,
+    /// Print the intermediate code after a pass runs, `all` prints it after every pass. Only the
+    /// passes that `-O` or `--passes` picked run, the others are warned about
+    #[clap(long, value_delimiter = ',')]
+    pub dump_after: Vec<String>,
+    /// Trap when an array or slice is indexed out of bounds
+    #[clap(long)]
+    pub bounds_checks: bool,
 }
@@ -1,8 +1,9 @@
-use crate::name::Name;
-use std::fmt;
+use super::layout::Layout;
+use crate::ast;
+use crate::name::{name, Name};
+use std::fmt::{self, Write};
 
-pub use crate::ast::BinOp;
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Instruction {
     /// ex. _L0:
     Label(Name),
@@ -17,6 +18,30 @@ pub enum Instruction {
     Arg(Primary),
     /// ex. ret x
     Ret(Option<Primary>),
+    /// A call in tail position, the callee's return value becomes the caller's return value so
+    /// backends can emit a jump instead of a call
+    /// ex. tailcall foo
+    TailCall(Name),
+    /// Declares a variable that holds a struct, backends have to keep it in memory and its fields
+    /// are accessed with [`Expr::Load`] and [`Instruction::Store`]
+    /// ex. local p, size 8, align 4
+    Local(Name, Layout),
+    /// Stores a value in a variable's memory, at a byte offset from the start of the variable
+    /// ex. p[4] := i32 x
+    Store(Name, Primary, Ty, Primary),
+    /// Stores a value at an address
+    /// ex. *_t0 := i32 x
+    StoreDeref(Primary, Ty, Primary),
+    /// Stores a value in a global's memory, at a byte offset from the start of the global
+    /// ex. @counter[0] := i32 x
+    StoreGlobal(Name, Primary, Ty, Primary),
+    /// Stops the program, used when a bounds check fails
+    /// ex. trap
+    Trap,
+    /// Jumps to the label at the index given by the value, which has to be less than the number
+    /// of labels
+    /// ex. jumptable _t0, [_L1, _L2, _L1]
+    JumpTable(Primary, &'static [Name]),
 }
 
 impl Instruction {
@@ -29,6 +54,14 @@ impl Instruction {
     pub fn is_label(&self) -> bool {
         matches!(self, Self::Label(..))
     }
+
+    /// Returns `true` if control never falls through to the next instruction
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            Self::Goto(..) | Self::Ret(..) | Self::TailCall(..) | Self::Trap | Self::JumpTable(..)
+        )
+    }
 }
 
 impl fmt::Display for Instruction {
@@ -46,11 +79,81 @@ impl fmt::Display for Instruction {
                     write!(f, "ret")
                 }
             }
+            Self::TailCall(name) => write!(f, "tailcall {}", name),
+            Self::Local(var, layout) => write!(f, "local {}, {}", var, layout),
+            Self::Store(var, offset, ty, value) => {
+                write!(f, "{}[{}] := {} {}", var, offset, ty, value)
+            }
+            Self::StoreDeref(ptr, ty, value) => write!(f, "*{} := {} {}", ptr, ty, value),
+            Self::StoreGlobal(global, offset, ty, value) => {
+                write!(f, "@{}[{}] := {} {}", global, offset, ty, value)
+            }
+            Self::Trap => "trap".fmt(f),
+            Self::JumpTable(value, labels) => {
+                write!(f, "jumptable {}, [", value)?;
+                for (idx, label) in labels.iter().enumerate() {
+                    if idx > 0 {
+                        ", ".fmt(f)?;
+                    }
+                    label.fmt(f)?;
+                }
+                "]".fmt(f)
+            }
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+/// The name of the function that initializes the globals whose initializers aren't constant,
+/// backends call it before `main`. `$` can't start a kip identifier so it can't clash with a
+/// function of the module
+pub const INIT_FUNC: &str = "$init";
+
+/// A module-level variable, kept in the data section.
+/// ex. global counter, size 4, align 4 = i32 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Global {
+    pub name: Name,
+    pub layout: Layout,
+    /// the global's initial value if it's a constant, globals without one start zeroed and are
+    /// set by [`INIT_FUNC`]
+    pub init: Option<(Ty, ConstKind)>,
+}
+
+impl fmt::Display for Global {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "global {}, {}", self.name, self.layout)?;
+        if let Some((ty, value)) = self.init {
+            write!(f, " = {} {}", ty, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the temporary with the given number
+pub fn tmp_var(idx: usize) -> Name {
+    name(&format!("_t{idx}"))
+}
+
+/// Returns `true` if the variable is a temporary created by the code generator (`_t0`, `_t1`, ...)
+pub fn is_tmp_var(var: Name) -> bool {
+    var.strip_prefix("_t")
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Formats a list of instructions, one per line with every instruction that isn't a label tabbed
+pub fn to_string(instructions: &[Instruction]) -> String {
+    let mut ic = String::new();
+    for instruction in instructions {
+        match instruction {
+            Instruction::Label(_) => writeln!(&mut ic, "{instruction}").unwrap(),
+            _ => writeln!(&mut ic, "    {instruction}").unwrap(),
+        }
+    }
+
+    ic
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Primary {
     Const(ConstKind),
     Var(Name),
@@ -65,9 +168,10 @@ impl fmt::Display for Primary {
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConstKind {
     Int(i64),
+    Float(f64),
     Str(Name),
 }
 
@@ -75,24 +179,238 @@ impl fmt::Display for ConstKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Int(value) => value.fmt(f),
-            Self::Str(string) => write!(f, "\"{}\"", string.as_str().escape_debug()),
+            // `{:?}` always includes a decimal point or an exponent, so floats can be told apart
+            // from integers
+            Self::Float(value) => write!(f, "{value:?}"),
+            Self::Str(string) => write!(f, "\"{}\"", ast::lit::escape(string)),
+        }
+    }
+}
+
+/// The binary operators of the intermediate code, these are kip's binary operators plus some
+/// that are only created by optimizations
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum BinOp {
+    Mul,
+    Div,
+    Mod,
+    Add,
+    Sub,
+    Ge,
+    Gt,
+    Lt,
+    Le,
+    Eq,
+    Ne,
+    And,
+    Or,
+    /// The `<<` operator (shift left)
+    Shl,
+    /// The `>>` operator (shift right), the vacated bits are filled with copies of the sign bit
+    /// for signed types and with zeros for unsigned types
+    Shr,
+    /// The `&` operator (bitwise and)
+    BitAnd,
+}
+
+impl From<ast::BinOp> for BinOp {
+    fn from(op: ast::BinOp) -> Self {
+        match op {
+            ast::BinOp::Mul => Self::Mul,
+            ast::BinOp::Div => Self::Div,
+            ast::BinOp::Mod => Self::Mod,
+            ast::BinOp::Add => Self::Add,
+            ast::BinOp::Sub => Self::Sub,
+            ast::BinOp::Ge => Self::Ge,
+            ast::BinOp::Gt => Self::Gt,
+            ast::BinOp::Lt => Self::Lt,
+            ast::BinOp::Le => Self::Le,
+            ast::BinOp::Eq => Self::Eq,
+            ast::BinOp::Ne => Self::Ne,
+            ast::BinOp::And => Self::And,
+            ast::BinOp::Or => Self::Or,
+        }
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mul => '*'.fmt(f),
+            Self::Div => '/'.fmt(f),
+            Self::Mod => '%'.fmt(f),
+            Self::Add => '+'.fmt(f),
+            Self::Sub => '-'.fmt(f),
+            Self::Ge => ">=".fmt(f),
+            Self::Gt => '>'.fmt(f),
+            Self::Lt => '<'.fmt(f),
+            Self::Le => "<=".fmt(f),
+            Self::Eq => "==".fmt(f),
+            Self::Ne => "!=".fmt(f),
+            Self::And => "&&".fmt(f),
+            Self::Or => "||".fmt(f),
+            Self::Shl => "<<".fmt(f),
+            Self::Shr => ">>".fmt(f),
+            Self::BitAnd => '&'.fmt(f),
         }
     }
 }
 
+/// The type of the operands of an operation, so that backends can pick the instruction with the
+/// right width and signedness.
+///
+/// Integer arithmetic wraps around at the width of its type (two's complement). Division and
+/// modulo round toward zero, division or modulo by zero is undefined. Float arithmetic follows
+/// IEEE 754, and modulo of floats has the sign of the dividend like C's `fmod`.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Ty {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Bool,
+    /// A pointer, strings are pointers to their first character. Adding an integer to a pointer
+    /// offsets it by that many bytes
+    Ptr,
+}
+
+impl Ty {
+    /// Get the type's width in bits.
+    pub fn bits(self) -> u8 {
+        match self {
+            Self::Bool => 1,
+            Self::I8 | Self::U8 => 8,
+            Self::I16 | Self::U16 => 16,
+            Self::I32 | Self::U32 | Self::F32 => 32,
+            Self::I64 | Self::U64 | Self::F64 | Self::Ptr => 64,
+        }
+    }
+
+    /// Returns `true` if the type is a signed integer
+    pub fn is_signed(self) -> bool {
+        matches!(self, Self::I8 | Self::I16 | Self::I32 | Self::I64)
+    }
+
+    /// Returns `true` if the type is a float
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+}
+
+impl From<ast::Type> for Ty {
+    fn from(ty: ast::Type) -> Self {
+        match ty {
+            ast::Type::Int { signed, size } => match (signed, size.bits()) {
+                (true, 8) => Self::I8,
+                (true, 16) => Self::I16,
+                (true, 32) => Self::I32,
+                (true, _) => Self::I64,
+                (false, 8) => Self::U8,
+                (false, 16) => Self::U16,
+                (false, 32) => Self::U32,
+                (false, _) => Self::U64,
+            },
+            ast::Type::Float(size) => match size {
+                ast::ty::FloatSize::F32 => Self::F32,
+                ast::ty::FloatSize::F64 => Self::F64,
+            },
+            ast::Type::Bool => Self::Bool,
+            // a `char` is its code point
+            ast::Type::Char => Self::U32,
+            ast::Type::Str | ast::Type::Ptr(_) => Self::Ptr,
+            ast::Type::Newtype(_, inner) => Self::from(*inner),
+            _ => panic!("`{ty}` isn't a type that operations can be performed on"),
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::I8 => "i8".fmt(f),
+            Self::I16 => "i16".fmt(f),
+            Self::I32 => "i32".fmt(f),
+            Self::I64 => "i64".fmt(f),
+            Self::U8 => "u8".fmt(f),
+            Self::U16 => "u16".fmt(f),
+            Self::U32 => "u32".fmt(f),
+            Self::U64 => "u64".fmt(f),
+            Self::F32 => "f32".fmt(f),
+            Self::F64 => "f64".fmt(f),
+            Self::Bool => "bool".fmt(f),
+            Self::Ptr => "ptr".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Expr {
     Call(Name),
-    Binary(BinOp, Primary, Primary),
+    /// Calls the function at an address, see [`Expr::FuncAddr`]
+    /// ex. _t1 := call *_t0
+    CallIndirect(Primary),
+    /// The address of a function's code
+    /// ex. _t0 := &func add
+    FuncAddr(Name),
+    /// Allocates memory that lives until the program exits, it holds the variables captured by a
+    /// closure
+    /// ex. _t0 := alloc size 8, align 4
+    Alloc(Layout),
+    /// ex. _t0 := i32 a + b
+    Binary(BinOp, Ty, Primary, Primary),
+    /// Converts a value from one type to another. Converting to a narrower integer truncates it,
+    /// converting to a wider one sign extends signed integers and zero extends unsigned ones.
+    /// Converting a float to an integer rounds toward zero and saturates at the integer's bounds,
+    /// `NaN` becomes `0`
+    /// ex. _t0 := i32 a as u8
+    Cast(Ty, Primary, Ty),
+    /// Loads a value from a variable's memory, at a byte offset from the start of the variable
+    /// ex. _t0 := i32 p[4]
+    Load(Ty, Name, Primary),
+    /// The address of a variable's memory, only variables declared with
+    /// [`Instruction::Local`] have an address
+    /// ex. _t0 := &a
+    Addr(Name),
+    /// Loads a value from an address
+    /// ex. _t1 := i32 *_t0
+    Deref(Ty, Primary),
+    /// Loads a value from a global's memory, at a byte offset from the start of the global
+    /// ex. _t0 := i32 @counter[0]
+    LoadGlobal(Ty, Name, Primary),
+    /// The address of a global's memory
+    /// ex. _t0 := &@counter
+    GlobalAddr(Name),
     Primary(Primary),
 }
 
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Binary(op, lhs, rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            Self::Binary(op, ty, lhs, rhs) => write!(f, "{} {} {} {}", ty, lhs, op, rhs),
+            Self::Cast(from, value, to) => write!(f, "{} {} as {}", from, value, to),
+            Self::Load(ty, var, offset) => write!(f, "{} {}[{}]", ty, var, offset),
+            Self::Addr(var) => write!(f, "&{}", var),
+            Self::Deref(ty, ptr) => write!(f, "{} *{}", ty, ptr),
+            Self::LoadGlobal(ty, global, offset) => write!(f, "{} @{}[{}]", ty, global, offset),
+            Self::GlobalAddr(global) => write!(f, "&@{}", global),
             Self::Primary(value) => write!(f, "{}", value),
             Self::Call(name) => write!(f, "call {}", name),
+            Self::CallIndirect(func) => write!(f, "call *{}", func),
+            Self::FuncAddr(func) => write!(f, "&func {}", func),
+            Self::Alloc(layout) => write!(f, "alloc {}", layout),
         }
     }
 }
+
+impl Expr {
+    /// Returns `true` if the expression calls a function, directly or through its address
+    pub fn is_call(&self) -> bool {
+        matches!(self, Self::Call(_) | Self::CallIndirect(_))
+    }
+}
@@ -0,0 +1,155 @@
+//! Memory layout of values: how many bytes a value of a type takes up and where each field of a
+//! struct is placed. Structs are laid out like C structs, every field is placed at the first offset
+//! after the previous field that is a multiple of the field's alignment. The elements of an array
+//! are placed one after the other, every element's size is a multiple of its alignment.
+//! An enum starts with its tag, the index of its variant as a `uint32`, and the fields of every
+//! variant are placed after the tag like the fields of a struct, so the variants overlap
+
+use crate::ast::stmt::{EnumDef, StructDef};
+use crate::ast::ty::Type;
+use crate::name::Name;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// The size of an enum's tag
+pub const TAG_SIZE: u64 = 4;
+
+/// The structs and enums of a module, their layouts depend on their declarations
+#[derive(Debug, Default)]
+pub struct TypeDefs {
+    pub structs: HashMap<Name, StructDef>,
+    pub enums: HashMap<Name, EnumDef>,
+}
+
+/// The size and alignment of a type, both in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: u64,
+    pub align: u64,
+}
+
+impl Layout {
+    /// The layout of a scalar, which is aligned to its size
+    fn scalar(size: u64) -> Self {
+        Self { size, align: size }
+    }
+
+    /// Computes the layout of a type.
+    /// Panics if the type refers to a struct or enum that doesn't exist, the type checker makes
+    /// sure every one of them exists and that none of them contain themselves
+    pub fn of(ty: Type, defs: &TypeDefs) -> Self {
+        match ty {
+            Type::Int { size, .. } => Self::scalar(u64::from(size.bits()) / 8),
+            Type::Float(size) => Self::scalar(u64::from(size.bits()) / 8),
+            Type::Bool => Self::scalar(1),
+            Type::Char => Self::scalar(4),
+            // strings are pointers to their characters
+            Type::Str | Type::Ptr(_) => Self::scalar(8),
+            Type::Void => Self { size: 0, align: 1 },
+            Type::Array(elem, len) => {
+                let elem = Self::of(*elem, defs);
+                Self {
+                    size: elem.size * len,
+                    align: elem.align,
+                }
+            }
+            // a pointer to the first element followed by the number of elements
+            Type::Slice(_) => Self { size: 16, align: 8 },
+            // a pointer to the function's code followed by a pointer to its captured variables
+            Type::Func(..) => Self { size: 16, align: 8 },
+            Type::Newtype(_, inner) => Self::of(*inner, defs),
+            Type::Tuple(elems) => record_layout(elems.iter().copied(), defs).0,
+            Type::Name(name) if defs.enums.contains_key(&name) => {
+                enum_layout(&defs.enums[&name], defs).0
+            }
+            Type::Name(_) | Type::Generic(..) => struct_layout(&struct_def(ty, defs), defs).0,
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "size {}, align {}", self.size, self.align)
+    }
+}
+
+/// Finds the declaration of a struct type. The fields of a generic struct have its type arguments in
+/// place of its type parameters
+pub fn struct_def(ty: Type, defs: &TypeDefs) -> StructDef {
+    match ty {
+        Type::Name(name) => defs.structs[&name].clone(),
+        Type::Generic(name, args) => defs.structs[&name].instantiate(args),
+        _ => unreachable!("`{ty}` isn't a struct"),
+    }
+}
+
+/// Computes the layout of a struct and the offset of each of its fields, in the order they were
+/// declared
+pub fn struct_layout(def: &StructDef, defs: &TypeDefs) -> (Layout, Vec<u64>) {
+    record_layout(def.fields.iter().map(|field| field.ty), defs)
+}
+
+/// Computes the layout of values of the types placed one after the other like the fields of a
+/// struct, and the offset of each one
+pub fn record_layout(
+    fields: impl IntoIterator<Item = Type>,
+    defs: &TypeDefs,
+) -> (Layout, Vec<u64>) {
+    let (size, align, offsets) = place_fields(0, fields, defs);
+
+    // the size is padded so that every element of an array of the struct is aligned
+    let layout = Layout {
+        size: size.next_multiple_of(align),
+        align,
+    };
+    (layout, offsets)
+}
+
+/// Computes the layout of an enum and the offsets of the fields of each of its variants, in the
+/// order they were declared
+pub fn enum_layout(def: &EnumDef, defs: &TypeDefs) -> (Layout, Vec<Vec<u64>>) {
+    let mut size = TAG_SIZE;
+    let mut align = TAG_SIZE;
+
+    let offsets = def
+        .variants
+        .iter()
+        .map(|variant| {
+            let (end, variant_align, offsets) =
+                place_fields(TAG_SIZE, variant.fields.iter().copied(), defs);
+            size = size.max(end);
+            align = align.max(variant_align);
+            offsets
+        })
+        .collect();
+
+    let layout = Layout {
+        size: size.next_multiple_of(align),
+        align,
+    };
+    (layout, offsets)
+}
+
+/// Places fields of the types one after the other, starting at the offset `start`.
+/// Returns where the last field ends, the largest alignment of the fields and the offset of each
+/// field
+fn place_fields(
+    start: u64,
+    fields: impl IntoIterator<Item = Type>,
+    defs: &TypeDefs,
+) -> (u64, u64, Vec<u64>) {
+    let mut offsets = Vec::new();
+    let mut size = start;
+    let mut align = 1;
+
+    for ty in fields {
+        let layout = Layout::of(ty, defs);
+        size = size.next_multiple_of(layout.align);
+        offsets.push(size);
+        size += layout.size;
+        align = align.max(layout.align);
+    }
+
+    (size, align, offsets)
+}
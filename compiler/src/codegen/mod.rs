@@ -3,23 +3,174 @@
 //! high-level enough that it's machine indepedent, but low level enough that it's easy (enough) to
 //! convert to assembly/machine code.
 
-/// use std::collections::HashMap;
 use crate::ast::Lit;
+use std::collections::{HashMap, HashSet};
 
-use crate::ast::stmt::{Block, FuncProto, Module, Stmt};
+use crate::ast::stmt::{method_name, Block, EnumDef, FuncProto, Module, Param, Stmt, StructDef};
+use crate::ast::ty::IntSize;
 use crate::ast::visit::*;
-use crate::ast::{BinOp, Expr, Region, UnOp};
+use crate::ast::{Arm, BinOp, Expr, ExprKind, Pattern, PatternKind, Region, StmtKind, Type, UnOp};
+use crate::consteval::{self, Value};
 use crate::name::name;
 use crate::name::Name as Symbol;
+use crate::typechk::{Instance, TypeTable};
 
 pub mod ic;
+mod layout;
 mod optimize;
+pub mod pass;
+#[cfg(test)]
+mod tests;
+mod verify;
 mod visit;
 
-use ic::Instruction;
-use optimize::elim_common_subexprs;
+use ic::{ConstKind, Instruction, Primary};
+use layout::{enum_layout, record_layout, struct_def, struct_layout, Layout, TypeDefs};
+use pass::PassManager;
 
-use std::fmt::Write;
+/// The offset of a slice's length, after the pointer to its first element
+const SLICE_LEN: u64 = 8;
+
+/// The offset of a closure's environment, the memory that holds its captured variables, after the
+/// pointer to its code
+const CLOSURE_ENV: u64 = 8;
+
+/// The hidden first parameter of a lambda, which points to its environment. `$` can't start a kip
+/// identifier so it can't clash with a parameter
+const ENV_PARAM: &str = "$env";
+
+/// A match on an integer with at least this many literal arms jumps straight to the arm with a
+/// jump table, if the literals are close enough together
+const JUMP_TABLE_MIN_CASES: usize = 4;
+
+fn int(value: i64) -> Primary {
+    Primary::Const(ConstKind::Int(value))
+}
+
+/// The name of the function that a closure made from the function `func` calls, it takes an
+/// environment like a lambda and passes its other arguments on to `func`
+fn closure_name(func: Symbol) -> Symbol {
+    name(&format!("{func}$closure"))
+}
+
+/// The constant that holds a value computed at compile time
+fn const_kind(value: Value) -> ConstKind {
+    match value {
+        // like literals, values that don't fit in an `i64` are `uint64`s with the same bits
+        Value::Int(k) => ConstKind::Int(k as i64),
+        Value::Float(k) => ConstKind::Float(k),
+        Value::Bool(b) => ConstKind::Int(b.into()),
+        Value::Str(str) => ConstKind::Str(str),
+    }
+}
+
+/// Where a value is in memory
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    /// At a byte offset from the start of a variable's memory
+    Local(Symbol, Primary),
+    /// At a byte offset from an address
+    Ptr(Primary, Primary),
+    /// At a byte offset from the start of a global's memory
+    Global(Symbol, Primary),
+}
+
+/// The value that a pattern is matched against
+#[derive(Debug, Clone, Copy)]
+enum Matched {
+    /// A scalar
+    Value(Primary),
+    /// A value in memory, the payload of an enum or a whole aggregate
+    At(Place),
+}
+
+/// Finds the variables whose address is taken in a block
+fn addressed_vars(block: &[Box<Stmt>], vars: &mut HashSet<Symbol>) {
+    fn place_var(place: &Expr) -> Option<Symbol> {
+        match &place.kind {
+            ExprKind::Variable(var) => Some(*var),
+            ExprKind::Field(base, _) | ExprKind::Index(base, _) => place_var(base),
+            _ => None,
+        }
+    }
+
+    fn visit(expr: &Expr, vars: &mut HashSet<Symbol>) {
+        match &expr.kind {
+            ExprKind::Lit(_) | ExprKind::Variable(_) | ExprKind::Error => {}
+            ExprKind::Unary(op, rhs) => {
+                if *op == UnOp::Ref {
+                    vars.extend(place_var(rhs));
+                }
+                visit(rhs, vars);
+            }
+            ExprKind::Binary(_, lhs, rhs)
+            | ExprKind::Assign(lhs, rhs)
+            | ExprKind::Index(lhs, rhs) => {
+                visit(lhs, vars);
+                visit(rhs, vars);
+            }
+            ExprKind::Cast(value, _) | ExprKind::Field(value, _) => visit(value, vars),
+            ExprKind::Call(callee, args) => {
+                visit(callee, vars);
+                args.iter().for_each(|expr| visit(expr, vars))
+            }
+            ExprKind::Tuple(exprs) | ExprKind::Array(exprs) | ExprKind::Variant(_, _, exprs) => {
+                exprs.iter().for_each(|expr| visit(expr, vars))
+            }
+            // the body of a lambda is a function of its own, and it gets copies of the variables
+            // it captures
+            ExprKind::Lambda(..) => {}
+            // a receiver whose address is passed as `self` is a struct or an enum, which are
+            // always kept in memory
+            ExprKind::MethodCall(receiver, _, args) => {
+                visit(receiver, vars);
+                args.iter().for_each(|expr| visit(expr, vars))
+            }
+            ExprKind::StructLit(_, fields) => fields.iter().for_each(|(_, expr)| visit(expr, vars)),
+            ExprKind::Cond(condition, then_block, else_block) => {
+                visit(condition, vars);
+                addressed_vars(then_block, vars);
+                if let Some(else_block) = else_block {
+                    addressed_vars(else_block, vars);
+                }
+            }
+            ExprKind::Match(scrutinee, arms) => {
+                visit(scrutinee, vars);
+                arms.iter().for_each(|arm| addressed_vars(&arm.body, vars));
+            }
+        }
+    }
+
+    for stmt in block {
+        match &stmt.kind {
+            StmtKind::Expr(expr)
+            | StmtKind::Ret(Some(expr))
+            | StmtKind::Var(_, expr)
+            | StmtKind::Destructure(_, expr) => visit(expr, vars),
+            StmtKind::Block(block) => addressed_vars(block, vars),
+            _ => {}
+        }
+    }
+}
+
+/// The value of a literal pattern as an integer, `None` if the pattern isn't a literal
+fn pattern_int(pattern: &Pattern) -> Option<i64> {
+    let PatternKind::Lit { lit, negative } = pattern.kind else {
+        return None;
+    };
+    // like literal expressions, values that don't fit in an `i64` are `uint64`s with the same bits
+    let value = match lit {
+        Lit::Int(value) => value as i64,
+        Lit::Char(c) => c as i64,
+        Lit::Bool(b) => b.into(),
+        Lit::Float(_) | Lit::Str(_) => return None,
+    };
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
 
 #[derive(Default)]
 pub struct CodeGenerator {
@@ -28,25 +179,585 @@ pub struct CodeGenerator {
     tmp_var: usize,
     // the number of temporary labels the code generator has created
     tmp_label: usize,
+    // the number of result variables the code generator has created, see
+    // [`CodeGenerator::new_result_var`]
+    result_var: usize,
+    // the prototype of the function currently being generated, used to detect self tail calls
+    current_func: Option<FuncProto>,
+    // the label after the prologue of the current function that self tail calls jump to, it's
+    // only emitted if one does. `None` if the function has no prologue, then they jump to the
+    // function's own label
+    body_label: Option<Symbol>,
+    // where the code of the current function starts in `instructions`
+    func_start: usize,
+    // the type of every expression in the function being generated, from the type checker
+    types: TypeTable,
+    // the instances of generic functions that haven't been generated yet
+    instances: Vec<Instance>,
+    // every struct and enum in the module, used to compute where fields are in memory
+    defs: TypeDefs,
+    // every function in the module, used to know what type each argument is passed as
+    funcs: HashMap<Symbol, FuncProto>,
+    // whether indexing checks that the index is in bounds
+    bounds_checks: bool,
+    // the variables of the current function whose address is taken, they are kept in memory
+    addressed: HashSet<Symbol>,
+    // the module-level variables, in the order they are declared
+    globals: Vec<ic::Global>,
+    // the code that initializes the globals whose initializers aren't constants, it becomes the
+    // body of the module-init function
+    init: Vec<Instruction>,
+    // the code of the lambdas, which are generated as functions after the rest of the module
+    lambdas: Vec<Instruction>,
+    // the number of lambdas that were generated, used to name them
+    lambda_count: usize,
+    // the functions that are used as values, each one gets a function that closures made from it
+    // call (see [`closure_name`])
+    closures: Vec<Symbol>,
 }
 
 impl CodeGenerator {
-    pub fn new() -> Self {
-        Default::default()
+    pub fn new(types: TypeTable) -> Self {
+        Self {
+            types,
+            ..Default::default()
+        }
+    }
+
+    /// Makes indexing trap when the index is out of bounds
+    pub fn bounds_checks(mut self, enabled: bool) -> Self {
+        self.bounds_checks = enabled;
+        self
     }
 
     pub fn gen(&mut self, module: &Module) {
         for stmt in module {
-            walk_stmt(self, stmt);
+            match &stmt.kind {
+                StmtKind::Struct(def) => {
+                    self.defs.structs.insert(def.name, def.clone());
+                }
+                StmtKind::Enum(def) => {
+                    self.defs.enums.insert(def.name, def.clone());
+                }
+                StmtKind::Func(proto, _) | StmtKind::Extern(proto) => {
+                    self.funcs.insert(proto.name, proto.clone());
+                }
+                StmtKind::Impl(_, methods) => {
+                    for method in methods {
+                        if let StmtKind::Func(proto, _) = &method.kind {
+                            self.funcs.insert(proto.name, proto.clone());
+                        }
+                    }
+                }
+                _ => {}
+            }
         }
 
-        self.optimize_mut()
+        // instances are called by their mangled names
+        self.instances = std::mem::take(&mut self.types.instances);
+        for instance in &self.instances {
+            let proto = self.funcs[&instance.func].instantiate(instance.name, &instance.args);
+            self.funcs.insert(instance.name, proto);
+        }
+
+        for stmt in module {
+            match &stmt.kind {
+                StmtKind::Var(name, init) => self.gen_global(*name, init),
+                StmtKind::Destructure(pattern, init) => self.gen_global_destructure(pattern, init),
+                StmtKind::Expr(_) | StmtKind::Block(_) => self.gen_init_stmt(stmt),
+                _ => {
+                    walk_stmt(self, stmt);
+                }
+            }
+        }
+
+        let lambdas = std::mem::take(&mut self.lambdas);
+        self.instructions.extend(lambdas);
+        for func in std::mem::take(&mut self.closures) {
+            self.emit_label(closure_name(func));
+            for param in self.funcs[&func].params.clone() {
+                self.emit_arg(Primary::Var(param.name));
+            }
+            self.emit_tail_call(func);
+        }
+
+        if !self.init.is_empty() {
+            self.emit_label(name(ic::INIT_FUNC));
+            let init = std::mem::take(&mut self.init);
+            self.instructions.extend(init);
+            self.emit_ret(None);
+        }
+    }
+
+    /// Adds a module-level variable to the data section. A constant initializer becomes the
+    /// global's initial value, any other initializer is evaluated by the module-init function
+    fn gen_global(&mut self, name: Symbol, init: &Expr) {
+        let ty = self.type_of(init);
+        let value = match consteval::eval(init, &self.types) {
+            Ok(value) if !Self::is_aggregate(ty) => Some((ic::Ty::from(ty), const_kind(value))),
+            _ => None,
+        };
+        self.globals.push(ic::Global {
+            name,
+            layout: Layout::of(ty, &self.defs),
+            init: value,
+        });
+        if value.is_some() {
+            return;
+        }
+
+        let outer = std::mem::take(&mut self.instructions);
+        let value = walk_expr(self, init).unwrap();
+        self.gen_store(ty, Place::Global(name, int(0)), value);
+        let init = std::mem::replace(&mut self.instructions, outer);
+        self.init.extend(init);
+    }
+
+    /// Adds a statement at the top of the module to the module-init function, which runs it in
+    /// order with the initializers of the globals
+    fn gen_init_stmt(&mut self, stmt: &Stmt) {
+        let outer = std::mem::take(&mut self.instructions);
+        walk_stmt(self, stmt);
+        let init = std::mem::replace(&mut self.instructions, outer);
+        self.init.extend(init);
+    }
+
+    /// Adds the variables of a module-level tuple pattern to the data section, the module-init
+    /// function evaluates the initializer and stores each element in its variable
+    fn gen_global_destructure(&mut self, pattern: &Pattern, init: &Expr) {
+        let outer = std::mem::take(&mut self.instructions);
+        let ty = self.type_of(init);
+        let Primary::Var(value) = walk_expr(self, init).unwrap() else {
+            unreachable!("tuples are always held in variables");
+        };
+        self.gen_global_bindings(pattern, ty, Place::Local(value, int(0)));
+        let init = std::mem::replace(&mut self.instructions, outer);
+        self.init.extend(init);
+    }
+
+    /// Declares the globals that a pattern binds and stores the parts of the value at `place` in
+    /// them
+    fn gen_global_bindings(&mut self, pattern: &Pattern, ty: Type, place: Place) {
+        match &pattern.kind {
+            PatternKind::Binding(name) => {
+                self.globals.push(ic::Global {
+                    name: *name,
+                    layout: Layout::of(ty, &self.defs),
+                    init: None,
+                });
+                let dst = Place::Global(*name, int(0));
+                if Self::is_aggregate(ty) {
+                    self.gen_copy(ty, dst, place);
+                } else {
+                    let value = self.gen_load(ty, place);
+                    self.gen_store(ty, dst, value);
+                }
+            }
+            PatternKind::Tuple(elems) => {
+                for (elem, (offset, ty)) in elems.iter().zip(self.tuple_elems(ty)) {
+                    let place = self.gen_offset_place(place, int(offset as i64));
+                    self.gen_global_bindings(elem, ty, place);
+                }
+            }
+            // the type checker only allows patterns that match every value
+            _ => {}
+        }
+    }
+
+    /// Get the type of an expression
+    fn type_of(&self, expr: &Expr) -> Type {
+        self.type_at(expr.region)
+    }
+
+    fn type_at(&self, region: Region) -> Type {
+        *self
+            .types
+            .types
+            .get(&region)
+            .expect("every expression should have been type checked")
+    }
+
+    /// Get the type of an expression as the type of an operation
+    fn ty_of(&self, expr: &Expr) -> ic::Ty {
+        ic::Ty::from(self.type_of(expr))
+    }
+
+    /// Returns `true` if values of the type are kept in memory instead of in a single variable
+    fn is_aggregate(ty: Type) -> bool {
+        matches!(
+            ty,
+            Type::Name(_)
+                | Type::Generic(..)
+                | Type::Array(..)
+                | Type::Slice(_)
+                | Type::Func(..)
+                | Type::Tuple(_)
+        )
+    }
+
+    /// The function that a call calls, a call to a generic function calls one of its instances
+    fn callee(&self, func_name: Symbol, region: Region) -> Symbol {
+        self.types
+            .callees
+            .get(&region)
+            .copied()
+            .unwrap_or(func_name)
+    }
+
+    /// The function that a call calls directly, `None` if it calls a function value
+    fn direct_callee(&self, callee: &Expr, region: Region) -> Option<Symbol> {
+        match callee.kind {
+            ExprKind::Variable(func_name) if self.types.funcs.contains(&callee.region) => {
+                Some(self.callee(func_name, region))
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds the offset and type of a field of a struct, or of the length of a slice
+    fn field(&self, ty: Type, field: Symbol) -> (u64, Type) {
+        if let Type::Slice(_) = ty {
+            return (SLICE_LEN, Type::UINT64);
+        }
+        if let Type::Tuple(_) = ty {
+            let idx: usize = field
+                .parse()
+                .expect("tuple elements are named by their index");
+            return self.tuple_elems(ty)[idx];
+        }
+        let def = struct_def(ty, &self.defs);
+        let (_, offsets) = struct_layout(&def, &self.defs);
+        let idx = def
+            .fields
+            .iter()
+            .position(|f| f.name == field)
+            .expect("the type checker makes sure fields exist");
+
+        (offsets[idx], def.fields[idx].ty)
+    }
+
+    /// Finds the offset and type of each element of a tuple
+    fn tuple_elems(&self, ty: Type) -> Vec<(u64, Type)> {
+        let Type::Tuple(elems) = ty else {
+            unreachable!("`{ty}` isn't a tuple");
+        };
+        let (_, offsets) = record_layout(elems.iter().copied(), &self.defs);
+        offsets.into_iter().zip(elems.iter().copied()).collect()
+    }
+
+    /// Finds the tag of a variant of an enum, and the offset and type of each of its fields
+    fn variant(&self, ty: Type, variant: Symbol) -> (i64, Vec<(u64, Type)>) {
+        let Type::Name(name) = ty else {
+            unreachable!("`{ty}` isn't an enum");
+        };
+        let def = &self.defs.enums[&name];
+        let (_, offsets) = enum_layout(def, &self.defs);
+        let idx = def
+            .variant(variant)
+            .expect("the type checker makes sure variants exist");
+        let fields = offsets[idx]
+            .iter()
+            .copied()
+            .zip(def.variants[idx].fields.iter().copied())
+            .collect();
+
+        (idx as i64, fields)
+    }
+
+    /// Returns `true` if the type is an enum
+    fn is_enum(&self, ty: Type) -> bool {
+        matches!(ty, Type::Name(name) if self.defs.enums.contains_key(&name))
+    }
+
+    /// Creates a temporary to hold a value of the type, aggregates are declared as locals
+    fn new_tmp_of(&mut self, ty: Type) -> Symbol {
+        let t = self.new_tmp_var();
+        self.emit_local_if_needed(t, ty);
+        t
+    }
+
+    /// Declares a variable as a local if it has to be kept in memory, because it's an aggregate or
+    /// because its address is taken
+    fn emit_local_if_needed(&mut self, var: Symbol, ty: Type) {
+        if Self::is_aggregate(ty) || self.addressed.contains(&var) {
+            let layout = Layout::of(ty, &self.defs);
+            self.instructions.push(Instruction::Local(var, layout));
+        }
+    }
+
+    /// Adds two byte offsets, constants are added right away
+    fn gen_add_offset(&mut self, lhs: Primary, rhs: Primary) -> Primary {
+        match (lhs, rhs) {
+            (Primary::Const(ConstKind::Int(l)), Primary::Const(ConstKind::Int(r))) => int(l + r),
+            (offset, zero) | (zero, offset) if zero == int(0) => offset,
+            _ => {
+                let t = self.new_tmp_var();
+                self.emit_assign_binary(t, ic::BinOp::Add, ic::Ty::U64, lhs, rhs);
+                Primary::Var(t)
+            }
+        }
+    }
+
+    /// Moves a place forward by a byte offset
+    fn gen_offset_place(&mut self, place: Place, by: Primary) -> Place {
+        match place {
+            Place::Local(var, offset) => Place::Local(var, self.gen_add_offset(offset, by)),
+            Place::Ptr(ptr, offset) => Place::Ptr(ptr, self.gen_add_offset(offset, by)),
+            Place::Global(var, offset) => Place::Global(var, self.gen_add_offset(offset, by)),
+        }
+    }
+
+    /// Finds where in memory a place expression is.
+    /// Anything that isn't a place is evaluated into a temporary first (`make().x`)
+    fn gen_place(&mut self, expr: &Expr) -> Place {
+        match &expr.kind {
+            ExprKind::Variable(var) if self.types.globals.contains(&expr.region) => {
+                Place::Global(*var, int(0))
+            }
+            ExprKind::Variable(var) => Place::Local(*var, int(0)),
+            ExprKind::Field(base, field) => {
+                let place = self.gen_place(base);
+                let (offset, _) = self.field(self.type_of(base), *field);
+                self.gen_offset_place(place, int(offset as i64))
+            }
+            ExprKind::Index(base, index) => self.gen_index_place(base, index),
+            ExprKind::Unary(UnOp::Deref, ptr) => Place::Ptr(walk_expr(self, ptr).unwrap(), int(0)),
+            _ => match walk_expr(self, expr) {
+                Some(Primary::Var(var)) => Place::Local(var, int(0)),
+                _ => unreachable!("only aggregates have fields and elements"),
+            },
+        }
     }
+
+    /// Finds where the element of an array or slice is in memory.
+    /// The elements of an array are in the array's memory, a slice points to its elements
+    fn gen_index_place(&mut self, base: &Expr, index: &Expr) -> Place {
+        let place = self.gen_place(base);
+        let index = self.gen_index(index);
+        match self.type_of(base) {
+            Type::Array(elem, len) => {
+                self.gen_bounds_check(index, int(len as i64));
+                let offset = self.gen_elem_offset(index, *elem);
+                self.gen_offset_place(place, offset)
+            }
+            Type::Slice(elem) => {
+                let ptr = self.gen_load_scalar(ic::Ty::Ptr, place);
+                let len_place = self.gen_offset_place(place, int(SLICE_LEN as i64));
+                let len = self.gen_load_scalar(ic::Ty::U64, len_place);
+                self.gen_bounds_check(index, len);
+                let offset = self.gen_elem_offset(index, *elem);
+                Place::Ptr(ptr, offset)
+            }
+            _ => unreachable!("only arrays and slices can be indexed"),
+        }
+    }
+
+    /// Evaluates an index as a `uint64`, so negative indices become too large to be in bounds
+    fn gen_index(&mut self, index: &Expr) -> Primary {
+        let from = self.ty_of(index);
+        let value = walk_expr(self, index).unwrap();
+        if from == ic::Ty::U64 || matches!(value, Primary::Const(_)) {
+            return value;
+        }
+
+        let t = self.new_tmp_var();
+        self.emit_assign(t, ic::Expr::Cast(from, value, ic::Ty::U64));
+        Primary::Var(t)
+    }
+
+    /// Computes the byte offset of the element of an array at an index
+    fn gen_elem_offset(&mut self, index: Primary, elem: Type) -> Primary {
+        let size = Layout::of(elem, &self.defs).size as i64;
+        match index {
+            Primary::Const(ConstKind::Int(index)) => int(index * size),
+            _ if size == 1 => index,
+            _ => {
+                let t = self.new_tmp_var();
+                self.emit_assign_binary(t, ic::BinOp::Mul, ic::Ty::U64, index, int(size));
+                Primary::Var(t)
+            }
+        }
+    }
+
+    /// Traps if the index isn't less than the length, when bounds checks are enabled.
+    /// Constant indices of arrays were already checked by the type checker
+    fn gen_bounds_check(&mut self, index: Primary, len: Primary) {
+        if !self.bounds_checks || matches!((index, len), (Primary::Const(_), Primary::Const(_))) {
+            return;
+        }
+
+        let out_of_bounds = self.new_tmp_var();
+        let in_bounds = self.new_tmp_label();
+        self.emit_assign_binary(out_of_bounds, ic::BinOp::Ge, ic::Ty::U64, index, len);
+        self.emit_ifz(Primary::Var(out_of_bounds), in_bounds);
+        self.instructions.push(Instruction::Trap);
+        self.emit_label(in_bounds);
+    }
+
+    /// Reads a value of the type from memory
+    fn gen_load(&mut self, ty: Type, place: Place) -> Primary {
+        if !Self::is_aggregate(ty) {
+            return self.gen_load_scalar(ic::Ty::from(ty), place);
+        }
+
+        let t = self.new_tmp_of(ty);
+        self.gen_copy(ty, Place::Local(t, int(0)), place);
+        Primary::Var(t)
+    }
+
+    fn gen_load_scalar(&mut self, ty: ic::Ty, place: Place) -> Primary {
+        let t = self.new_tmp_var();
+        let load = match place {
+            Place::Local(var, offset) => ic::Expr::Load(ty, var, offset),
+            Place::Ptr(ptr, offset) => ic::Expr::Deref(ty, self.gen_address(ptr, offset)),
+            Place::Global(var, offset) => ic::Expr::LoadGlobal(ty, var, offset),
+        };
+        self.emit_assign(t, load);
+        Primary::Var(t)
+    }
+
+    /// Writes a value of the type to memory
+    fn gen_store(&mut self, ty: Type, place: Place, value: Primary) {
+        if !Self::is_aggregate(ty) {
+            return self.gen_store_scalar(ic::Ty::from(ty), place, value);
+        }
+
+        let Primary::Var(src) = value else {
+            unreachable!("aggregates are always held in variables");
+        };
+        self.gen_copy(ty, place, Place::Local(src, int(0)));
+    }
+
+    fn gen_store_scalar(&mut self, ty: ic::Ty, place: Place, value: Primary) {
+        let store = match place {
+            Place::Local(var, offset) => Instruction::Store(var, offset, ty, value),
+            Place::Ptr(ptr, offset) => {
+                Instruction::StoreDeref(self.gen_address(ptr, offset), ty, value)
+            }
+            Place::Global(var, offset) => Instruction::StoreGlobal(var, offset, ty, value),
+        };
+        self.instructions.push(store);
+    }
+
+    /// Gets the address of a place, the place's variable was declared as a local
+    fn gen_address_of(&mut self, place: Place) -> Primary {
+        match place {
+            Place::Global(var, offset) => {
+                let t = self.new_tmp_var();
+                self.emit_assign(t, ic::Expr::GlobalAddr(var));
+                self.gen_address(Primary::Var(t), offset)
+            }
+            Place::Local(var, offset) => {
+                let t = self.new_tmp_var();
+                self.emit_assign(t, ic::Expr::Addr(var));
+                self.gen_address(Primary::Var(t), offset)
+            }
+            Place::Ptr(ptr, offset) => self.gen_address(ptr, offset),
+        }
+    }
+
+    /// Offsets a pointer by a number of bytes
+    fn gen_address(&mut self, ptr: Primary, offset: Primary) -> Primary {
+        if offset == int(0) {
+            return ptr;
+        }
+
+        let t = self.new_tmp_var();
+        self.emit_assign_binary(t, ic::BinOp::Add, ic::Ty::Ptr, ptr, offset);
+        Primary::Var(t)
+    }
+
+    /// Copies an aggregate one scalar at a time, struct fields and array elements are copied in
+    /// order. Which fields an enum has depends on its variant, so it's copied in chunks of its
+    /// alignment instead
+    fn gen_copy(&mut self, ty: Type, dst: Place, src: Place) {
+        let parts: Vec<(u64, Type)> = match ty {
+            _ if self.is_enum(ty) => {
+                let layout = Layout::of(ty, &self.defs);
+                let chunk = Type::Int {
+                    signed: false,
+                    size: IntSize::new(layout.align as u8 * 8).unwrap(),
+                };
+                (0..layout.size / layout.align)
+                    .map(|idx| (idx * layout.align, chunk))
+                    .collect()
+            }
+            Type::Name(_) | Type::Generic(..) => {
+                let def = struct_def(ty, &self.defs);
+                let (_, offsets) = struct_layout(&def, &self.defs);
+                offsets
+                    .into_iter()
+                    .zip(def.fields.iter().map(|field| field.ty))
+                    .collect()
+            }
+            Type::Array(elem, len) => {
+                let size = Layout::of(*elem, &self.defs).size;
+                (0..len).map(|idx| (idx * size, *elem)).collect()
+            }
+            Type::Tuple(_) => self.tuple_elems(ty),
+            Type::Slice(_) => {
+                let ptr = self.gen_load_scalar(ic::Ty::Ptr, src);
+                self.gen_store_scalar(ic::Ty::Ptr, dst, ptr);
+                vec![(SLICE_LEN, Type::UINT64)]
+            }
+            // a closure is two pointers
+            Type::Func(..) => {
+                for offset in [0, CLOSURE_ENV as i64] {
+                    let src = self.gen_offset_place(src, int(offset));
+                    let dst = self.gen_offset_place(dst, int(offset));
+                    let ptr = self.gen_load_scalar(ic::Ty::Ptr, src);
+                    self.gen_store_scalar(ic::Ty::Ptr, dst, ptr);
+                }
+                Vec::new()
+            }
+            _ => unreachable!("only aggregates are copied in parts"),
+        };
+
+        for (offset, ty) in parts {
+            let src = self.gen_offset_place(src, int(offset as i64));
+            let dst = self.gen_offset_place(dst, int(offset as i64));
+            if Self::is_aggregate(ty) {
+                self.gen_copy(ty, dst, src);
+            } else {
+                let value = self.gen_load(ty, src);
+                self.gen_store(ty, dst, value);
+            }
+        }
+    }
+
+    /// Evaluates an expression that is used as a value of type `ty`, an array that is used as a
+    /// slice becomes a slice of the whole array
+    fn gen_expr_as(&mut self, expr: &Expr, ty: Type) -> Primary {
+        let value = walk_expr(self, expr).unwrap();
+        let (Type::Array(_, len), Type::Slice(_)) = (self.type_of(expr), ty) else {
+            return value;
+        };
+        let Primary::Var(array) = value else {
+            unreachable!("aggregates are always held in variables");
+        };
+
+        let ptr = self.new_tmp_var();
+        self.emit_assign(ptr, ic::Expr::Addr(array));
+        let slice = self.new_tmp_of(ty);
+        self.gen_store_scalar(ic::Ty::Ptr, Place::Local(slice, int(0)), Primary::Var(ptr));
+        let len_place = Place::Local(slice, int(SLICE_LEN as i64));
+        self.gen_store_scalar(ic::Ty::U64, len_place, int(len as i64));
+        Primary::Var(slice)
+    }
+
     // returns a Symbol in the form of "_t{number}" to store temporary values
     fn new_tmp_var(&mut self) -> Symbol {
-        let tmp_var_name = format!("_t{}", self.tmp_var);
+        let tmp_var = ic::tmp_var(self.tmp_var);
         self.tmp_var += 1;
-        name(&tmp_var_name)
+        tmp_var
+    }
+
+    /// Creates a variable for the result of an expression that is assigned on more than one path,
+    /// which a temporary can't be (`$r0`, `$r1`, ...)
+    fn new_result_var(&mut self) -> Symbol {
+        let var = name(&format!("$r{}", self.result_var));
+        self.result_var += 1;
+        var
     }
 
     // returns a Symbol in the form of "_t{number}" for store temporary values
@@ -56,24 +767,66 @@ impl CodeGenerator {
         name(&label_name)
     }
 
-    // wrapper types suck fr
+    /// Copies a variable into a new temporary so that it keeps its current value.
+    /// Operands are used directly in instructions, so a variable that is read by one operand and
+    /// written by a later one would otherwise be read after the write (`a + (a = 1)`)
+    fn preserve(&mut self, value: Primary, ty: Type, later: &[&Expr]) -> Primary {
+        match value {
+            Primary::Var(_) if later.iter().any(|expr| expr.has_side_effects()) => {
+                let t = self.new_tmp_of(ty);
+                self.emit_assign_primary(t, value);
+                Primary::Var(t)
+            }
+            _ => value,
+        }
+    }
 
-    fn emit_assign_const_int(&mut self, name: Symbol, init: i64) {
-        self.emit_assign(
-            name,
-            ic::Expr::Primary(ic::Primary::Const(ic::ConstKind::Int(init))),
-        );
+    /// Generates each expression in order, as a value of the type it's paired with, and returns
+    /// their values
+    fn gen_operands(&mut self, operands: &[(&Expr, Type)]) -> Vec<Primary> {
+        let exprs: Vec<&Expr> = operands.iter().map(|(expr, _)| *expr).collect();
+        let mut values = Vec::with_capacity(exprs.len());
+        for (idx, (expr, ty)) in operands.iter().enumerate() {
+            let value = self.gen_expr_as(expr, *ty);
+            values.push(self.preserve(value, *ty, &exprs[idx + 1..]));
+        }
+
+        values
     }
 
-    fn emit_assign_const_str(&mut self, name: Symbol, init: Symbol) {
-        self.emit_assign(
-            name,
-            ic::Expr::Primary(ic::Primary::Const(ic::ConstKind::Str(init))),
-        );
+    /// Pairs the arguments of a call with the types of the parameters they are passed as, the
+    /// arguments passed to the `...` of a variadic function keep their own types
+    fn arg_types<'a>(&self, func_name: Symbol, args: &'a [Box<Expr>]) -> Vec<(&'a Expr, Type)> {
+        let params = &self.funcs[&func_name].params;
+        args.iter()
+            .enumerate()
+            .map(|(idx, arg)| {
+                let ty = params
+                    .get(idx)
+                    .map_or_else(|| self.type_of(arg), |param| param.ty);
+                (&**arg, ty)
+            })
+            .collect()
+    }
+
+    /// Applies C's default argument promotions to an argument passed to the `...` of a variadic
+    /// function: integers narrower than an `int32` and `bool`s become `int32`s, and `float32`s
+    /// become `float64`s
+    fn gen_vararg(&mut self, value: Primary, ty: Type) -> Primary {
+        let from = ic::Ty::from(ty);
+        let to = match from {
+            ic::Ty::I8 | ic::Ty::I16 | ic::Ty::U8 | ic::Ty::U16 | ic::Ty::Bool => ic::Ty::I32,
+            ic::Ty::F32 => ic::Ty::F64,
+            _ => return value,
+        };
+
+        let t = self.new_tmp_var();
+        self.emit_assign(t, ic::Expr::Cast(from, value, to));
+        Primary::Var(t)
     }
 
-    fn emit_assign_var(&mut self, name: Symbol, init: Symbol) {
-        self.emit_assign(name, ic::Expr::Primary(ic::Primary::Var(init)));
+    fn emit_assign_primary(&mut self, name: Symbol, init: Primary) {
+        self.emit_assign(name, ic::Expr::Primary(init));
     }
 
     fn emit_assign(&mut self, name: Symbol, init: ic::Expr) {
@@ -85,122 +838,670 @@ impl CodeGenerator {
             .push(Instruction::Assign(name, ic::Expr::Call(func)));
     }
 
-    fn emit_assign_binary(&mut self, name: Symbol, op: ic::BinOp, lhs: Symbol, rhs: Symbol) {
+    fn emit_assign_binary(
+        &mut self,
+        name: Symbol,
+        op: ic::BinOp,
+        ty: ic::Ty,
+        lhs: Primary,
+        rhs: Primary,
+    ) {
         self.instructions.push(Instruction::Assign(
             name,
-            ic::Expr::Binary(op, ic::Primary::Var(lhs), ic::Primary::Var(rhs)),
+            ic::Expr::Binary(op, ty, lhs, rhs),
         ));
     }
-    fn emit_arg(&mut self, name: Symbol) {
-        self.instructions
-            .push(Instruction::Arg(ic::Primary::Var(name)));
+
+    fn emit_arg(&mut self, value: Primary) {
+        self.instructions.push(Instruction::Arg(value));
     }
 
-    fn emit_ifz(&mut self, condition: Symbol, label: Symbol) {
-        self.instructions
-            .push(Instruction::Ifz(ic::Primary::Var(condition), label));
+    fn emit_ifz(&mut self, condition: Primary, label: Symbol) {
+        self.instructions.push(Instruction::Ifz(condition, label));
     }
 
     fn emit_label(&mut self, label: Symbol) {
         self.instructions.push(Instruction::Label(label));
     }
 
-    fn emit_ret(&mut self, value: Option<Symbol>) {
-        self.instructions
-            .push(Instruction::Ret(value.map(ic::Primary::Var)));
+    fn emit_ret(&mut self, value: Option<Primary>) {
+        self.instructions.push(Instruction::Ret(value));
     }
 
     fn emit_goto(&mut self, label: Symbol) {
         self.instructions.push(Instruction::Goto(label))
     }
 
-    // FIXME: i should probably do this as a display trait or something
-    pub fn intermediate_code(&self) -> String {
-        let mut ic = String::new();
-        // this tabs all instructions that arent labels
-        for instruction in &self.instructions {
-            match instruction {
-                Instruction::Label(_) => writeln!(&mut ic, "{instruction}").unwrap(),
-                _ => writeln!(&mut ic, "    {instruction}").unwrap(),
+    fn emit_tail_call(&mut self, func: Symbol) {
+        self.instructions.push(Instruction::TailCall(func))
+    }
+
+    /// Generates a block whose last statement is in tail position
+    fn gen_tail_block(&mut self, block: &[Box<Stmt>]) {
+        let Some((last, rest)) = block.split_last() else {
+            return;
+        };
+
+        self.visit_block(rest);
+        self.gen_tail_stmt(last);
+    }
+
+    /// Generates a statement in tail position, meaning nothing else in the function executes after
+    /// it. Returns are always tail calls (see [`CodeGenerator::visit_ret_stmt`]), but a trailing
+    /// expression can only be turned into a tail call when its value is discarded by a void function
+    fn gen_tail_stmt(&mut self, stmt: &Stmt) {
+        let returns_void = self
+            .current_func
+            .as_ref()
+            .is_some_and(|proto| proto.ret == Type::Void);
+
+        match &stmt.kind {
+            StmtKind::Expr(expr) => match &expr.kind {
+                ExprKind::Call(callee, args) if returns_void && self.can_tail_call() => {
+                    match self.direct_callee(callee, expr.region) {
+                        Some(func_name) => self.gen_tail_call(func_name, args, expr.region),
+                        // a function value is called like in any other position
+                        None => {
+                            walk_expr(self, expr);
+                        }
+                    }
+                }
+                ExprKind::Cond(condition, then_block, else_block) => {
+                    self.gen_cond(condition, then_block, else_block.as_ref(), true);
+                }
+                ExprKind::Match(scrutinee, arms) => self.gen_match(scrutinee, arms, true),
+                _ => {
+                    walk_expr(self, expr);
+                }
+            },
+            StmtKind::Block(block) => self.gen_tail_block(block),
+            _ => {
+                walk_stmt(self, stmt);
             }
         }
+    }
 
-        ic
+    /// Whether a call in tail position can reuse the frame of the current function. It can't once
+    /// the address of one of the function's variables is taken, the callee could still use it
+    fn can_tail_call(&self) -> bool {
+        self.addressed.is_empty()
     }
 
-    // FIXME: i should probably do this as a display trait or something
-    pub fn optimized_intermediate_code(&self) -> String {
-        let mut ic = String::new();
-        let instructions = self.optimize();
-        // this tabs all instructions that arent labels
-        for instruction in instructions {
-            match instruction {
-                Instruction::Label(_) => writeln!(&mut ic, "{instruction}").unwrap(),
-                _ => writeln!(&mut ic, "    {instruction}").unwrap(),
+    /// Returns `true` if the code of the current function takes the address of one of its
+    /// variables. Besides `&`, an array that is used as a slice has its address taken
+    fn frame_address_taken(&self) -> bool {
+        self.instructions[self.func_start..]
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::Assign(_, ic::Expr::Addr(_))))
+    }
+
+    /// Generates a call whose result is immediately returned.
+    /// A call to the function being generated becomes reassignments of its parameters followed by a
+    /// jump back to the start of the function's body, any other call is marked as a tail call. If
+    /// the arguments point into the current frame, it's an ordinary call and a return instead
+    fn gen_tail_call(&mut self, func_name: Symbol, args: &[Box<Expr>], region: Region) {
+        let params = match &self.current_func {
+            Some(proto) if proto.name == func_name && proto.params.len() == args.len() => proto
+                .params
+                .iter()
+                .map(|param| param.name)
+                .collect::<Vec<_>>(),
+            _ => {
+                self.gen_args(func_name, args);
+                if self.frame_address_taken() {
+                    self.gen_call_and_ret(func_name, region);
+                } else {
+                    self.emit_tail_call(func_name);
+                }
+                return;
+            }
+        };
+
+        // every argument has to be evaluated before any parameter is reassigned, because the
+        // arguments can refer to the parameters (`ret fact(n - 1, n * acc);`)
+        let args = self.arg_types(func_name, args);
+        let values = self.gen_operands(&args);
+        if self.frame_address_taken() {
+            for value in values {
+                self.emit_arg(value);
+            }
+            self.gen_call_and_ret(func_name, region);
+            return;
+        }
+
+        // a parameter that is passed along as a different parameter has to be copied first, or it
+        // would be overwritten before it is read (`ret gcd(b, a % b);`)
+        let values: Vec<_> = values
+            .into_iter()
+            .enumerate()
+            .map(|(idx, value)| match value {
+                Primary::Var(var) if params[..idx].contains(&var) => {
+                    let t = self.new_tmp_var();
+                    self.emit_assign_primary(t, value);
+                    Primary::Var(t)
+                }
+                _ => value,
+            })
+            .collect();
+
+        for (param, value) in params.into_iter().zip(values) {
+            // passing a parameter as itself doesn't need a reassignment
+            if value != Primary::Var(param) {
+                self.emit_assign_primary(param, value);
+            }
+        }
+
+        self.emit_goto(self.body_label.unwrap_or(func_name));
+    }
+
+    /// Calls a function whose arguments were passed and returns its result, for a call in tail
+    /// position that can't be a tail call
+    fn gen_call_and_ret(&mut self, func_name: Symbol, region: Region) {
+        let t = self.new_tmp_of(self.type_at(region));
+        self.emit_assign_call(t, func_name);
+        let returns_void = self
+            .current_func
+            .as_ref()
+            .is_none_or(|proto| proto.ret == Type::Void);
+        self.emit_ret((!returns_void).then_some(Primary::Var(t)));
+    }
+
+    /// Generates `&&` or `||`. The right operand is only evaluated when the left one doesn't decide
+    /// the result, so it can rely on it (`b != 0 && a / b > 2`)
+    fn gen_short_circuit(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr) -> Primary {
+        let result = self.new_result_var();
+        let lhs = walk_expr(self, lhs).unwrap();
+        self.emit_assign_primary(result, lhs);
+
+        let end_label = self.new_tmp_label();
+        if op == BinOp::And {
+            // `false && _` is `false`
+            self.emit_ifz(lhs, end_label);
+        } else {
+            // `true || _` is `true`
+            let rhs_label = self.new_tmp_label();
+            self.emit_ifz(lhs, rhs_label);
+            self.emit_goto(end_label);
+            self.emit_label(rhs_label);
+        }
+
+        let rhs = walk_expr(self, rhs).unwrap();
+        self.emit_assign_primary(result, rhs);
+        self.emit_label(end_label);
+        Primary::Var(result)
+    }
+
+    /// Calls a function by its name
+    fn gen_call(&mut self, func_name: Symbol, args: &[Box<Expr>], region: Region) -> Primary {
+        self.gen_args(func_name, args);
+        let t = self.new_tmp_of(self.type_at(region));
+        self.emit_assign_call(t, func_name);
+        Primary::Var(t)
+    }
+
+    /// Creates a closure that calls the code at `code` with the environment `env`
+    fn gen_closure(&mut self, ty: Type, code: Primary, env: Primary) -> Primary {
+        let t = self.new_tmp_of(ty);
+        self.gen_store_scalar(ic::Ty::Ptr, Place::Local(t, int(0)), code);
+        let env_place = Place::Local(t, int(CLOSURE_ENV as i64));
+        self.gen_store_scalar(ic::Ty::Ptr, env_place, env);
+        Primary::Var(t)
+    }
+
+    /// Evaluates the arguments of a call and passes them to it.
+    /// The `arg` instructions are emitted after every argument is evaluated, so that calls nested
+    /// inside the arguments don't get mixed up with the arguments of this call
+    fn gen_args(&mut self, func_name: Symbol, args: &[Box<Expr>]) {
+        let args = self.arg_types(func_name, args);
+        let params = self.funcs[&func_name].params.len();
+        let values = self.gen_operands(&args);
+        for (idx, (value, (_, ty))) in values.into_iter().zip(&args).enumerate() {
+            let value = if idx < params {
+                value
+            } else {
+                self.gen_vararg(value, *ty)
+            };
+            self.emit_arg(value);
+        }
+    }
+
+    /// Generates a function, or an instance of a generic function with its instantiated prototype.
+    /// The body of a lambda starts by copying its captured variables, each with its type and its
+    /// offset, out of its environment
+    fn gen_func(
+        &mut self,
+        proto: &FuncProto,
+        body: &[Box<Stmt>],
+        captures: &[(Symbol, Type, u64)],
+    ) {
+        let start = self.instructions.len();
+        self.func_start = start;
+        self.emit_label(proto.name);
+        self.addressed.clear();
+        addressed_vars(body, &mut self.addressed);
+        for param in &proto.params {
+            self.emit_local_if_needed(param.name, param.ty);
+        }
+        for &(var, ty, offset) in captures {
+            let env = Primary::Var(name(ENV_PARAM));
+            let value = self.gen_load(ty, Place::Ptr(env, int(offset as i64)));
+            self.emit_local_if_needed(var, ty);
+            self.emit_assign_primary(var, value);
+        }
+        // self tail calls jump past the prologue, so its locals aren't declared again
+        let body_start = self.instructions.len();
+        self.body_label = (body_start > start + 1).then(|| name(&format!("{}$body", proto.name)));
+        self.current_func = Some(proto.clone());
+        // the end of a function body is its tail position
+        self.gen_tail_block(body);
+        // a void function returns when it reaches the end of its body, the type checker makes sure
+        // other functions can't
+        if proto.ret == Type::Void && !self.instructions.last().is_some_and(Instruction::is_jump) {
+            self.emit_ret(None);
+        }
+        self.current_func = None;
+        if let Some(label) = self.body_label.take() {
+            if self.instructions[body_start..].contains(&Instruction::Goto(label)) {
+                self.instructions
+                    .insert(body_start, Instruction::Label(label));
             }
         }
+    }
+
+    fn gen_cond(
+        &mut self,
+        condition: &Expr,
+        then_block: &Block,
+        else_block: Option<&Block>,
+        tail: bool,
+    ) {
+        // evalute the condition
+        let t = walk_expr(self, condition).unwrap();
+
+        // this label is
+        let else_label = self.new_tmp_label();
+        let end_label = self.new_tmp_label();
 
-        ic
+        self.emit_ifz(t, else_label);
+        if tail {
+            self.gen_tail_block(then_block);
+        } else {
+            self.visit_block(then_block);
+        }
+        if else_block.is_some() {
+            self.emit_goto(end_label);
+        }
+        self.emit_label(else_label);
+        if let Some(else_block) = else_block {
+            if tail {
+                self.gen_tail_block(else_block);
+            } else {
+                self.visit_block(else_block);
+            }
+            self.emit_label(end_label);
+        }
     }
-    pub fn optimize_mut(&mut self) {
-        // a block is a sequence of instructions where control enters and leaves the sequence at only
-        // one place respectively
-        let blocks = self
-            .instructions
-            .split_inclusive_mut(|i| i.is_ifz() || i.is_label());
 
-        for basic_block in blocks {
-            elim_common_subexprs(basic_block);
+    /// Generates a block, in tail position if the construct it belongs to is
+    fn gen_branch(&mut self, block: &[Box<Stmt>], tail: bool) {
+        if tail {
+            self.gen_tail_block(block);
+        } else {
+            self.visit_block(block);
         }
     }
 
-    fn optimize(&self) -> Vec<Instruction> {
-        let mut new_instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
-        let blocks = self
-            .instructions
-            .split_inclusive(|instr| instr.is_ifz() || instr.is_label());
+    /// Generates a match. Every arm tests its pattern and jumps to the next arm if it doesn't
+    /// match, except for the last arm which has to match because the match is exhaustive.
+    /// A match on an integer with many literals jumps straight to the arm with a jump table
+    fn gen_match(&mut self, scrutinee: &Expr, arms: &[Arm], tail: bool) {
+        let ty = self.type_of(scrutinee);
+        let value = walk_expr(self, scrutinee).unwrap();
+        let matched = match value {
+            Primary::Var(var) if Self::is_aggregate(ty) => Matched::At(Place::Local(var, int(0))),
+            _ => Matched::Value(value),
+        };
 
-        for block in blocks {
-            new_instructions.extend(elim_common_subexprs(block));
+        if self.gen_jump_table(value, ty, arms, tail) {
+            return;
         }
 
-        new_instructions
+        let end_label = self.new_tmp_label();
+        for (idx, arm) in arms.iter().enumerate() {
+            let next_label = (idx + 1 < arms.len()).then(|| self.new_tmp_label());
+            if let Some(next_label) = next_label {
+                self.gen_pattern_test(&arm.pattern, ty, matched, next_label);
+            }
+            self.gen_bindings(&arm.pattern, ty, matched);
+            self.gen_branch(&arm.body, tail);
+            if let Some(next_label) = next_label {
+                self.emit_goto(end_label);
+                self.emit_label(next_label);
+            }
+        }
+        self.emit_label(end_label);
+    }
+
+    /// Generates a match on an integer as a jump table, if every arm but the last compares with a
+    /// literal, the last arm matches everything and the literals are dense enough that most
+    /// entries of the table go to an arm. Returns `false` if the match doesn't qualify
+    fn gen_jump_table(&mut self, value: Primary, ty: Type, arms: &[Arm], tail: bool) -> bool {
+        let Some((default, cases)) = arms.split_last() else {
+            return false;
+        };
+        if !ty.is_int()
+            || cases.len() < JUMP_TABLE_MIN_CASES
+            || !matches!(
+                default.pattern.kind,
+                PatternKind::Wildcard | PatternKind::Binding(_)
+            )
+        {
+            return false;
+        }
+        let Some(values) = cases
+            .iter()
+            .map(|arm| pattern_int(&arm.pattern))
+            .collect::<Option<Vec<i64>>>()
+        else {
+            return false;
+        };
+
+        // the values are distinct, the type checker reports arms that can't be reached
+        let min = *values.iter().min().unwrap();
+        let max = *values.iter().max().unwrap();
+        let span = max.abs_diff(min) + 1;
+        if span > 2 * values.len() as u64 {
+            return false;
+        }
+
+        // the value is moved to start at zero as a `uint64`, so a value below the smallest case
+        // wraps around and is out of range like a value above the largest one
+        let from = ic::Ty::from(ty);
+        let offset = if from == ic::Ty::U64 {
+            value
+        } else {
+            let t = self.new_tmp_var();
+            self.emit_assign(t, ic::Expr::Cast(from, value, ic::Ty::U64));
+            Primary::Var(t)
+        };
+        let offset = if min == 0 {
+            offset
+        } else {
+            let t = self.new_tmp_var();
+            self.emit_assign_binary(t, ic::BinOp::Sub, ic::Ty::U64, offset, int(min));
+            Primary::Var(t)
+        };
+
+        let end_label = self.new_tmp_label();
+        let default_label = self.new_tmp_label();
+        let case_labels: Vec<Symbol> = cases.iter().map(|_| self.new_tmp_label()).collect();
+        let in_range = self.new_tmp_var();
+        self.emit_assign_binary(
+            in_range,
+            ic::BinOp::Lt,
+            ic::Ty::U64,
+            offset,
+            int(span as i64),
+        );
+        self.emit_ifz(Primary::Var(in_range), default_label);
+
+        // the labels are leaked so that instructions stay `Copy`, like interned names and types
+        let table: Vec<Symbol> = (0..span)
+            .map(|idx| {
+                let value = min.wrapping_add(idx as i64);
+                values
+                    .iter()
+                    .position(|v| *v == value)
+                    .map_or(default_label, |case| case_labels[case])
+            })
+            .collect();
+        self.instructions
+            .push(Instruction::JumpTable(offset, Vec::leak(table)));
+
+        for (arm, label) in cases.iter().zip(case_labels) {
+            self.emit_label(label);
+            self.gen_branch(&arm.body, tail);
+            self.emit_goto(end_label);
+        }
+        self.emit_label(default_label);
+        self.gen_bindings(&default.pattern, ty, Matched::Value(value));
+        self.gen_branch(&default.body, tail);
+        self.emit_label(end_label);
+        true
+    }
+
+    /// Gets the value that a pattern is matched against as a scalar
+    fn gen_matched_value(&mut self, matched: Matched, ty: Type) -> Primary {
+        match matched {
+            Matched::Value(value) => value,
+            Matched::At(place) => self.gen_load(ty, place),
+        }
+    }
+
+    /// Jumps to `fail_label` if the value doesn't match the pattern
+    fn gen_pattern_test(
+        &mut self,
+        pattern: &Pattern,
+        ty: Type,
+        matched: Matched,
+        fail_label: Symbol,
+    ) {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => {}
+            PatternKind::Lit { .. } => {
+                let value = self.gen_matched_value(matched, ty);
+                let lit = int(pattern_int(pattern).expect("only scalar literals are patterns"));
+                let t = self.new_tmp_var();
+                self.emit_assign_binary(t, ic::BinOp::Eq, ic::Ty::from(ty), value, lit);
+                self.emit_ifz(Primary::Var(t), fail_label);
+            }
+            PatternKind::Variant(_, variant, fields) => {
+                let Matched::At(place) = matched else {
+                    unreachable!("enums are aggregates");
+                };
+                let (tag, field_places) = self.variant(ty, *variant);
+                let found = self.gen_load_scalar(ic::Ty::U32, place);
+                let t = self.new_tmp_var();
+                self.emit_assign_binary(t, ic::BinOp::Eq, ic::Ty::U32, found, int(tag));
+                self.emit_ifz(Primary::Var(t), fail_label);
+
+                for (field, (offset, ty)) in fields.iter().zip(field_places) {
+                    let place = self.gen_offset_place(place, int(offset as i64));
+                    self.gen_pattern_test(field, ty, Matched::At(place), fail_label);
+                }
+            }
+            PatternKind::Tuple(elems) => {
+                let Matched::At(place) = matched else {
+                    unreachable!("tuples are aggregates");
+                };
+                for (elem, (offset, ty)) in elems.iter().zip(self.tuple_elems(ty)) {
+                    let place = self.gen_offset_place(place, int(offset as i64));
+                    self.gen_pattern_test(elem, ty, Matched::At(place), fail_label);
+                }
+            }
+        }
+    }
+
+    /// Assigns the parts of the value that a pattern binds to the pattern's variables
+    fn gen_bindings(&mut self, pattern: &Pattern, ty: Type, matched: Matched) {
+        match (&pattern.kind, matched) {
+            // a whole aggregate is copied like any variable
+            (PatternKind::Binding(var), Matched::At(Place::Local(src, offset)))
+                if offset == int(0) =>
+            {
+                self.emit_local_if_needed(*var, ty);
+                self.emit_assign_primary(*var, Primary::Var(src));
+            }
+            _ => self.gen_part_bindings(pattern, ty, matched),
+        }
+    }
+
+    /// Assigns the parts of a value to the variables of a pattern that matches a part of it, the
+    /// first element of a tuple is at the same place as the whole tuple
+    fn gen_part_bindings(&mut self, pattern: &Pattern, ty: Type, matched: Matched) {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Lit { .. } => {}
+            PatternKind::Binding(var) => {
+                let value = self.gen_matched_value(matched, ty);
+                self.emit_local_if_needed(*var, ty);
+                self.emit_assign_primary(*var, value);
+            }
+            PatternKind::Variant(_, variant, fields) => {
+                let Matched::At(place) = matched else {
+                    unreachable!("enums are aggregates");
+                };
+                let (_, field_places) = self.variant(ty, *variant);
+                for (field, (offset, ty)) in fields.iter().zip(field_places) {
+                    let place = self.gen_offset_place(place, int(offset as i64));
+                    self.gen_part_bindings(field, ty, Matched::At(place));
+                }
+            }
+            PatternKind::Tuple(elems) => {
+                let Matched::At(place) = matched else {
+                    unreachable!("tuples are aggregates");
+                };
+                for (elem, (offset, ty)) in elems.iter().zip(self.tuple_elems(ty)) {
+                    let place = self.gen_offset_place(place, int(offset as i64));
+                    self.gen_part_bindings(elem, ty, Matched::At(place));
+                }
+            }
+        }
+    }
+
+    /// Formats the data section, one global per line, followed by the code
+    pub fn intermediate_code(&self) -> String {
+        let mut ic = String::new();
+        for global in &self.globals {
+            ic.push_str(&format!("{global}\n"));
+        }
+        if !self.globals.is_empty() {
+            ic.push('\n');
+        }
+        ic + &ic::to_string(&self.instructions)
+    }
+
+    /// Runs the optimization passes of the pass manager over the generated code
+    pub fn optimize(&mut self, pass_manager: &PassManager) {
+        pass_manager.run(&mut self.instructions);
     }
 }
-/// Only [`CodeGenerator::visit_expr`] returns a string (the name of temporary it generates)
-impl StmtVisitor<Option<Symbol>> for CodeGenerator {
-    fn visit_expr_stmt(&mut self, expr: &Expr, _: Region) -> Option<Symbol> {
+/// Only expressions produce a value (a variable or a constant that holds the expression's result)
+impl StmtVisitor<Option<Primary>> for CodeGenerator {
+    fn visit_expr_stmt(&mut self, expr: &Expr, _: Region) -> Option<Primary> {
         walk_expr(self, expr)
     }
 
-    fn visit_ret_stmt(&mut self, value: &Expr, _: Region) -> Option<Symbol> {
-        let t = walk_expr(self, value);
+    fn visit_ret_stmt(&mut self, value: Option<&Expr>, _: Region) -> Option<Primary> {
+        let Some(value) = value else {
+            self.emit_ret(None);
+            return None;
+        };
+        if let ExprKind::Call(callee, args) = &value.kind {
+            let func_name = self
+                .direct_callee(callee, value.region)
+                .filter(|_| self.can_tail_call());
+            if let Some(func_name) = func_name {
+                self.gen_tail_call(func_name, args, value.region);
+                return None;
+            }
+        }
+
+        let t = match self
+            .current_func
+            .as_ref()
+            .map_or(Type::Void, |proto| proto.ret)
+        {
+            Type::Void => walk_expr(self, value),
+            ret => Some(self.gen_expr_as(value, ret)),
+        };
         self.emit_ret(t);
         None
     }
 
-    fn visit_var_stmt(&mut self, name: Symbol, init: &Expr, _: Region) -> Option<Symbol> {
-        let initializer_symbol = walk_expr(self, init).unwrap();
-        self.emit_assign_var(name, initializer_symbol);
+    fn visit_var_stmt(&mut self, name: Symbol, init: &Expr, _: Region) -> Option<Primary> {
+        let initializer = walk_expr(self, init).unwrap();
+        self.emit_local_if_needed(name, self.type_of(init));
+        self.emit_assign_primary(name, initializer);
         None
     }
 
-    fn visit_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>], _: Region) -> Option<Symbol> {
-        self.emit_label(proto.name);
-        self.visit_block(body)
+    fn visit_destructure_stmt(
+        &mut self,
+        pattern: &Pattern,
+        init: &Expr,
+        _: Region,
+    ) -> Option<Primary> {
+        let ty = self.type_of(init);
+        let Primary::Var(value) = walk_expr(self, init).unwrap() else {
+            unreachable!("tuples are always held in variables");
+        };
+        self.gen_bindings(pattern, ty, Matched::At(Place::Local(value, int(0))));
+        None
+    }
+
+    // constants are inlined wherever they are used
+    fn visit_const(&mut self, _: Symbol, _: Type, _: &Expr, _: Region) -> Option<Primary> {
+        None
+    }
+
+    fn visit_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>], _: Region) -> Option<Primary> {
+        if proto.generics.is_empty() {
+            self.gen_func(proto, body, &[]);
+            return None;
+        }
+
+        // every instance of a generic function is generated from the same body, with the types
+        // that the type checker found for that instance
+        let (instances, rest) = std::mem::take(&mut self.instances)
+            .into_iter()
+            .partition::<Vec<_>, _>(|instance| instance.func == proto.name);
+        self.instances = rest;
+        for instance in instances {
+            let outer = std::mem::replace(&mut self.types, instance.table);
+            let proto = self.funcs[&instance.name].clone();
+            self.gen_func(&proto, body, &[]);
+            self.types = outer;
+        }
+        None
     }
 
     // TODO: implement something
-    fn visit_extern(&mut self, _: &FuncProto, _: Region) -> Option<Symbol> {
+    fn visit_extern(&mut self, _: &FuncProto, _: Region) -> Option<Primary> {
         None
     }
 
     // TODO:
-    fn visit_impt(&mut self, _: Symbol, _: Region) -> Option<Symbol> {
+    fn visit_impt(&mut self, _: Symbol, _: Region) -> Option<Primary> {
         None
     }
 
-    fn visit_block(&mut self, block: &[Box<Stmt>]) -> Option<Symbol> {
+    fn visit_error_stmt(&mut self, _: Region) -> Option<Primary> {
+        unreachable!("code is only generated for modules without syntax errors")
+    }
+
+    // the layout of a struct is computed wherever it is used
+    fn visit_struct(&mut self, _: &StructDef, _: Region) -> Option<Primary> {
+        None
+    }
+
+    // and so is the layout of an enum
+    fn visit_enum(&mut self, _: &EnumDef, _: Region) -> Option<Primary> {
+        None
+    }
+
+    // a newtype is laid out like the type it wraps
+    fn visit_type(&mut self, _: Symbol, _: Type, _: Region) -> Option<Primary> {
+        None
+    }
+
+    fn visit_impl(&mut self, _: Symbol, methods: &[Box<Stmt>], _: Region) -> Option<Primary> {
+        for method in methods {
+            walk_stmt(self, method);
+        }
+        None
+    }
+
+    fn visit_block(&mut self, block: &[Box<Stmt>]) -> Option<Primary> {
         for stmt in block {
             walk_stmt(self, stmt);
         }
@@ -208,37 +1509,85 @@ impl StmtVisitor<Option<Symbol>> for CodeGenerator {
     }
 }
 
-/// Only [`CodeGenerator::visit_expr`] returns a string (the name of temporary it generates)
-impl ExprVisitor<Option<Symbol>> for CodeGenerator {
-    fn visit_lit_expr(&mut self, lit: Lit, _: Region) -> Option<Symbol> {
-        match lit {
-            Lit::Int(k) => {
-                let t = self.new_tmp_var();
-                self.emit_assign_const_int(t, k);
-                Some(t)
+/// Every expression produces the variable or constant that holds its result, only a variable
+/// or a constant needs no instructions
+impl ExprVisitor<Option<Primary>> for CodeGenerator {
+    fn visit_lit_expr(&mut self, lit: Lit, _: Region) -> Option<Primary> {
+        let value = match lit {
+            // literals that don't fit in an `i64` are `uint64`s, which have the same bits
+            Lit::Int(k) => ConstKind::Int(k as i64),
+            Lit::Float(k) => ConstKind::Float(k),
+            Lit::Str(str) => ConstKind::Str(str),
+            Lit::Char(k) => ConstKind::Int(k as i64),
+            Lit::Bool(b) => ConstKind::Int(b.into()),
+        };
+
+        Some(Primary::Const(value))
+    }
+
+    fn visit_variable_expr(&mut self, var: Symbol, region: Region) -> Option<Primary> {
+        // a function used as a value is a closure with no environment
+        if self.types.funcs.contains(&region) {
+            if !self.closures.contains(&var) {
+                self.closures.push(var);
             }
+            let code = self.new_tmp_var();
+            self.emit_assign(code, ic::Expr::FuncAddr(closure_name(var)));
+            return Some(self.gen_closure(self.type_at(region), Primary::Var(code), int(0)));
+        }
+        if self.types.globals.contains(&region) {
+            return Some(self.gen_load(self.type_at(region), Place::Global(var, int(0))));
+        }
+        match self.types.consts.get(&region) {
+            Some(&value) => Some(Primary::Const(const_kind(value))),
+            None => Some(Primary::Var(var)),
+        }
+    }
 
-            Lit::Str(str) => {
-                let t = self.new_tmp_var();
-                self.emit_assign_const_str(t, str);
-                Some(t)
+    fn visit_unary_expr(&mut self, op: UnOp, rhs: &Expr, region: Region) -> Option<Primary> {
+        match op {
+            UnOp::Ref => {
+                let place = self.gen_place(rhs);
+                return Some(self.gen_address_of(place));
             }
-            Lit::Char(k) => {
-                let t = self.new_tmp_var();
-                self.emit_assign_const_int(t, k as i64);
-                Some(t)
+            UnOp::Deref => {
+                let ptr = walk_expr(self, rhs).unwrap();
+                return Some(self.gen_load(self.type_at(region), Place::Ptr(ptr, int(0))));
+            }
+            // ownership is only checked, a moved value is used like any other value
+            UnOp::Move => return walk_expr(self, rhs),
+            // a clone of an aggregate gets its own memory, scalars are copied wherever they are
+            // used
+            UnOp::Clone => {
+                let ty = self.type_of(rhs);
+                let value = walk_expr(self, rhs).unwrap();
+                if !Self::is_aggregate(ty) {
+                    return Some(value);
+                }
+                let t = self.new_tmp_of(ty);
+                self.emit_assign_primary(t, value);
+                return Some(Primary::Var(t));
             }
+            _ => {}
         }
-    }
 
-    fn visit_variable_expr(&mut self, var: Symbol, _: Region) -> Option<Symbol> {
+        let value = walk_expr(self, rhs).unwrap();
+        let ty = self.ty_of(rhs);
+        let zero = Primary::Const(ConstKind::Int(0));
         let t = self.new_tmp_var();
-        self.emit_assign_var(t, var);
-        Some(t)
-    }
-
-    fn visit_unary_expr(&mut self, _: UnOp, _: &Expr, _: Region) -> Option<Symbol> {
-        todo!()
+        match op {
+            // `-x` is `-0.0 - x` for floats, so that negating `0.0` gives `-0.0`
+            UnOp::Neg if ty.is_float() => {
+                let zero = Primary::Const(ConstKind::Float(-0.0));
+                self.emit_assign_binary(t, ic::BinOp::Sub, ty, zero, value)
+            }
+            // `-x` is `0 - x`
+            UnOp::Neg => self.emit_assign_binary(t, ic::BinOp::Sub, ty, zero, value),
+            // `!x` is `x == 0`
+            UnOp::Not => self.emit_assign_binary(t, ic::BinOp::Eq, ty, value, zero),
+            UnOp::Ref | UnOp::Deref | UnOp::Move | UnOp::Clone => unreachable!(),
+        }
+        Some(Primary::Var(t))
     }
 
     fn visit_binary_expr(
@@ -247,28 +1596,104 @@ impl ExprVisitor<Option<Symbol>> for CodeGenerator {
         lhs: &Expr,
         rhs: &Expr,
         _: Region,
-    ) -> Option<Symbol> {
-        let t1 = walk_expr(self, lhs).unwrap();
-        let t2 = walk_expr(self, rhs).unwrap();
+    ) -> Option<Primary> {
+        if let BinOp::And | BinOp::Or = op {
+            return Some(self.gen_short_circuit(op, lhs, rhs));
+        }
+
+        // both operands have the same type, the result of a comparison is a `bool` instead
+        let ty = self.type_of(lhs);
+        let [lhs, rhs] = self.gen_operands(&[(lhs, ty), (rhs, ty)])[..] else {
+            unreachable!("two operands in, two values out");
+        };
         let t = self.new_tmp_var();
-        self.emit_assign_binary(t, op, t1, t2);
-        Some(t)
+        self.emit_assign_binary(t, op.into(), ic::Ty::from(ty), lhs, rhs);
+        Some(Primary::Var(t))
     }
 
     fn visit_call_expr(
         &mut self,
-        func_name: Symbol,
+        callee: &Expr,
         args: &[Box<Expr>],
-        _: Region,
-    ) -> Option<Symbol> {
-        for arg in args {
-            let t = walk_expr(self, arg).unwrap();
-            self.emit_arg(t);
+        region: Region,
+    ) -> Option<Primary> {
+        if let Some(func_name) = self.direct_callee(callee, region) {
+            return Some(self.gen_call(func_name, args, region));
         }
-        let t = self.new_tmp_var();
-        self.emit_assign_call(t, func_name);
 
-        Some(t)
+        // a closure is called with its environment before the arguments
+        let ty = self.type_of(callee);
+        let Type::Func(params, _) = ty else {
+            unreachable!("only functions can be called");
+        };
+        let closure = walk_expr(self, callee).unwrap();
+        let later: Vec<&Expr> = args.iter().map(|arg| &**arg).collect();
+        let Primary::Var(closure) = self.preserve(closure, ty, &later) else {
+            unreachable!("aggregates are always held in variables");
+        };
+        let operands: Vec<(&Expr, Type)> = args
+            .iter()
+            .zip(params)
+            .map(|(arg, param)| (&**arg, *param))
+            .collect();
+        let values = self.gen_operands(&operands);
+
+        let code = self.gen_load_scalar(ic::Ty::Ptr, Place::Local(closure, int(0)));
+        let env_place = Place::Local(closure, int(CLOSURE_ENV as i64));
+        let env = self.gen_load_scalar(ic::Ty::Ptr, env_place);
+        self.emit_arg(env);
+        for value in values {
+            self.emit_arg(value);
+        }
+        let t = self.new_tmp_of(self.type_at(region));
+        self.emit_assign(t, ic::Expr::CallIndirect(code));
+        Some(Primary::Var(t))
+    }
+
+    fn visit_method_call_expr(
+        &mut self,
+        receiver: &Expr,
+        method: Symbol,
+        args: &[Box<Expr>],
+        region: Region,
+    ) -> Option<Primary> {
+        let ty = self.type_of(receiver);
+        let (Type::Name(type_name) | Type::Ptr(&Type::Name(type_name))) = ty else {
+            unreachable!("only structs and enums have methods");
+        };
+        let func_name = self.callee(method_name(type_name, method), region);
+        let (self_param, params) = self.funcs[&func_name].params.split_first().unwrap();
+        let self_ty = self_param.ty;
+        let operands: Vec<(&Expr, Type)> = args
+            .iter()
+            .zip(params)
+            .map(|(arg, param)| (&**arg, param.ty))
+            .collect();
+
+        // a method that takes a pointer gets the address of the receiver, and a method called on
+        // a pointer gets a copy of what it points to
+        let receiver = match ty {
+            _ if self_ty == ty => walk_expr(self, receiver).unwrap(),
+            Type::Ptr(_) => {
+                let ptr = walk_expr(self, receiver).unwrap();
+                self.gen_load(self_ty, Place::Ptr(ptr, int(0)))
+            }
+            _ => {
+                let place = self.gen_place(receiver);
+                self.gen_address_of(place)
+            }
+        };
+        let later: Vec<&Expr> = args.iter().map(|arg| &**arg).collect();
+        let receiver = self.preserve(receiver, self_ty, &later);
+        let values = self.gen_operands(&operands);
+
+        self.emit_arg(receiver);
+        for value in values {
+            self.emit_arg(value);
+        }
+        let t = self.new_tmp_of(self.type_at(region));
+        self.emit_assign_call(t, func_name);
+        Some(Primary::Var(t))
     }
 
     fn visit_cond_expr(
@@ -277,32 +1702,224 @@ impl ExprVisitor<Option<Symbol>> for CodeGenerator {
         then_block: &Block,
         else_block: Option<&Block>,
         _: Region,
-    ) -> Option<Symbol> {
-        // evalute the condition into a temporary
-        let t = walk_expr(self, condition).unwrap();
+    ) -> Option<Primary> {
+        self.gen_cond(condition, then_block, else_block, false);
 
-        // this label is
-        let else_label = self.new_tmp_label();
-        let end_label = self.new_tmp_label();
+        // TODO:
+        None
+    }
 
-        self.emit_ifz(t, else_label);
-        self.visit_block(then_block);
-        if else_block.is_some() {
-            self.emit_goto(end_label);
+    fn visit_assign_expr(&mut self, target: &Expr, value: &Expr, _: Region) -> Option<Primary> {
+        let ty = self.type_of(target);
+        let value = self.gen_expr_as(value, ty);
+        match target.kind {
+            ExprKind::Variable(var_name) if !self.types.globals.contains(&target.region) => {
+                self.emit_assign_primary(var_name, value);
+                Some(Primary::Var(var_name))
+            }
+            _ => {
+                let place = self.gen_place(target);
+                self.gen_store(ty, place, value);
+                Some(value)
+            }
         }
-        self.emit_label(else_label);
-        if let Some(else_block) = else_block {
-            self.visit_block(else_block);
-            self.emit_label(end_label);
+    }
+
+    fn visit_cast_expr(&mut self, value: &Expr, _: Type, region: Region) -> Option<Primary> {
+        let from = self.ty_of(value);
+        // the type that is cast to can be a type parameter, the type checker knows what it stands
+        // for
+        let to = ic::Ty::from(self.type_at(region));
+        let value = walk_expr(self, value).unwrap();
+        // casting to the same type doesn't change the value
+        if from == to {
+            return Some(value);
         }
 
-        // TODO:
+        let t = self.new_tmp_var();
+        self.emit_assign(t, ic::Expr::Cast(from, value, to));
+        Some(Primary::Var(t))
+    }
+
+    fn visit_struct_lit_expr(
+        &mut self,
+        _: Symbol,
+        fields: &[(Symbol, Box<Expr>)],
+        region: Region,
+    ) -> Option<Primary> {
+        // the type of a generic struct literal includes its type arguments
+        let ty = self.type_at(region);
+        let t = self.new_tmp_of(ty);
+
+        // the fields are evaluated in the order they are written, and each one is stored right
+        // away so later fields can't change it
+        for (field, value) in fields {
+            let (offset, field_ty) = self.field(ty, *field);
+            let value = self.gen_expr_as(value, field_ty);
+            self.gen_store(field_ty, Place::Local(t, int(offset as i64)), value);
+        }
+
+        Some(Primary::Var(t))
+    }
+
+    fn visit_field_expr(&mut self, base: &Expr, field: Symbol, _: Region) -> Option<Primary> {
+        // the length of an array is part of its type
+        if let Type::Array(_, len) = self.type_of(base) {
+            if base.has_side_effects() {
+                walk_expr(self, base);
+            }
+            return Some(int(len as i64));
+        }
+
+        let place = self.gen_place(base);
+        let (offset, ty) = self.field(self.type_of(base), field);
+        let place = self.gen_offset_place(place, int(offset as i64));
+        Some(self.gen_load(ty, place))
+    }
+
+    fn visit_tuple_expr(&mut self, elems: &[Box<Expr>], region: Region) -> Option<Primary> {
+        let ty = self.type_at(region);
+        let t = self.new_tmp_of(ty);
+
+        // like struct literals, each element is stored as soon as it's evaluated
+        for (elem, (offset, elem_ty)) in elems.iter().zip(self.tuple_elems(ty)) {
+            let value = self.gen_expr_as(elem, elem_ty);
+            self.gen_store(elem_ty, Place::Local(t, int(offset as i64)), value);
+        }
+
+        Some(Primary::Var(t))
+    }
+
+    fn visit_array_expr(&mut self, elems: &[Box<Expr>], region: Region) -> Option<Primary> {
+        let ty = self.type_at(region);
+        let Type::Array(elem_ty, _) = ty else {
+            unreachable!("array literals are arrays");
+        };
+        let size = Layout::of(*elem_ty, &self.defs).size;
+        let t = self.new_tmp_of(ty);
+
+        // like struct literals, each element is stored as soon as it's evaluated
+        for (idx, elem) in elems.iter().enumerate() {
+            let value = self.gen_expr_as(elem, *elem_ty);
+            let offset = int((idx as u64 * size) as i64);
+            self.gen_store(*elem_ty, Place::Local(t, offset), value);
+        }
+
+        Some(Primary::Var(t))
+    }
+
+    fn visit_index_expr(&mut self, base: &Expr, index: &Expr, region: Region) -> Option<Primary> {
+        let place = self.gen_index_place(base, index);
+        Some(self.gen_load(self.type_at(region), place))
+    }
+
+    fn visit_variant_expr(
+        &mut self,
+        enum_name: Symbol,
+        variant: Symbol,
+        args: &[Box<Expr>],
+        region: Region,
+    ) -> Option<Primary> {
+        // `Point::new()` calls a function in `impl Point`
+        if !self.defs.enums.contains_key(&enum_name) {
+            let func_name = self.callee(method_name(enum_name, variant), region);
+            return Some(self.gen_call(func_name, args, region));
+        }
+
+        let ty = self.type_at(region);
+        let (tag, fields) = self.variant(ty, variant);
+        let t = self.new_tmp_of(ty);
+
+        // the tag is stored first, then the fields are stored as they are evaluated like the
+        // fields of a struct literal
+        self.gen_store_scalar(ic::Ty::U32, Place::Local(t, int(0)), int(tag));
+        for (arg, (offset, field_ty)) in args.iter().zip(fields) {
+            let value = self.gen_expr_as(arg, field_ty);
+            self.gen_store(field_ty, Place::Local(t, int(offset as i64)), value);
+        }
+
+        Some(Primary::Var(t))
+    }
+
+    fn visit_match_expr(&mut self, scrutinee: &Expr, arms: &[Arm], _: Region) -> Option<Primary> {
+        self.gen_match(scrutinee, arms, false);
         None
     }
 
-    fn visit_assign_expr(&mut self, var_name: Symbol, value: &Expr, _: Region) -> Option<Symbol> {
-        let t = walk_expr(self, value).unwrap();
-        self.emit_assign_var(var_name, t);
-        Some(var_name)
+    /// A lambda is generated as a function that takes its environment before its parameters. The
+    /// closure points to that function and to a copy of the variables it captures
+    fn visit_lambda_expr(
+        &mut self,
+        params: &[Param],
+        _: Type,
+        body: &Block,
+        region: Region,
+    ) -> Option<Primary> {
+        // the types in an instance of a generic function are only known from the type checker
+        let ty = self.type_at(region);
+        let Type::Func(param_tys, ret) = ty else {
+            unreachable!("lambdas are functions");
+        };
+        let captures: Vec<(Symbol, Type)> = self.types.captures[&region]
+            .iter()
+            .map(|&(var, use_region)| (var, self.type_at(use_region)))
+            .collect();
+        let (layout, offsets) = record_layout(captures.iter().map(|&(_, ty)| ty), &self.defs);
+
+        let func = self
+            .current_func
+            .as_ref()
+            .map_or(name(ic::INIT_FUNC), |proto| proto.name);
+        let proto = FuncProto {
+            name: name(&format!("{func}$lambda{}", self.lambda_count)),
+            generics: Vec::new(),
+            variadic: false,
+            params: params
+                .iter()
+                .zip(param_tys)
+                .map(|(param, ty)| Param { ty: *ty, ..*param })
+                .collect(),
+            ret: *ret,
+            ret_region: region,
+            region,
+        };
+        self.lambda_count += 1;
+
+        let outer = std::mem::take(&mut self.instructions);
+        let outer_addressed = std::mem::take(&mut self.addressed);
+        let outer_func = self.current_func.take();
+        let outer_body_label = self.body_label.take();
+        let outer_func_start = self.func_start;
+        let fields: Vec<(Symbol, Type, u64)> = captures
+            .iter()
+            .zip(&offsets)
+            .map(|(&(var, ty), &offset)| (var, ty, offset))
+            .collect();
+        self.gen_func(&proto, body, &fields);
+        let code = std::mem::replace(&mut self.instructions, outer);
+        self.lambdas.extend(code);
+        self.addressed = outer_addressed;
+        self.current_func = outer_func;
+        self.body_label = outer_body_label;
+        self.func_start = outer_func_start;
+
+        let env = if captures.is_empty() {
+            int(0)
+        } else {
+            let env = self.new_tmp_var();
+            self.emit_assign(env, ic::Expr::Alloc(layout));
+            for (var, ty, offset) in fields {
+                let place = Place::Ptr(Primary::Var(env), int(offset as i64));
+                self.gen_store(ty, place, Primary::Var(var));
+            }
+            Primary::Var(env)
+        };
+        let code = self.new_tmp_var();
+        self.emit_assign(code, ic::Expr::FuncAddr(proto.name));
+        Some(self.gen_closure(ty, Primary::Var(code), env))
+    }
+
+    fn visit_error_expr(&mut self, _: Region) -> Option<Primary> {
+        unreachable!("code is only generated for modules without syntax errors")
     }
 }
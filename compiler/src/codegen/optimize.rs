@@ -1,56 +1,384 @@
 /// Includes intermediate code optimization functions
 ///
-use super::ic::{Expr, Instruction, Primary};
+use super::ic::{self, is_tmp_var, BinOp, ConstKind, Expr, Instruction, Primary};
 use crate::name::Name as Symbol;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem;
+use std::ops::Range;
 
-pub fn elim_common_subexprs(block: &[Instruction]) -> Vec<Instruction> {
-    let mut available_exprs: HashMap<Symbol, Expr> = HashMap::new();
-    let mut new_instructions = Vec::with_capacity(block.len());
+/// Splits the instructions into basic blocks.
+/// A basic block is a sequence of instructions where control enters and leaves the sequence at
+/// only one place respectively, so a label starts a new block and a jump ends the current one
+pub fn basic_blocks(instructions: &[Instruction]) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
 
-    for instruction in block {
-        let mut optimized_instruction = *instruction;
+    for (idx, instruction) in instructions.iter().enumerate() {
+        if instruction.is_label() && idx != start {
+            blocks.push(start..idx);
+            start = idx;
+        }
 
-        if let Instruction::Assign(symbol, expr) = instruction {
-            for (available_symbol, available_expr) in available_exprs.iter() {
-                if expr == available_expr {
-                    optimized_instruction = Instruction::Assign(
-                        *symbol,
-                        Expr::Primary(Primary::Var(*available_symbol)),
-                    );
-                }
-            }
-            available_exprs.insert(*symbol, *expr);
+        if instruction.is_ifz() || instruction.is_jump() {
+            blocks.push(start..idx + 1);
+            start = idx + 1;
         }
+    }
 
-        new_instructions.push(optimized_instruction);
+    if start != instructions.len() {
+        blocks.push(start..instructions.len());
     }
 
-    new_instructions
+    blocks
+}
+
+/// Returns `true` if `expr` reads the variable `var`
+fn reads(expr: &Expr, var: Symbol) -> bool {
+    let var = Primary::Var(var);
+    match *expr {
+        Expr::Binary(_, _, lhs, rhs) => lhs == var || rhs == var,
+        Expr::Primary(value)
+        | Expr::Cast(_, value, _)
+        | Expr::Deref(_, value)
+        | Expr::CallIndirect(value) => value == var,
+        Expr::Load(_, base, offset) => Primary::Var(base) == var || offset == var,
+        Expr::LoadGlobal(_, _, offset) => offset == var,
+        Expr::Addr(base) => Primary::Var(base) == var,
+        Expr::GlobalAddr(_) | Expr::Call(_) | Expr::FuncAddr(_) | Expr::Alloc(_) => false,
+    }
+}
+
+/// Returns `true` if `expr` reads memory that can be changed through a pointer, globals can also
+/// be changed by any callee
+fn reads_memory(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Load(..) | Expr::Deref(..) | Expr::LoadGlobal(..)
+    )
+}
+
+/// Returns `true` if the instruction can write to memory through a pointer, a callee can write
+/// to any memory whose address was passed to it
+fn writes_through_ptr(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::StoreDeref(..) | Instruction::Assign(_, Expr::Call(_) | Expr::CallIndirect(_))
+    )
+}
+
+/// The variables whose address is taken, their memory can be changed without assigning them
+fn addressed_vars(instructions: &[Instruction]) -> HashSet<Symbol> {
+    instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Assign(_, Expr::Addr(var)) => Some(*var),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Calls `f` on every value an instruction reads.
+/// The variable that a load reads from or a store writes to isn't an operand, it names memory
+/// rather than holding a value
+fn for_each_operand(instruction: &mut Instruction, mut f: impl FnMut(&mut Primary)) {
+    match instruction {
+        Instruction::Assign(_, Expr::Binary(_, _, lhs, rhs))
+        | Instruction::Store(_, lhs, _, rhs)
+        | Instruction::StoreGlobal(_, lhs, _, rhs)
+        | Instruction::StoreDeref(lhs, _, rhs) => {
+            f(lhs);
+            f(rhs);
+        }
+        Instruction::Assign(
+            _,
+            Expr::Primary(value)
+            | Expr::Cast(_, value, _)
+            | Expr::Load(_, _, value)
+            | Expr::LoadGlobal(_, _, value)
+            | Expr::Deref(_, value)
+            | Expr::CallIndirect(value),
+        )
+        | Instruction::Ifz(value, _)
+        | Instruction::JumpTable(value, _)
+        | Instruction::Arg(value)
+        | Instruction::Ret(Some(value)) => f(value),
+        _ => {}
+    }
+}
+
+/// Common subexpression elimination (`cse`):
+/// an expression that was already computed in the same basic block is replaced by the variable
+/// that holds its value
+// every pass takes a `Vec` so it can be run by the pass manager, even if it doesn't remove anything
+#[allow(clippy::ptr_arg)]
+pub fn elim_common_subexprs(instructions: &mut Vec<Instruction>) {
+    let addressed = addressed_vars(instructions);
+
+    for block in basic_blocks(instructions) {
+        let mut available_exprs: Vec<(Symbol, Expr)> = Vec::new();
+
+        for instruction in &mut instructions[block] {
+            // memory may have changed through a pointer, so every load and every variable whose
+            // address was taken has to be read again
+            if writes_through_ptr(instruction) {
+                available_exprs.retain(|(s, e)| {
+                    !reads_memory(e)
+                        && !addressed.contains(s)
+                        && !addressed.iter().any(|var| reads(e, *var))
+                });
+            }
+
+            let (symbol, expr) = match instruction {
+                Instruction::Assign(symbol, expr) => (symbol, expr),
+                // the variable's memory changed, so loads from it (or through a pointer that may
+                // point into it) have to be performed again
+                Instruction::Store(base, ..) => {
+                    available_exprs.retain(|(s, e)| {
+                        s != base && !reads(e, *base) && !matches!(e, Expr::Deref(..))
+                    });
+                    continue;
+                }
+                // and so did the global's, which a pointer may point into as well
+                Instruction::StoreGlobal(global, ..) => {
+                    available_exprs.retain(|(_, e)| match e {
+                        Expr::LoadGlobal(_, var, _) => var != global,
+                        _ => !matches!(e, Expr::Deref(..)),
+                    });
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if addressed.contains(symbol) {
+                available_exprs.retain(|(_, e)| !matches!(e, Expr::Deref(..)));
+            }
+
+            if let Some((available_symbol, _)) = available_exprs.iter().find(|(_, e)| e == expr) {
+                *expr = Expr::Primary(Primary::Var(*available_symbol));
+            }
+
+            // the symbol has a new value, so everything that was computed from or stored in its
+            // old value isn't available anymore
+            available_exprs.retain(|(s, e)| s != symbol && !reads(e, *symbol));
+
+            // calls can have side effects so they have to be performed every time, and every
+            // allocation returns new memory
+            if !expr.is_call() && !matches!(expr, Expr::Alloc(_)) && !reads(expr, *symbol) {
+                available_exprs.push((*symbol, *expr));
+            }
+        }
+    }
 }
 
-// TODO: implement copy propogation
-/*
-pub fn copy_propagation(block: &mut [Instruction]) {
-    // this stores all the local expressions in a block
-    let mut available_expressions: HashMap<Symbol, &Expr> = HashMap::new();
+/// Copy propagation (`copy-prop`):
+/// after a copy like `x := y` every read of `x` in the same basic block is replaced by `y`, until
+/// either of them is reassigned
+#[allow(clippy::ptr_arg)]
+pub fn copy_propagation(instructions: &mut Vec<Instruction>) {
+    let addressed = addressed_vars(instructions);
+
+    for block in basic_blocks(instructions) {
+        let mut copies: Vec<(Symbol, Primary)> = Vec::new();
+
+        for instruction in &mut instructions[block] {
+            for_each_operand(instruction, |operand| {
+                if let Primary::Var(var) = *operand {
+                    if let Some((_, value)) = copies.iter().find(|(copy, _)| *copy == var) {
+                        *operand = *value;
+                    }
+                }
+            });
+
+            match *instruction {
+                Instruction::Assign(symbol, expr) => {
+                    copies
+                        .retain(|(copy, value)| *copy != symbol && *value != Primary::Var(symbol));
+
+                    if let Expr::Primary(value) = expr {
+                        if value != Primary::Var(symbol) {
+                            copies.push((symbol, value));
+                        }
+                    }
+                }
+                // a store changes part of a struct so copies of the struct aren't equal anymore
+                Instruction::Store(base, ..) => {
+                    copies.retain(|(copy, value)| *copy != base && *value != Primary::Var(base));
+                }
+                _ => {}
+            }
+
+            // and so can a write through a pointer, to any variable whose address was taken
+            if writes_through_ptr(instruction) {
+                copies.retain(|(copy, value)| {
+                    !addressed.contains(copy)
+                        && !matches!(value, Primary::Var(var) if addressed.contains(var))
+                });
+            }
+        }
+    }
+}
 
-    // loops thru all the assignments in the block and adds them to the avaible expressions
-    /* for instruction in block {
-    if let Instruction::Assign(sym, expr) = instruction {
+/// Dead code elimination (`dce`):
+/// removes instructions that can never execute and temporaries whose value is never used
+pub fn elim_dead_code(instructions: &mut Vec<Instruction>) {
+    // everything between a jump and the next label is unreachable
+    let mut reachable = true;
+    instructions.retain(|instruction| {
+        if instruction.is_label() {
+            reachable = true;
+        }
+        let keep = reachable;
+        if instruction.is_jump() {
+            reachable = false;
+        }
+        keep
+    });
 
-        match expr {
-            Expr::Call(var2) => {
-                if let Some((_, expr)) = available_expressions.get_key_value(var2) {
+    // removing an assignment can make the temporaries it read dead as well
+    loop {
+        let mut used = HashSet::new();
+        for instruction in instructions.iter_mut() {
+            for_each_operand(instruction, |operand| {
+                if let Primary::Var(var) = *operand {
+                    used.insert(var);
                 }
-            },
-            Expr::Binary(var1, _, var2) => todo!(),
-            Expr::Primary(var) => {
-                if let Some((_, expr)) = available_expressions.get_key_value(var) {
+            });
+            if let Instruction::Assign(_, Expr::Load(_, base, _) | Expr::Addr(base)) = instruction {
+                used.insert(*base);
+            }
+        }
+
+        let len = instructions.len();
+        instructions.retain(|instruction| match instruction {
+            // calls are kept for their side effects even if their result is unused
+            Instruction::Assign(_, expr) if expr.is_call() => true,
+            Instruction::Assign(symbol, _) => !is_tmp_var(*symbol) || used.contains(symbol),
+            _ => true,
+        });
+
+        if instructions.len() == len {
+            break;
+        }
+    }
+}
+
+/// Hands out temporaries that aren't used by any instruction yet
+struct TmpVars(usize);
+
+impl TmpVars {
+    fn after(instructions: &[Instruction]) -> Self {
+        let next = instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Assign(var, _) | Instruction::Local(var, _) if is_tmp_var(*var) => {
+                    var[2..].parse::<usize>().ok()
                 }
-            },
+                _ => None,
+            })
+            .max()
+            .map_or(0, |last| last + 1);
+
+        Self(next)
+    }
+
+    fn next(&mut self) -> Symbol {
+        self.0 += 1;
+        ic::tmp_var(self.0 - 1)
+    }
+}
+
+fn int(value: i64) -> Primary {
+    Primary::Const(ConstKind::Int(value))
+}
+
+/// Returns `k` if the value is the constant `2^k` (`k > 0`)
+fn log2(value: Primary) -> Option<i64> {
+    match value {
+        Primary::Const(ConstKind::Int(c)) if c > 1 && c.count_ones() == 1 => {
+            Some(c.trailing_zeros().into())
         }
+        _ => None,
+    }
+}
+
+/// Algebraic simplification and strength reduction (`simplify`):
+/// - identities like `x + 0`, `x * 1`, `x * 0`, `x - x`, `x / 1` and `x == x` are replaced by
+///   their result
+/// - multiplication by a power of two becomes a left shift
+/// - division and modulo by a power of two become shifts and masks
+pub fn simplify(instructions: &mut Vec<Instruction>) {
+    let mut tmp_vars = TmpVars::after(instructions);
+
+    for instruction in mem::take(instructions) {
+        // most of the identities don't hold for every float (`NaN - NaN` isn't `0`, `-0.0 + 0.0`
+        // isn't `-0.0`) so float operations are left alone
+        let Instruction::Assign(var, Expr::Binary(op, ty, lhs, rhs)) = instruction else {
+            instructions.push(instruction);
+            continue;
+        };
+        if ty.is_float() {
+            instructions.push(instruction);
+            continue;
+        }
+
+        let binary = |op, lhs, rhs| Expr::Binary(op, ty, lhs, rhs);
+        let simplified = match (op, lhs, rhs) {
+            (BinOp::Add | BinOp::Sub, x, zero) | (BinOp::Add, zero, x) if zero == int(0) => {
+                Expr::Primary(x)
+            }
+            (BinOp::Mul | BinOp::Div, x, one) | (BinOp::Mul, one, x) if one == int(1) => {
+                Expr::Primary(x)
+            }
+            (BinOp::Mul, _, zero) | (BinOp::Mul, zero, _) if zero == int(0) => Expr::Primary(zero),
+            (BinOp::Mod, _, one) if one == int(1) => Expr::Primary(int(0)),
+            (BinOp::Sub, x, y) if x == y => Expr::Primary(int(0)),
+            (BinOp::Eq | BinOp::Le | BinOp::Ge, x, y) if x == y => Expr::Primary(int(1)),
+            (BinOp::Ne | BinOp::Lt | BinOp::Gt, x, y) if x == y => Expr::Primary(int(0)),
 
-        available_expressions.insert(sym, expr);
-    } */
-} */
+            (BinOp::Mul, x, c) | (BinOp::Mul, c, x) if log2(c).is_some() => {
+                binary(BinOp::Shl, x, int(log2(c).unwrap()))
+            }
+            // an unsigned shift right is the same as dividing and the bits that are shifted out
+            // are the remainder
+            (BinOp::Div, x, c) if !ty.is_signed() && log2(c).is_some() => {
+                binary(BinOp::Shr, x, int(log2(c).unwrap()))
+            }
+            (BinOp::Mod, x, c) if !ty.is_signed() && log2(c).is_some() => {
+                binary(BinOp::BitAnd, x, int((1 << log2(c).unwrap()) - 1))
+            }
+            (BinOp::Div | BinOp::Mod, x, c) if log2(c).is_some() => {
+                let k = log2(c).unwrap();
+                // signed division rounds toward zero but shifting rounds toward negative
+                // infinity, so `2^k - 1` is added to negative dividends first
+                let sign = tmp_vars.next();
+                let bias = tmp_vars.next();
+                let biased = tmp_vars.next();
+                instructions.extend([
+                    // `-1` if x is negative, `0` otherwise
+                    Instruction::Assign(sign, binary(BinOp::Shr, x, int((ty.bits() - 1).into()))),
+                    Instruction::Assign(
+                        bias,
+                        binary(BinOp::BitAnd, Primary::Var(sign), int((1 << k) - 1)),
+                    ),
+                    Instruction::Assign(biased, binary(BinOp::Add, x, Primary::Var(bias))),
+                ]);
+
+                if op == BinOp::Div {
+                    binary(BinOp::Shr, Primary::Var(biased), int(k))
+                } else {
+                    // `x % 2^k` is `x - (x / 2^k) * 2^k`
+                    let multiple = tmp_vars.next();
+                    instructions.push(Instruction::Assign(
+                        multiple,
+                        binary(BinOp::BitAnd, Primary::Var(biased), int(-(1 << k))),
+                    ));
+                    binary(BinOp::Sub, x, Primary::Var(multiple))
+                }
+            }
+
+            _ => Expr::Binary(op, ty, lhs, rhs),
+        };
+
+        instructions.push(Instruction::Assign(var, simplified));
+    }
+}
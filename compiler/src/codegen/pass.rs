@@ -0,0 +1,147 @@
+//! Runs an ordered list of optimization passes over the intermediate code
+
+use super::ic::{self, Instruction};
+use super::optimize;
+use super::verify::verify;
+
+use anyhow::{bail, Result};
+
+/// An optimization pass over the intermediate code of a whole module
+#[derive(Clone, Copy)]
+pub struct Pass {
+    /// The name used to refer to the pass on the command line
+    pub name: &'static str,
+    run: fn(&mut Vec<Instruction>),
+}
+
+/// Every pass the pass manager knows about
+pub const PASSES: &[Pass] = &[
+    Pass {
+        name: "copy-prop",
+        run: optimize::copy_propagation,
+    },
+    Pass {
+        name: "cse",
+        run: optimize::elim_common_subexprs,
+    },
+    Pass {
+        name: "dce",
+        run: optimize::elim_dead_code,
+    },
+    Pass {
+        name: "simplify",
+        run: optimize::simplify,
+    },
+];
+
+/// The passes that are run at each optimization level (`-O0`, `-O1`, `-O2`)
+const OPT_LEVELS: [&[&str]; 3] = [
+    &[],
+    &["simplify", "cse", "dce"],
+    &["copy-prop", "simplify", "cse", "copy-prop", "dce"],
+];
+
+/// From this optimization level on, the passes run again until the code stops changing. A pass can
+/// leave work for the passes before it: once `_t0 := a * 0` is simplified to `_t0 := 0`, copy
+/// propagation turns `_t0 + a` into `0 + a`, which only another round simplifies
+const FIXPOINT_LEVEL: u8 = 2;
+
+pub struct PassManager {
+    passes: Vec<Pass>,
+    /// print the intermediate code after these passes run
+    dump_after: Vec<String>,
+    /// check that each pass produced well-formed intermediate code
+    verify: bool,
+    /// run the passes again until they don't change the code anymore
+    to_fixpoint: bool,
+}
+
+impl PassManager {
+    /// Creates a pass manager that runs the passes of an optimization level
+    pub fn new(opt_level: u8) -> Self {
+        let names = OPT_LEVELS[usize::from(opt_level).min(OPT_LEVELS.len() - 1)];
+        let manager =
+            Self::with_passes(names).expect("optimization levels only contain known passes");
+        Self {
+            to_fixpoint: opt_level >= FIXPOINT_LEVEL,
+            ..manager
+        }
+    }
+
+    /// Creates a pass manager that runs the given passes in order
+    pub fn with_passes<S: AsRef<str>>(names: &[S]) -> Result<Self> {
+        let mut passes = Vec::with_capacity(names.len());
+        for name in names {
+            let name = name.as_ref();
+            let Some(pass) = PASSES.iter().find(|pass| pass.name == name) else {
+                let known: Vec<_> = PASSES.iter().map(|pass| pass.name).collect();
+                bail!(
+                    "unknown pass `{name}`, the known passes are: {}",
+                    known.join(", ")
+                );
+            };
+            passes.push(*pass);
+        }
+
+        Ok(Self {
+            passes,
+            dump_after: Vec::new(),
+            verify: cfg!(debug_assertions),
+            to_fixpoint: false,
+        })
+    }
+
+    /// Prints the intermediate code after each of the given passes runs, `all` dumps after every
+    /// pass
+    pub fn dump_after(mut self, names: Vec<String>) -> Result<Self> {
+        for name in &names {
+            if name != "all" && !PASSES.iter().any(|pass| pass.name == name) {
+                bail!("cannot dump after unknown pass `{name}`");
+            }
+        }
+
+        self.dump_after = names;
+        Ok(self)
+    }
+
+    /// The passes that the code is dumped after that this pass manager doesn't run, nothing is
+    /// printed for them
+    pub fn skipped_dumps(&self) -> Vec<&str> {
+        self.dump_after
+            .iter()
+            .filter(|name| *name != "all" && !self.passes.iter().any(|pass| pass.name == *name))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn run(&self, instructions: &mut Vec<Instruction>) {
+        loop {
+            let before = self.to_fixpoint.then(|| instructions.clone());
+            self.run_once(instructions);
+            if before.is_none_or(|before| before == *instructions) {
+                break;
+            }
+        }
+    }
+
+    fn run_once(&self, instructions: &mut Vec<Instruction>) {
+        for pass in &self.passes {
+            (pass.run)(instructions);
+
+            if self.verify {
+                if let Err(error) = verify(instructions) {
+                    panic!("invalid intermediate code after `{}`: {error}", pass.name);
+                }
+            }
+
+            if self
+                .dump_after
+                .iter()
+                .any(|name| name == pass.name || name == "all")
+            {
+                println!("; intermediate code after `{}`", pass.name);
+                print!("{}", ic::to_string(instructions));
+            }
+        }
+    }
+}
@@ -0,0 +1,1264 @@
+use super::ic::{self, Instruction};
+use super::CodeGenerator;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::source::Source;
+use crate::typechk::TypeChecker;
+
+fn gen(source_code: &'static str) -> Vec<Instruction> {
+    generator(source_code).instructions
+}
+
+fn generator(source_code: &'static str) -> CodeGenerator {
+    let source = Source::new(source_code, "<string literal>");
+    let mut lexer = Lexer::new(&source);
+    let mut parser = Parser::new(lexer.lex(), &source);
+
+    let module = parser.parse();
+    assert!(parser.errors().is_empty(), "failed to parse");
+
+    let mut typechk = TypeChecker::new();
+    typechk.check(&module);
+    assert!(typechk.errors().is_empty(), "{:?}", typechk.errors());
+
+    let mut codegen = CodeGenerator::new(typechk.into_types());
+    codegen.gen(&module);
+    codegen
+}
+
+fn calls(instructions: &[Instruction]) -> usize {
+    instructions
+        .iter()
+        .filter(|i| matches!(i, Instruction::Assign(_, ic::Expr::Call(_))))
+        .count()
+}
+
+mod operands {
+    use super::*;
+
+    #[test]
+    fn operands_are_used_directly() {
+        let instructions = gen("func add(a: int32, b: int32): int32 { ret a + b; }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "add:\n    _t0 := i32 a + b\n    ret _t0\n"
+        );
+    }
+
+    #[test]
+    fn nested_calls_in_arguments() {
+        let instructions = gen("extern func g(a: int32, b: int32): int32;
+            func f(a: int32): int32 {
+                ret g(1, g(a, 2));
+            }");
+
+        // the arguments of the inner call are passed before the outer call's arguments
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    arg a\n    arg 2\n    _t0 := call g\n    arg 1\n    arg _t0\n    tailcall g\n"
+        );
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let instructions = gen(r#"extern func puts(s: str): int32;
+            func f() { puts("tab\t\"quote\"\n\x01"); }"#);
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    arg \"tab\\t\\\"quote\\\"\\n\\x01\"\n    tailcall puts\n"
+        );
+    }
+
+    #[test]
+    fn float_operations() {
+        let instructions = gen("func f(x: float32): float64 { ret -(x * 2.5) as float64; }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    _t0 := f32 x * 2.5\n    _t1 := f32 -0.0 - _t0\n    _t2 := f32 _t1 as f64\n    ret _t2\n"
+        );
+    }
+
+    #[test]
+    fn variables_read_before_assignment() {
+        let instructions = gen("func f(a: int32): int32 { ret a + (a = 1); }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    _t0 := a\n    a := 1\n    _t1 := i32 _t0 + a\n    ret _t1\n"
+        );
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let instructions = gen(
+            "func f(a: int32, b: int32): bool { ret a != 0 && b / a > 2; }
+            func g(a: int32, b: int32): bool { ret a == 0 || b / a > 2; }",
+        );
+
+        // the division only runs if the left operand doesn't decide the result
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    _t0 := i32 a != 0
+    $r0 := _t0
+    ifz _t0 goto _L0
+    _t1 := i32 b / a
+    _t2 := i32 _t1 > 2
+    $r0 := _t2
+_L0:
+    ret $r0
+g:
+    _t3 := i32 a == 0
+    $r1 := _t3
+    ifz _t3 goto _L2
+    goto _L1
+_L2:
+    _t4 := i32 b / a
+    _t5 := i32 _t4 > 2
+    $r1 := _t5
+_L1:
+    ret $r1
+"
+        );
+    }
+
+    #[test]
+    fn chars_are_code_points() {
+        let instructions = gen("func f(c: char, b: uint8): uint32 {
+                if c == '\u{1F600}' { ret c as uint32; }
+                ret (b as char) as uint32;
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    _t0 := u32 c == 128512
+    ifz _t0 goto _L0
+    ret c
+_L0:
+    _t1 := u8 b as u32
+    ret _t1
+"
+        );
+    }
+
+    #[test]
+    fn newtypes_are_their_wrapped_type() {
+        let instructions = gen("newtype Id = uint8;
+            func f(id: Id): uint32 { var two = 2 as Id; ret (id as uint8 + two as uint8) as uint32; }");
+
+        // converting between a newtype and the type it wraps doesn't change the value
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    two := 2
+    _t0 := u8 id + two
+    _t1 := u8 _t0 as u32
+    ret _t1
+"
+        );
+    }
+
+    #[test]
+    fn variadic_arguments_are_promoted() {
+        let instructions = gen("extern func printf(fmt: str, ...): int32;
+            func f(a: uint8, b: float32, c: int64) { printf(\"\", a, b, c, true); }");
+
+        // only the arguments after the parameters are promoted
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    arg \"\"
+    _t0 := u8 a as i32
+    arg _t0
+    _t1 := f32 b as f64
+    arg _t1
+    arg c
+    _t2 := bool 1 as i32
+    arg _t2
+    tailcall printf
+"
+        );
+    }
+}
+
+mod constants {
+    use super::*;
+
+    #[test]
+    fn constants_are_inlined() {
+        let instructions = gen("const BASE: int32 = 10;
+            const NEXT: int32 = BASE * BASE + 1;
+            func f(d: int32): int32 {
+                const ZERO: int32 = '0' as int32;
+                ret d % NEXT + ZERO;
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    _t0 := i32 d % 101\n    _t1 := i32 _t0 + 48\n    ret _t1\n"
+        );
+    }
+}
+
+mod globals {
+    use super::*;
+
+    #[test]
+    fn constant_initializers_are_static() {
+        let codegen = generator(
+            "const STEP: int32 = 2;
+            var count = STEP * 5;
+            var on = true;
+            func tick(): int32 {
+                count = count + STEP;
+                var on = 1;
+                ret count + on;
+            }",
+        );
+        assert_eq!(
+            codegen.intermediate_code(),
+            "global count, size 4, align 4 = i32 10\n\
+             global on, size 1, align 1 = bool 1\n\
+             \n\
+             tick:\n    _t0 := i32 @count[0]\n    _t1 := i32 _t0 + 2\n    @count[0] := i32 _t1\n    \
+             on := 1\n    _t2 := i32 @count[0]\n    _t3 := i32 _t2 + on\n    ret _t3\n"
+        );
+    }
+
+    #[test]
+    fn other_initializers_run_in_the_init_function() {
+        let codegen = generator(
+            "extern func seed(): int32;
+            var a = seed();
+            var b = a + 1;
+            struct P { x: int32, y: int32 }
+            var p = P { x: 1, y: 2 };",
+        );
+        assert_eq!(
+            codegen.intermediate_code(),
+            "global a, size 4, align 4\n\
+             global b, size 4, align 4\n\
+             global p, size 8, align 4\n\
+             \n\
+             $init:\n    _t0 := call seed\n    @a[0] := i32 _t0\n    \
+             _t1 := i32 @a[0]\n    _t2 := i32 _t1 + 1\n    @b[0] := i32 _t2\n    \
+             local _t3, size 8, align 4\n    _t3[0] := i32 1\n    _t3[4] := i32 2\n    \
+             _t4 := i32 _t3[0]\n    @p[0] := i32 _t4\n    _t5 := i32 _t3[4]\n    @p[4] := i32 _t5\n    \
+             ret\n"
+        );
+    }
+
+    #[test]
+    fn top_level_statements_run_in_the_init_function() {
+        let codegen = generator(
+            "extern func tick(): int32;
+            var n = 1;
+            tick();
+            { var t = tick(); n = t; }
+            func f(): int32 { ret n; }",
+        );
+        assert_eq!(
+            codegen.intermediate_code(),
+            "global n, size 4, align 4 = i32 1\n\
+             \n\
+             f:\n    _t2 := i32 @n[0]\n    ret _t2\n\
+             $init:\n    _t0 := call tick\n    _t1 := call tick\n    t := _t1\n    \
+             @n[0] := i32 t\n    ret\n"
+        );
+    }
+
+    #[test]
+    fn global_addresses() {
+        let instructions = gen("var n = 0;
+            func f(): *int32 { ret &n; }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    _t0 := &@n\n    ret _t0\n"
+        );
+    }
+}
+
+mod methods {
+    use super::*;
+
+    #[test]
+    fn methods_are_called_by_their_type() {
+        let instructions = gen("struct Counter { n: int32 }
+            impl Counter {
+                func new(): Counter { ret Counter { n: 0 }; }
+                func get(self): int32 { ret self.n; }
+                func add(self: *Counter, by: int32) { (*self).n = self.get() + by; }
+            }
+            func f() {
+                var c = Counter::new();
+                c.add(1);
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "Counter::new:\n    local _t0, size 4, align 4\n    _t0[0] := i32 0\n    ret _t0\n\
+             Counter::get:\n    local self, size 4, align 4\n    _t1 := i32 self[0]\n    ret _t1\n\
+             Counter::add:\n    local _t2, size 4, align 4\n    _t3 := i32 *self\n    _t2[0] := i32 _t3\n    \
+             arg _t2\n    _t4 := call Counter::get\n    _t5 := i32 _t4 + by\n    *self := i32 _t5\n    ret\n\
+             f:\n    local _t6, size 4, align 4\n    _t6 := call Counter::new\n    local c, size 4, align 4\n    \
+             c := _t6\n    _t7 := &c\n    arg _t7\n    arg 1\n    _t8 := call Counter::add\n    ret\n"
+        );
+    }
+}
+
+mod closures {
+    use super::*;
+
+    #[test]
+    fn lambdas_copy_their_captures_into_an_environment() {
+        let instructions = gen("func adder(n: int32): func(int32): int32 {
+                ret func(x: int32): int32 { ret x + n; };
+            }
+            func f(): int32 { ret adder(1)(2); }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "adder:
+    _t2 := alloc size 4, align 4
+    *_t2 := i32 n
+    _t3 := &func adder$lambda0
+    local _t4, size 16, align 8
+    _t4[0] := ptr _t3
+    _t4[8] := ptr _t2
+    ret _t4
+f:
+    arg 1
+    local _t5, size 16, align 8
+    _t5 := call adder
+    _t6 := ptr _t5[0]
+    _t7 := ptr _t5[8]
+    arg _t7
+    arg 2
+    _t8 := call *_t6
+    ret _t8
+adder$lambda0:
+    _t0 := i32 *$env
+    n := _t0
+    _t1 := i32 x + n
+    ret _t1
+"
+        );
+    }
+
+    #[test]
+    fn functions_as_values_are_called_through_a_wrapper() {
+        let instructions = gen("func neg(a: int32): int32 { ret -a; }
+            func f(): func(int32): int32 { ret neg; }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "neg:
+    _t0 := i32 0 - a
+    ret _t0
+f:
+    _t1 := &func neg$closure
+    local _t2, size 16, align 8
+    _t2[0] := ptr _t1
+    _t2[8] := ptr 0
+    ret _t2
+neg$closure:
+    arg a
+    tailcall neg
+"
+        );
+    }
+}
+
+mod structs {
+    use super::*;
+
+    #[test]
+    fn fields_are_laid_out_like_c() {
+        let instructions = gen("struct S { a: uint8, b: int64, c: int16 }
+            func f(): int16 {
+                var s = S { a: 1, b: 2, c: 3 };
+                ret s.c;
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    local _t0, size 24, align 8
+    _t0[0] := u8 1
+    _t0[8] := i64 2
+    _t0[16] := i16 3
+    local s, size 24, align 8
+    s := _t0
+    _t1 := i16 s[16]
+    ret _t1
+"
+        );
+    }
+
+    #[test]
+    fn clones_get_their_own_memory() {
+        let instructions = gen("struct P { x: int32 }
+            func f(p: P, n: int32): P {
+                var m = clone n;
+                var q = clone p;
+                ret move q;
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    local p, size 4, align 4
+    m := n
+    local _t0, size 4, align 4
+    _t0 := p
+    local q, size 4, align 4
+    q := _t0
+    ret q
+"
+        );
+    }
+
+    #[test]
+    fn nested_fields() {
+        let instructions = gen("struct Point { x: int32, y: int32 }
+            struct Line { from: Point, to: Point }
+            func f(l: Line): Point {
+                l.to.y = l.from.x;
+                ret l.from;
+            }");
+
+        // nested fields are a single load or store at the sum of the offsets, and a struct field
+        // is copied one field at a time
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    local l, size 16, align 4
+    _t0 := i32 l[0]
+    l[12] := i32 _t0
+    local _t1, size 8, align 4
+    _t2 := i32 l[0]
+    _t1[0] := i32 _t2
+    _t3 := i32 l[4]
+    _t1[4] := i32 _t3
+    ret _t1
+"
+        );
+    }
+}
+
+mod arrays {
+    use super::*;
+
+    fn gen_checked(source_code: &'static str) -> Vec<Instruction> {
+        let source = Source::new(source_code, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+        assert!(parser.errors().is_empty(), "failed to parse");
+
+        let mut typechk = TypeChecker::new();
+        typechk.check(&module);
+        let mut codegen = CodeGenerator::new(typechk.into_types()).bounds_checks(true);
+        codegen.gen(&module);
+        codegen.instructions
+    }
+
+    #[test]
+    fn array_elements() {
+        let instructions = gen("func f(i: int32): int16 {
+                var a = [1 as int16, 2];
+                a[1] = 3;
+                ret a[i];
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    local _t0, size 4, align 2
+    _t0[0] := i16 1
+    _t0[2] := i16 2
+    local a, size 4, align 2
+    a := _t0
+    a[2] := i16 3
+    _t1 := i32 i as u64
+    _t2 := u64 _t1 * 2
+    _t3 := i16 a[_t2]
+    ret _t3
+"
+        );
+    }
+
+    #[test]
+    fn arrays_are_passed_as_slices() {
+        let instructions = gen("extern func sum(xs: [int32]): int32;
+            func f(a: [int32; 3]): int32 { ret sum(a); }");
+
+        // the slice points to the array and holds its length
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    local a, size 12, align 4
+    _t0 := &a
+    local _t1, size 16, align 8
+    _t1[0] := ptr _t0
+    _t1[8] := u64 3
+    arg _t1
+    _t2 := call sum
+    ret _t2
+"
+        );
+    }
+
+    #[test]
+    fn bounds_checks() {
+        let unchecked = gen("func f(s: [uint8], i: uint64): uint8 { ret s[i]; }");
+        assert!(!unchecked.contains(&Instruction::Trap));
+
+        let checked = gen_checked("func f(s: [uint8], i: uint64): uint8 { ret s[i]; }");
+        assert_eq!(
+            ic::to_string(&checked),
+            "f:
+    local s, size 16, align 8
+    _t0 := ptr s[0]
+    _t1 := u64 s[8]
+    _t2 := u64 i >= _t1
+    ifz _t2 goto _L0
+    trap
+_L0:
+    _t4 := ptr _t0 + i
+    _t3 := u8 *_t4
+    ret _t3
+"
+        );
+
+        // constant indices of arrays are checked by the type checker
+        let constant = gen_checked("func f(a: [uint8; 2]): uint8 { ret a[1]; }");
+        assert!(!constant.contains(&Instruction::Trap));
+    }
+}
+
+mod pointers {
+    use super::*;
+
+    #[test]
+    fn address_taken_variables_are_locals() {
+        let instructions = gen("extern func scan(out: *int32);
+            func f(): int32 {
+                var n = 0;
+                var m = 1;
+                scan(&n);
+                ret n + m;
+            }");
+
+        // `n` has to be in memory for `scan` to write to it, `m` doesn't
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    local n, size 4, align 4
+    n := 0
+    m := 1
+    _t0 := &n
+    arg _t0
+    _t1 := call scan
+    _t2 := i32 n + m
+    ret _t2
+"
+        );
+    }
+
+    #[test]
+    fn loads_and_stores_through_pointers() {
+        let instructions = gen("struct P { x: int32, y: int32 }
+            func f(p: *P, q: **int32): int32 {
+                (*p).y = **q;
+                ret (*p).y;
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    _t0 := ptr *q
+    _t1 := i32 *_t0
+    _t2 := ptr p + 4
+    *_t2 := i32 _t1
+    _t4 := ptr p + 4
+    _t3 := i32 *_t4
+    ret _t3
+"
+        );
+    }
+}
+
+mod generics {
+    use super::*;
+
+    #[test]
+    fn each_instance_is_generated_once() {
+        let instructions = gen("func max<T>(a: T, b: T): T {
+                if a > b { ret a; }
+                ret b;
+            }
+            func f(a: uint8, b: int64): int64 {
+                var x = max(a, 1);
+                var y = max(a, x);
+                ret max(b, 2);
+            }");
+
+        let labels: Vec<String> = instructions
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Label(label) if !label.starts_with("_L") => Some(label.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(labels, ["max$uint8", "max$int64", "f"]);
+        assert!(ic::to_string(&instructions).contains("_t0 := u8 a > b"));
+        assert!(ic::to_string(&instructions).contains("tailcall max$int64"));
+    }
+
+    #[test]
+    fn generic_structs_are_laid_out_per_instance() {
+        let instructions = gen("struct Pair<A, B> { first: A, second: B }
+            func last<A, B>(p: Pair<A, B>, n: uint64): B {
+                if n == 0 { ret p.second; }
+                ret last(p, n - 1);
+            }
+            func f(): int64 {
+                ret last(Pair { first: true, second: 1 as int64 }, 3);
+            }");
+
+        assert_eq!(
+            ic::to_string(&instructions),
+            "last$bool$int64:
+    local p, size 16, align 8
+last$bool$int64$body:
+    _t0 := u64 n == 0
+    ifz _t0 goto _L0
+    _t1 := i64 p[8]
+    ret _t1
+_L0:
+    _t2 := u64 n - 1
+    n := _t2
+    goto last$bool$int64$body
+f:
+    local _t3, size 16, align 8
+    _t3[0] := bool 1
+    _t3[8] := i64 1
+    arg _t3
+    arg 3
+    tailcall last$bool$int64
+"
+        );
+    }
+}
+
+mod enums {
+    use super::*;
+
+    #[test]
+    fn variants_store_their_tag_and_fields() {
+        let instructions = gen("enum Shape { Circle(uint8), Rect(int32, int64), Empty }
+            struct Canvas { shape: Shape }
+            func f(c: Canvas): Shape {
+                c.shape = Shape::Rect(1, 2);
+                ret c.shape;
+            }");
+
+        // the payloads start after the tag and enums are copied one chunk of their alignment at a
+        // time, whatever their variant is
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    local c, size 16, align 8
+    local _t0, size 16, align 8
+    _t0[0] := u32 1
+    _t0[4] := i32 1
+    _t0[8] := i64 2
+    _t1 := u64 _t0[0]
+    c[0] := u64 _t1
+    _t2 := u64 _t0[8]
+    c[8] := u64 _t2
+    local _t3, size 16, align 8
+    _t4 := u64 c[0]
+    _t3[0] := u64 _t4
+    _t5 := u64 c[8]
+    _t3[8] := u64 _t5
+    ret _t3
+"
+        );
+    }
+
+    #[test]
+    fn match_tests_each_arm_in_order() {
+        let instructions = gen("enum Shape { Circle(int32), Rect(int32, int32) }
+            func area(s: Shape): int32 {
+                match s {
+                    Shape::Rect(w, 0) => { ret 0; }
+                    Shape::Rect(w, h) => { ret w * h; }
+                    Shape::Circle(r) => { ret 3 * r * r; }
+                }
+            }");
+
+        // the last arm doesn't test anything, the match is exhaustive
+        assert_eq!(
+            ic::to_string(&instructions),
+            "area:
+    local s, size 12, align 4
+    _t0 := u32 s[0]
+    _t1 := u32 _t0 == 1
+    ifz _t1 goto _L1
+    _t2 := i32 s[8]
+    _t3 := i32 _t2 == 0
+    ifz _t3 goto _L1
+    _t4 := i32 s[4]
+    w := _t4
+    ret 0
+    goto _L0
+_L1:
+    _t5 := u32 s[0]
+    _t6 := u32 _t5 == 1
+    ifz _t6 goto _L2
+    _t7 := i32 s[4]
+    w := _t7
+    _t8 := i32 s[8]
+    h := _t8
+    _t9 := i32 w * h
+    ret _t9
+    goto _L0
+_L2:
+    _t10 := i32 s[4]
+    r := _t10
+    _t11 := i32 3 * r
+    _t12 := i32 _t11 * r
+    ret _t12
+_L0:
+"
+        );
+    }
+
+    #[test]
+    fn dense_integer_matches_use_a_jump_table() {
+        let instructions = gen("extern func g(x: int8);
+            func f(x: int8) {
+                match x {
+                    -1 => { g(1); }
+                    0 => { g(2); }
+                    1 => { g(3); }
+                    3 => { g(4); }
+                    n => { g(n); }
+                }
+            }");
+
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:
+    _t0 := i8 x as u64
+    _t1 := u64 _t0 - -1
+    _t2 := u64 _t1 < 5
+    ifz _t2 goto _L1
+    jumptable _t1, [_L2, _L3, _L4, _L1, _L5]
+_L2:
+    arg 1
+    tailcall g
+    goto _L0
+_L3:
+    arg 2
+    tailcall g
+    goto _L0
+_L4:
+    arg 3
+    tailcall g
+    goto _L0
+_L5:
+    arg 4
+    tailcall g
+    goto _L0
+_L1:
+    n := x
+    arg n
+    tailcall g
+_L0:
+    ret
+"
+        );
+
+        // sparse values are tested one at a time
+        let instructions = gen("func f(x: int32) {
+                match x { 1 => { } 10 => { } 100 => { } 1000 => { } _ => { } }
+            }");
+        assert!(!instructions
+            .iter()
+            .any(|i| matches!(i, Instruction::JumpTable(..))));
+    }
+}
+
+mod tuples {
+    use super::*;
+
+    #[test]
+    fn tuples_are_returned_and_destructured() {
+        let instructions = gen(
+            "func divmod(a: int32, b: int32): (int32, int32) { ret (a / b, a % b); }
+            func f(): int32 {
+                var (q, r) = divmod(7, 2);
+                var t = ((q, true), r);
+                var ((x, _), y) = t;
+                ret x + t.1;
+            }",
+        );
+
+        // the first element of a tuple is at the same place as the whole tuple, but only its own
+        // bytes are loaded
+        assert_eq!(
+            ic::to_string(&instructions),
+            "divmod:
+    local _t0, size 8, align 4
+    _t1 := i32 a / b
+    _t0[0] := i32 _t1
+    _t2 := i32 a % b
+    _t0[4] := i32 _t2
+    ret _t0
+f:
+    arg 7
+    arg 2
+    local _t3, size 8, align 4
+    _t3 := call divmod
+    _t4 := i32 _t3[0]
+    q := _t4
+    _t5 := i32 _t3[4]
+    r := _t5
+    local _t6, size 12, align 4
+    local _t7, size 8, align 4
+    _t7[0] := i32 q
+    _t7[4] := bool 1
+    _t8 := i32 _t7[0]
+    _t6[0] := i32 _t8
+    _t9 := bool _t7[4]
+    _t6[4] := bool _t9
+    _t6[8] := i32 r
+    local t, size 12, align 4
+    t := _t6
+    _t10 := i32 t[0]
+    x := _t10
+    _t11 := i32 t[8]
+    y := _t11
+    _t12 := i32 t[8]
+    _t13 := i32 x + _t12
+    ret _t13
+"
+        );
+    }
+}
+
+mod tail_calls {
+    use super::*;
+
+    #[test]
+    fn self_tail_call() {
+        let instructions = gen("func fact(n: int32, acc: int32): int32 {
+                if n == 0 {
+                    ret acc;
+                }
+                ret fact(n - 1, n * acc);
+            }");
+
+        // the recursive call should be replaced by a jump back to the start of the function
+        assert_eq!(calls(&instructions), 0);
+        assert!(matches!(instructions.last(), Some(Instruction::Goto(label)) if *label == "fact"));
+
+        // both parameters are reassigned before the jump
+        let reassigned: Vec<_> = instructions
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Assign(var, _) if *var == "n" || *var == "acc" => Some(*var),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reassigned, ["n", "acc"]);
+    }
+
+    #[test]
+    fn swapped_parameters() {
+        let instructions = gen("func gcd(a: int32, b: int32): int32 {
+                if b == 0 {
+                    ret a;
+                }
+                ret gcd(b, a % b);
+            }");
+
+        // `b` is passed as `a`, so the old value of `a` has to be used to compute the new `b`
+        let code = ic::to_string(&instructions);
+        assert!(code.ends_with("    _t1 := i32 a % b\n    a := b\n    b := _t1\n    goto gcd\n"));
+    }
+
+    #[test]
+    fn tail_calls_in_branches() {
+        let instructions = gen("extern func putchar(c: int32): int32;
+            func countdown(n: int32) {
+                if n > 0 {
+                    putchar(n);
+                    countdown(n - 1);
+                } else {
+                    putchar(0);
+                }
+            }");
+
+        // `putchar(n)` isn't in tail position, the `countdown` and the last `putchar` are
+        assert_eq!(calls(&instructions), 1);
+        assert!(instructions
+            .iter()
+            .any(|i| matches!(i, Instruction::Goto(label) if *label == "countdown")));
+        assert!(instructions
+            .iter()
+            .any(|i| matches!(i, Instruction::TailCall(func) if *func == "putchar")));
+    }
+
+    #[test]
+    fn non_tail_calls() {
+        let instructions = gen("func fib(n: int32): int32 {
+                if n < 2 {
+                    ret n;
+                }
+                ret fib(n - 1) + fib(n - 2);
+            }");
+
+        // the result of the recursive calls are used so neither of them are tail calls
+        assert_eq!(calls(&instructions), 2);
+        assert!(!instructions
+            .iter()
+            .any(|i| matches!(i, Instruction::TailCall(_) | Instruction::Goto(_))));
+    }
+
+    #[test]
+    fn early_void_ret() {
+        let instructions = gen("extern func g();
+            func f(c: bool) {
+                if c {
+                    ret;
+                }
+                g();
+            }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    ifz c goto _L0\n    ret\n_L0:\n    tailcall g\n"
+        );
+    }
+
+    #[test]
+    fn void_functions_return_at_the_end() {
+        let instructions = gen("func set(p: *int32) {
+                *p = 1;
+            }
+            func two(): int32 {
+                ret 2;
+            }");
+
+        // `set` doesn't fall through into `two`
+        assert_eq!(
+            ic::to_string(&instructions),
+            "set:\n    *p := i32 1\n    ret\ntwo:\n    ret 2\n"
+        );
+    }
+
+    #[test]
+    fn no_tail_calls_after_address_taken() {
+        let instructions = gen("extern func g(p: *int32): int32;
+            func f(): int32 {
+                var x = 1;
+                ret g(&x);
+            }
+            func h(n: int32, p: *int32): int32 {
+                var y = n;
+                if n == 0 {
+                    ret *p;
+                }
+                ret h(n - 1, &y);
+            }");
+
+        // `g` and the next call of `h` can use `x` and `y`, so the frames have to stay around
+        assert_eq!(calls(&instructions), 2);
+        assert!(!instructions
+            .iter()
+            .any(|i| matches!(i, Instruction::TailCall(_) | Instruction::Goto(_))));
+    }
+
+    #[test]
+    fn no_tail_calls_with_arrays_as_slices() {
+        let instructions = gen("extern func sum(s: [int32]): int32;
+            func f(): int32 {
+                ret sum([1, 2]);
+            }
+            func g(): int32 {
+                var x = [1];
+                ret sum(x);
+            }
+            func h(s: [int32], n: int32): int32 {
+                if n == 0 {
+                    ret 0;
+                }
+                var y = [n];
+                ret h(y, n - 1);
+            }");
+
+        // passing an array as a slice takes its address, like `&` does
+        assert_eq!(calls(&instructions), 3);
+        assert!(!instructions
+            .iter()
+            .any(|i| matches!(i, Instruction::TailCall(_) | Instruction::Goto(_))));
+        assert!(instructions
+            .iter()
+            .all(|i| !matches!(i, Instruction::Ret(None))));
+    }
+
+    #[test]
+    fn self_tail_call_skips_prologue() {
+        let instructions = gen("struct P { x: int32, y: int32 }
+            func sum(p: P, n: int32): int32 {
+                if n == 0 {
+                    ret p.x;
+                }
+                ret sum(p, n - 1);
+            }");
+
+        // the jump goes past the `local` of the parameter
+        let code = ic::to_string(&instructions);
+        assert!(code.starts_with("sum:\n    local p, size 8, align 4\nsum$body:\n"));
+        assert!(code.ends_with("    goto sum$body\n"));
+    }
+}
+
+mod passes {
+    use super::super::ic::Expr;
+    use super::super::pass::PassManager;
+    use super::*;
+
+    fn optimize(instructions: &mut Vec<Instruction>, passes: &[&str]) {
+        PassManager::with_passes(passes)
+            .expect("unknown pass")
+            .run(instructions);
+    }
+
+    #[test]
+    fn unknown_pass() {
+        assert!(PassManager::with_passes(&["cse", "foo"]).is_err());
+        assert!(PassManager::new(0).dump_after(vec!["bar".into()]).is_err());
+
+        // `-O1` doesn't run `copy-prop`, so nothing would be dumped after it
+        let manager = PassManager::new(1)
+            .dump_after(vec!["copy-prop".into(), "cse".into(), "all".into()])
+            .unwrap();
+        assert_eq!(manager.skipped_dumps(), ["copy-prop"]);
+    }
+
+    #[test]
+    fn no_optimizations() {
+        let source = "func add(a: int32, b: int32): int32 { ret a + b; }";
+        let mut instructions = gen(source);
+        PassManager::new(0).run(&mut instructions);
+        assert_eq!(instructions, gen(source));
+    }
+
+    #[test]
+    fn cse_respects_reassignment() {
+        let mut instructions = gen("func f(d: int32): int32 {
+                var a = d / 10;
+                d = 0 - d;
+                var b = d / 10;
+                ret a + b;
+            }");
+        optimize(&mut instructions, &["copy-prop", "cse", "dce"]);
+
+        // `d` changes between the two divisions so the second one can't reuse the first
+        let divisions = instructions
+            .iter()
+            .filter(|i| matches!(i, Instruction::Assign(_, Expr::Binary(ic::BinOp::Div, ..))))
+            .count();
+        assert_eq!(divisions, 2);
+    }
+
+    #[test]
+    fn loads_after_pointer_writes() {
+        let mut instructions = gen("extern func clear(xs: [int32]);
+            func f(a: [int32; 2]): int32 {
+                var x = a[0];
+                clear(a);
+                ret x + a[0];
+            }");
+        optimize(&mut instructions, &["copy-prop", "cse", "dce"]);
+
+        // `clear` can change `a` through the slice, so `a[0]` has to be loaded again
+        let loads = instructions
+            .iter()
+            .filter(|i| matches!(i, Instruction::Assign(_, Expr::Load(..))))
+            .count();
+        assert_eq!(loads, 2);
+    }
+
+    #[test]
+    fn reads_after_pointer_writes() {
+        let mut instructions = gen("func f(p: *int32): int32 {
+                var n = 1;
+                var a = n + 2;
+                var q = &n;
+                *q = 5;
+                ret a + (n + 2);
+            }");
+        optimize(&mut instructions, &["copy-prop", "cse", "dce"]);
+
+        // `*q = 5` changes `n`, so neither `n := 1` nor `n + 2` can be reused after it
+        let code = ic::to_string(&instructions);
+        assert!(code.contains("_t2 := i32 n + 2\n"), "{code}");
+    }
+
+    #[test]
+    fn globals_after_calls() {
+        let mut instructions = gen("extern func g();
+            var n = 1;
+            func f(): int32 {
+                var a = n;
+                g();
+                ret a + n;
+            }");
+        optimize(&mut instructions, &["copy-prop", "cse", "dce"]);
+
+        // `g` can change `n`, so it has to be loaded again
+        let loads = instructions
+            .iter()
+            .filter(|i| matches!(i, Instruction::Assign(_, Expr::LoadGlobal(..))))
+            .count();
+        assert_eq!(loads, 2);
+    }
+
+    #[test]
+    fn dead_code() {
+        let mut instructions = gen("func f(): int32 {
+                var x = 1 + 2;
+                ret 3;
+            }");
+        optimize(&mut instructions, &["dce"]);
+
+        // `x` is a variable so it stays even though it isn't read
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    _t0 := i32 1 + 2\n    x := _t0\n    ret 3\n"
+        );
+    }
+}
+
+mod simplify {
+    use super::super::ic::{BinOp, ConstKind, Expr, Primary};
+    use super::super::optimize::simplify;
+    use super::super::pass::PassManager;
+    use super::*;
+
+    use std::collections::HashMap;
+
+    /// Runs the straight-line code of a function and returns the value it returns
+    fn eval(instructions: &[Instruction], params: &[(&str, i64)]) -> i64 {
+        let mut vars: HashMap<String, i64> = params
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        let value = |vars: &HashMap<String, i64>, primary: &Primary| match primary {
+            Primary::Var(var) => vars[var.as_str()],
+            Primary::Const(ConstKind::Int(k)) => *k,
+            _ => panic!("expected an integer"),
+        };
+
+        for instruction in instructions {
+            match instruction {
+                // the values in these tests fit in every type
+                Instruction::Assign(var, Expr::Primary(x) | Expr::Cast(_, x, _)) => {
+                    vars.insert(var.to_string(), value(&vars, x));
+                }
+                Instruction::Assign(var, Expr::Binary(op, _, lhs, rhs)) => {
+                    let (lhs, rhs) = (value(&vars, lhs), value(&vars, rhs));
+                    let result = match op {
+                        BinOp::Add => lhs.wrapping_add(rhs),
+                        BinOp::Sub => lhs.wrapping_sub(rhs),
+                        BinOp::Mul => lhs.wrapping_mul(rhs),
+                        BinOp::Div => lhs / rhs,
+                        BinOp::Mod => lhs % rhs,
+                        BinOp::Shl => lhs << rhs,
+                        BinOp::Shr => lhs >> rhs,
+                        BinOp::BitAnd => lhs & rhs,
+                        BinOp::Eq => (lhs == rhs).into(),
+                        BinOp::Lt => (lhs < rhs).into(),
+                        _ => unimplemented!("{op}"),
+                    };
+                    vars.insert(var.to_string(), result);
+                }
+                Instruction::Ret(Some(x)) => return value(&vars, x),
+                Instruction::Label(_) => {}
+                _ => panic!("unexpected instruction `{instruction}`"),
+            }
+        }
+
+        panic!("expected a return");
+    }
+
+    fn simplified(source_code: &'static str) -> Vec<Instruction> {
+        let mut instructions = gen(source_code);
+        simplify(&mut instructions);
+        instructions
+    }
+
+    #[test]
+    fn identities() {
+        let instructions = simplified(
+            "func f(x: int32): int32 {
+                var a = x + 0;
+                var b = x * 1;
+                var c = x * 0;
+                var d = x - x;
+                var e = x / 1;
+                var g = x == x;
+                ret (x < x) as int32;
+            }",
+        );
+
+        assert!(instructions
+            .iter()
+            .all(|i| !matches!(i, Instruction::Assign(_, Expr::Binary(..)))));
+        assert_eq!(eval(&instructions, &[("x", 5)]), 0);
+    }
+
+    #[test]
+    fn strength_reduction() {
+        let instructions = simplified("func f(x: int32): int32 { ret x * 8; }");
+        assert_eq!(
+            ic::to_string(&instructions),
+            "f:\n    _t0 := i32 x << 3\n    ret _t0\n"
+        );
+    }
+
+    #[test]
+    fn signed_division_and_modulo() {
+        let div = simplified("func f(x: int32): int32 { ret x / 4; }");
+        let rem = simplified("func f(x: int32): int32 { ret x % 4; }");
+        assert!(div.iter().chain(&rem).all(|i| !matches!(
+            i,
+            Instruction::Assign(_, Expr::Binary(BinOp::Div | BinOp::Mod, ..))
+        )));
+
+        // negative numbers have to round toward zero
+        for x in -17..=17 {
+            assert_eq!(eval(&div, &[("x", x)]), x / 4, "{x} / 4");
+            assert_eq!(eval(&rem, &[("x", x)]), x % 4, "{x} % 4");
+        }
+    }
+
+    #[test]
+    fn chained_identities_at_o2() {
+        // each identity only shows up once the one before it is simplified and propagated
+        let mut instructions = gen("func f(a: int32): int32 { ret a * 0 + a - a; }");
+        PassManager::new(2).run(&mut instructions);
+        assert_eq!(ic::to_string(&instructions), "f:\n    ret 0\n");
+    }
+
+    #[test]
+    fn unsigned_division_and_modulo() {
+        // unsigned values are never negative so no rounding fix up is needed
+        let div = simplified("func f(x: uint32): uint32 { ret x / 8; }");
+        let rem = simplified("func f(x: uint32): uint32 { ret x % 8; }");
+        assert_eq!(
+            ic::to_string(&div),
+            "f:\n    _t0 := u32 x >> 3\n    ret _t0\n"
+        );
+        assert_eq!(
+            ic::to_string(&rem),
+            "f:\n    _t0 := u32 x & 7\n    ret _t0\n"
+        );
+    }
+}
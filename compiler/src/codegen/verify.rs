@@ -0,0 +1,103 @@
+//! Sanity checks for the intermediate code, these are run between optimization passes to catch
+//! passes that break the code they are given
+
+use super::ic::{is_tmp_var, Expr, Instruction, Primary};
+use crate::name::Name;
+
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+/// Checks that:
+/// - every label is defined only once
+/// - every jump goes to a label that exists
+/// - every temporary is assigned only once and before it is read, unless it is a struct declared
+///   with `local` whose fields are stored to
+/// - only variables declared with `local` have their address taken
+pub fn verify(instructions: &[Instruction]) -> Result<()> {
+    let mut labels = HashSet::new();
+    for instruction in instructions {
+        if let Instruction::Label(label) = instruction {
+            if !labels.insert(*label) {
+                bail!("label `{label}` is defined more than once");
+            }
+        }
+    }
+
+    let mut tmp_vars: HashSet<Name> = HashSet::new();
+    let mut locals: HashSet<Name> = HashSet::new();
+    let read_var = |var: Name, tmp_vars: &HashSet<Name>, locals: &HashSet<Name>| {
+        if is_tmp_var(var) && !tmp_vars.contains(&var) && !locals.contains(&var) {
+            bail!("temporary `{var}` is read before it is assigned")
+        }
+        Ok(())
+    };
+    let read = |value: &Primary, tmp_vars: &HashSet<Name>, locals: &HashSet<Name>| match value {
+        Primary::Var(var) => read_var(*var, tmp_vars, locals),
+        Primary::Const(_) => Ok(()),
+    };
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::Goto(label) | Instruction::Ifz(_, label) if !labels.contains(label) => {
+                bail!("`{instruction}` jumps to a label that doesn't exist")
+            }
+            Instruction::JumpTable(value, targets) => {
+                if let Some(label) = targets.iter().find(|label| !labels.contains(label)) {
+                    bail!("`{instruction}` jumps to `{label}` which doesn't exist");
+                }
+                read(value, &tmp_vars, &locals)?;
+            }
+            Instruction::Assign(var, expr) => {
+                match expr {
+                    Expr::Binary(_, _, lhs, rhs) => {
+                        read(lhs, &tmp_vars, &locals)?;
+                        read(rhs, &tmp_vars, &locals)?;
+                    }
+                    Expr::Primary(value)
+                    | Expr::Cast(_, value, _)
+                    | Expr::Deref(_, value)
+                    | Expr::CallIndirect(value) => read(value, &tmp_vars, &locals)?,
+                    Expr::Addr(var) => {
+                        if !locals.contains(var) {
+                            bail!(
+                                "`{instruction}` takes the address of `{var}` which isn't a local"
+                            );
+                        }
+                    }
+                    Expr::Load(_, base, offset) => {
+                        read_var(*base, &tmp_vars, &locals)?;
+                        read(offset, &tmp_vars, &locals)?;
+                    }
+                    Expr::LoadGlobal(_, _, offset) => read(offset, &tmp_vars, &locals)?,
+                    Expr::GlobalAddr(_) | Expr::Call(_) | Expr::FuncAddr(_) | Expr::Alloc(_) => {}
+                }
+
+                if is_tmp_var(*var) && !tmp_vars.insert(*var) {
+                    bail!("temporary `{var}` is assigned more than once");
+                }
+            }
+            Instruction::Ifz(value, _)
+            | Instruction::Arg(value)
+            | Instruction::Ret(Some(value)) => read(value, &tmp_vars, &locals)?,
+            Instruction::StoreDeref(ptr, _, value) => {
+                read(ptr, &tmp_vars, &locals)?;
+                read(value, &tmp_vars, &locals)?;
+            }
+            Instruction::Local(var, _) => {
+                locals.insert(*var);
+            }
+            Instruction::Store(base, offset, _, value) => {
+                read_var(*base, &tmp_vars, &locals)?;
+                read(offset, &tmp_vars, &locals)?;
+                read(value, &tmp_vars, &locals)?;
+            }
+            Instruction::StoreGlobal(_, offset, _, value) => {
+                read(offset, &tmp_vars, &locals)?;
+                read(value, &tmp_vars, &locals)?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
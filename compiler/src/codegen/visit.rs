@@ -4,8 +4,10 @@
 
 use crate::name::Name;
 
-use super::ic::{Expr, Primary};
+use super::ic::{Expr, Primary, Ty};
+use super::layout::Layout;
 
+#[allow(dead_code)]
 pub trait InstructionVistor<T> {
     fn visit_label(&mut self, name: Name) -> T;
     fn visit_assign(&mut self, variable: Name, value: Expr) -> T;
@@ -13,4 +15,7 @@ pub trait InstructionVistor<T> {
     fn visit_ifz(&mut self, condition: Primary, label: Name) -> T;
     fn visit_arg(&mut self, arg: Primary) -> T;
     fn visit_ret(&mut self, value: Option<Primary>) -> T;
+    fn visit_tail_call(&mut self, func: Name) -> T;
+    fn visit_local(&mut self, variable: Name, layout: Layout) -> T;
+    fn visit_store(&mut self, variable: Name, offset: Primary, ty: Ty, value: Primary) -> T;
 }
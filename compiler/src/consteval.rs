@@ -0,0 +1,186 @@
+//! Evaluation of constant initializers at compile time. An initializer can use literals, other
+//! constants, arithmetic, comparisons, logic and casts. Integers are computed exactly and then
+//! checked against the type of every operation, so a constant that overflows is an error instead
+//! of wrapping around like it would at runtime
+use crate::ast::ty::FloatSize;
+use crate::ast::{BinOp, Expr, ExprKind, Lit, Region, Type, UnOp};
+use crate::name::Name;
+use crate::typechk::TypeTable;
+
+use std::cmp::Ordering;
+
+/// The value of a constant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Str(Name),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// the expression can't be evaluated at compile time
+    NotConstant(Region),
+    /// the result of an operation doesn't fit in its type
+    Overflow(Type, Region),
+    DivByZero(Region),
+    /// the expression has an error that was already reported
+    Reported,
+}
+
+/// Evaluates a constant initializer that was type checked, `table` has the type of every
+/// expression in it and the value of every constant it uses
+pub fn eval(expr: &Expr, table: &TypeTable) -> Result<Value, EvalError> {
+    // expressions with errors don't get a type
+    let Some(&ty) = table.types.get(&expr.region) else {
+        return Err(EvalError::Reported);
+    };
+
+    let value = match &expr.kind {
+        // the type checker made sure that literals fit in their types, `-128` is a negated `128`
+        // that only fits once it's negated
+        ExprKind::Lit(lit) => {
+            return Ok(match *lit {
+                Lit::Int(value) => Value::Int(value.into()),
+                Lit::Char(c) => Value::Int(u32::from(c).into()),
+                Lit::Float(value) => Value::Float(value),
+                Lit::Bool(b) => Value::Bool(b),
+                Lit::Str(string) => Value::Str(string),
+            })
+        }
+        ExprKind::Variable(_) => match table.consts.get(&expr.region) {
+            Some(&value) => value,
+            None => return Err(EvalError::NotConstant(expr.region)),
+        },
+        ExprKind::Unary(op, rhs) => match (op, eval(rhs, table)?) {
+            (UnOp::Neg, Value::Int(value)) => Value::Int(-value),
+            (UnOp::Neg, Value::Float(value)) => Value::Float(-value),
+            (UnOp::Not, Value::Bool(b)) => Value::Bool(!b),
+            _ => return Err(EvalError::NotConstant(expr.region)),
+        },
+        ExprKind::Binary(op, lhs, rhs) => {
+            let lhs = eval(lhs, table)?;
+            let rhs = eval(rhs, table)?;
+            binary(*op, lhs, rhs).ok_or(if is_division(*op, rhs) {
+                EvalError::DivByZero(expr.region)
+            } else {
+                EvalError::Overflow(ty, expr.region)
+            })?
+        }
+        ExprKind::Cast(value, _) => cast(eval(value, table)?, ty),
+        _ => return Err(EvalError::NotConstant(expr.region)),
+    };
+
+    // `float32` results are rounded to `float32` precision
+    let value = match value {
+        Value::Float(value) if ty == Type::Float(FloatSize::F32) => {
+            Value::Float(value as f32 as f64)
+        }
+        _ => value,
+    };
+
+    if fits(value, ty) {
+        Ok(value)
+    } else {
+        Err(EvalError::Overflow(ty, expr.region))
+    }
+}
+
+/// Returns `true` if the operation divides by zero
+fn is_division(op: BinOp, rhs: Value) -> bool {
+    matches!(op, BinOp::Div | BinOp::Mod) && rhs == Value::Int(0)
+}
+
+/// Computes a binary operation, `None` if it overflows or divides by zero
+fn binary(op: BinOp, lhs: Value, rhs: Value) -> Option<Value> {
+    use BinOp::*;
+
+    let value = match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => match op {
+            Add => Value::Int(lhs.checked_add(rhs)?),
+            Sub => Value::Int(lhs.checked_sub(rhs)?),
+            Mul => Value::Int(lhs.checked_mul(rhs)?),
+            // both round toward zero, like they do at runtime
+            Div => Value::Int(lhs.checked_div(rhs)?),
+            Mod => Value::Int(lhs.checked_rem(rhs)?),
+            _ => Value::Bool(compare(op, lhs.cmp(&rhs))),
+        },
+        (Value::Float(lhs), Value::Float(rhs)) => match op {
+            Add => Value::Float(lhs + rhs),
+            Sub => Value::Float(lhs - rhs),
+            Mul => Value::Float(lhs * rhs),
+            Div => Value::Float(lhs / rhs),
+            Mod => Value::Float(lhs % rhs),
+            // comparisons with NaN are all false except `!=`
+            _ => Value::Bool(match lhs.partial_cmp(&rhs) {
+                Some(ordering) => compare(op, ordering),
+                None => op == Ne,
+            }),
+        },
+        (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(match op {
+            And => lhs && rhs,
+            Or => lhs || rhs,
+            _ => compare(op, lhs.cmp(&rhs)),
+        }),
+        _ => unreachable!("the operands of `{op}` have the same type"),
+    };
+    Some(value)
+}
+
+fn compare(op: BinOp, ordering: Ordering) -> bool {
+    match op {
+        BinOp::Eq => ordering.is_eq(),
+        BinOp::Ne => ordering.is_ne(),
+        BinOp::Lt => ordering.is_lt(),
+        BinOp::Le => ordering.is_le(),
+        BinOp::Gt => ordering.is_gt(),
+        BinOp::Ge => ordering.is_ge(),
+        _ => unreachable!("`{op}` isn't a comparison"),
+    }
+}
+
+/// Converts a value like `as` does at runtime: integers are truncated to the size of the new type,
+/// and floats are rounded toward zero
+fn cast(value: Value, ty: Type) -> Value {
+    let int = match (value, ty) {
+        (value, Type::Newtype(_, inner)) => return cast(value, *inner),
+        (Value::Int(value), Type::Float(_)) => return Value::Float(value as f64),
+        (Value::Float(value), Type::Float(_)) => return Value::Float(value),
+        // chars are their code points, the type checker made sure that the value is one
+        (Value::Int(value), Type::Char) => return Value::Int(value),
+        (Value::Int(value), _) => value,
+        (Value::Bool(b), _) => b.into(),
+        (Value::Float(value), _) => value as i128,
+        (Value::Str(_), _) => unreachable!("strings can't be cast"),
+    };
+
+    let Type::Int { signed, size } = ty else {
+        unreachable!("`{ty}` isn't a type that values can be cast to");
+    };
+    let bits = u32::from(size.bits());
+    let truncated = int & ((1 << bits) - 1);
+    // the top bit of a signed integer is its sign
+    if signed && truncated >> (bits - 1) == 1 {
+        Value::Int(truncated - (1 << bits))
+    } else {
+        Value::Int(truncated)
+    }
+}
+
+/// Returns `true` if a value can be stored in the type
+fn fits(value: Value, ty: Type) -> bool {
+    match (value, ty) {
+        (Value::Int(value), Type::Int { signed, size }) => {
+            let bits = u32::from(size.bits());
+            let (min, max) = if signed {
+                (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+            } else {
+                (0, (1 << bits) - 1)
+            };
+            (min..=max).contains(&value)
+        }
+        (Value::Float(value), _) => !value.is_infinite(),
+        _ => true,
+    }
+}
@@ -3,11 +3,16 @@
 
 use crate::ast::stmt::Module;
 use crate::cli::Options;
+use crate::codegen::pass::PassManager;
 use crate::codegen::CodeGenerator;
+use crate::flowchk::FlowChecker;
+use crate::generate_error_message;
 use crate::lexer::Lexer;
+use crate::movechk::MoveChecker;
 use crate::parser::Parser;
 use crate::scopechk::ScopeChecker;
 use crate::source::Source;
+use crate::typechk::TypeChecker;
 
 use anyhow::{bail, Context, Result};
 use atty::Stream;
@@ -16,10 +21,10 @@ use std::fs;
 use std::io::{self, Read};
 
 pub fn run(options: Options) -> Result<()> {
-    let source = if let Some(source_path) = options.input {
+    let source = if let Some(source_path) = &options.input {
         // user provided a path to a source file
         let name = source_path.to_string_lossy().into_owned();
-        let contents = fs::read_to_string(&source_path)
+        let contents = fs::read_to_string(source_path)
             .with_context(|| format!("Failed to read code from {}", name))?;
         Source { contents, name }
     } else if atty::isnt(Stream::Stdin) {
@@ -32,22 +37,23 @@ pub fn run(options: Options) -> Result<()> {
         bail!("Please provide an input file");
     };
 
-    let mut lexer = Lexer::new(&source);
+    compile(&source, options)
+}
+
+/// Checks `source` and writes its intermediate code to the output file in `options`, if there is
+/// one
+fn compile(source: &Source, options: Options) -> Result<()> {
+    let mut lexer = Lexer::new(source);
     let tokens = lexer.lex();
-    let mut parser = Parser::new(tokens, &source);
+    let mut parser = Parser::new(tokens, source);
 
-    let mut parse_errors = vec![];
-    let module: Module = parser
-        .parse()
-        .into_iter()
-        .filter_map(|stmt| stmt.map_err(|e| parse_errors.push(e)).ok())
-        .collect();
+    let module: Module = parser.parse();
 
-    for err in &parse_errors {
+    for err in lexer.errors().iter().chain(parser.errors()) {
         println!("Syntax Error: {}", err);
     }
 
-    if !parse_errors.is_empty() {
+    if !lexer.errors().is_empty() || !parser.errors().is_empty() {
         bail!("Failed to parse file");
     } /* else {
           for stmt in &module {
@@ -58,19 +64,149 @@ pub fn run(options: Options) -> Result<()> {
     let mut scopechk = ScopeChecker::new();
     scopechk.check(&module);
 
+    for err in scopechk.errors() {
+        let message = generate_error_message(&err.kind.to_string(), source, err.region);
+        println!("Scope Error: {message}");
+    }
+
+    if !scopechk.errors().is_empty() {
+        bail!("Failed to scope check file");
+    }
+
+    let mut typechk = TypeChecker::new();
+    typechk.check(&module);
+
+    for err in typechk.errors() {
+        let message = generate_error_message(&err.kind.to_string(), source, err.region);
+        println!("Type Error: {message}");
+        if let Some((note, region)) = err.note() {
+            println!("note: {}", generate_error_message(&note, source, region));
+        }
+    }
+
+    if !typechk.errors().is_empty() {
+        bail!("Failed to type check file");
+    }
+
+    let mut flowchk = FlowChecker::new();
+    flowchk.check(&module);
+
+    for warning in flowchk.warnings() {
+        let message = generate_error_message(&warning.kind.to_string(), source, warning.region);
+        println!("Warning: {message}");
+    }
+
+    for err in flowchk.errors() {
+        let message = generate_error_message(&err.kind.to_string(), source, err.region);
+        println!("Control Flow Error: {message}");
+    }
+
+    if !flowchk.errors().is_empty() {
+        bail!("Failed to check control flow");
+    }
+
+    let mut movechk = MoveChecker::new();
+    movechk.check(&module);
+
+    for err in movechk.errors() {
+        let message = generate_error_message(&err.kind.to_string(), source, err.region);
+        println!("Ownership Error: {message}");
+        if let Some((note, region)) = err.note() {
+            println!("note: {}", generate_error_message(&note, source, region));
+        }
+    }
+
+    if !movechk.errors().is_empty() {
+        bail!("Failed to check ownership");
+    }
+
+    let types = typechk.into_types();
+
+    let pass_manager = match &options.passes {
+        Some(passes) => PassManager::with_passes(passes)?,
+        None => PassManager::new(options.opt_level),
+    }
+    .dump_after(options.dump_after)?;
+    for pass in pass_manager.skipped_dumps() {
+        println!("Warning: the pass `{pass}` doesn't run, so nothing is dumped after it; hint: pick it with `--passes` or a higher `-O` level");
+    }
+
     if let Some(output_file) = options.output {
         let output_file = output_file.to_string_lossy().into_owned();
-        let mut codegen = CodeGenerator::new();
+        let mut codegen = CodeGenerator::new(types).bounds_checks(options.bounds_checks);
         codegen.gen(&module);
+        codegen.optimize(&pass_manager);
 
-        let intermediate_code = if options.optimize {
-            codegen.optimized_intermediate_code()
-        } else {
-            codegen.intermediate_code()
-        };
+        let intermediate_code = codegen.intermediate_code();
         fs::write(&output_file, intermediate_code)
             .with_context(|| format!("failed to write intermediate code to {}", &output_file))?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser as _;
+
+    fn compile_str(src: &str) -> Result<()> {
+        let output = std::env::temp_dir().join("kip_driver_test.ic");
+        let options = Options::parse_from(["kip", "-o", output.to_str().unwrap()]);
+        compile(&Source::new(src, "<test>"), options)
+    }
+
+    #[test]
+    fn undefined_names_are_errors() {
+        let sources = [
+            "func f(): int32 { ret Q; }",
+            "const A: int32 = B + 1; const B: int32 = 2;",
+            "const A: int32 = A + 1;",
+        ];
+        for src in sources {
+            let err = compile_str(src).expect_err(src);
+            assert_eq!(err.to_string(), "Failed to scope check file", "{src}");
+        }
+        assert!(compile_str("const B: int32 = 2; func f(): int32 { ret B; }").is_ok());
+    }
+
+    #[test]
+    fn functions_can_be_used_before_their_declaration() {
+        let src = "func even(n: uint32): bool {
+                if n == 0 { ret true; }
+                ret odd(n - 1);
+            }
+            func odd(n: uint32): bool {
+                if n == 0 { ret false; }
+                ret even(n - 1);
+            }
+            func main(): int32 { ret later() as int32; }
+            extern func later(): bool;";
+        compile_str(src).unwrap();
+
+        let err = compile_str("func f() { } func f() { }").unwrap_err();
+        assert_eq!(err.to_string(), "Failed to scope check file");
+    }
+
+    #[test]
+    fn bare_optimize_flag() {
+        let level = |args: &[&str]| Options::parse_from(args).opt_level;
+        assert_eq!(level(&["kip", "main.kip", "-o", "main.ic", "-O"]), 1);
+        assert_eq!(level(&["kip", "main.kip", "--optimize"]), 1);
+        assert_eq!(level(&["kip", "main.kip", "-O2"]), 2);
+        assert_eq!(level(&["kip", "main.kip"]), 0);
+    }
+
+    #[test]
+    fn lexer_errors_are_fatal() {
+        let sources = [
+            "extern func puts(s: str); func f() { puts(\"\\x80\"); }",
+            "func f(): char { ret '\\q'; }",
+            "func f() { var café = 1; }",
+        ];
+        for src in sources {
+            let err = compile_str(src).expect_err(src);
+            assert_eq!(err.to_string(), "Failed to parse file", "{src}");
+        }
+    }
+}
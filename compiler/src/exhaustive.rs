@@ -0,0 +1,269 @@
+//! Exhaustiveness and reachability of `match` arms.
+//!
+//! Uses the usefulness algorithm from "Warnings for pattern matching" (Luc Maranget): a pattern is
+//! useful after some rows of patterns if there is a value that it matches and none of the rows
+//! do. An arm that isn't useful after the arms above it can never run, and a match is exhaustive
+//! if a wildcard isn't useful after all of its arms.
+//!
+//! Enums and `bool` have a finite set of constructors (their variants, `true` and `false`), and a
+//! tuple has a single one whose fields are its elements. Every other type is treated as having
+//! infinitely many, so only a wildcard or a binding covers all of its values.
+
+use crate::ast::pattern::{Pattern, PatternKind};
+use crate::ast::stmt::EnumDef;
+use crate::ast::{Lit, Type};
+use crate::name::Name;
+
+use std::collections::HashMap;
+
+/// A pattern reduced to the values it matches
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pat {
+    /// matches every value, wildcards and bindings
+    Wild,
+    /// matches the values built with the constructor whose fields match the patterns
+    Ctor(Ctor, Vec<Pat>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ctor {
+    /// the variant of an enum with this index
+    Variant(usize),
+    Bool(bool),
+    /// integers and characters
+    Int(i128),
+    /// a tuple, built from all of its elements
+    Tuple,
+}
+
+/// What is wrong with the arms of a match
+#[derive(Debug, Default)]
+pub struct Report {
+    /// the indices of the arms that can never match
+    pub unreachable: Vec<usize>,
+    /// a value that no arm matches, formatted as a pattern
+    pub missing: Option<String>,
+}
+
+pub struct Checker<'a> {
+    enums: &'a HashMap<Name, EnumDef>,
+}
+
+impl<'a> Checker<'a> {
+    pub fn new(enums: &'a HashMap<Name, EnumDef>) -> Self {
+        Self { enums }
+    }
+
+    /// Checks the patterns of a match on a value of type `ty`, in the order of their arms.
+    /// The patterns are expected to have been type checked
+    pub fn check(&self, patterns: &[&Pattern], ty: Type) -> Report {
+        let mut report = Report::default();
+        let mut rows: Vec<Vec<Pat>> = Vec::with_capacity(patterns.len());
+
+        for (idx, pattern) in patterns.iter().enumerate() {
+            let row = vec![self.lower(pattern, ty)];
+            if !self.useful(&rows, &row, &[ty]) {
+                report.unreachable.push(idx);
+            }
+            rows.push(row);
+        }
+
+        report.missing = self
+            .missing(&rows, &[ty])
+            .map(|witness| self.show(&witness[0], ty));
+        report
+    }
+
+    fn lower(&self, pattern: &Pattern, ty: Type) -> Pat {
+        match &pattern.kind {
+            PatternKind::Wildcard | PatternKind::Binding(_) => Pat::Wild,
+            PatternKind::Lit { lit, negative } => {
+                let ctor = match *lit {
+                    Lit::Bool(value) => Ctor::Bool(value),
+                    Lit::Int(value) if *negative => Ctor::Int(-i128::from(value)),
+                    Lit::Int(value) => Ctor::Int(value.into()),
+                    Lit::Char(c) => Ctor::Int(u32::from(c).into()),
+                    // the type checker rejects any other literal
+                    _ => return Pat::Wild,
+                };
+                Pat::Ctor(ctor, Vec::new())
+            }
+            PatternKind::Tuple(elems) => {
+                let Type::Tuple(elem_tys) = ty else {
+                    unreachable!("tuple patterns match tuples");
+                };
+                let elems = elems
+                    .iter()
+                    .zip(elem_tys)
+                    .map(|(elem, ty)| self.lower(elem, *ty))
+                    .collect();
+                Pat::Ctor(Ctor::Tuple, elems)
+            }
+            PatternKind::Variant(_, variant, fields) => {
+                let Type::Name(name) = ty else {
+                    unreachable!("variant patterns match enums");
+                };
+                let idx = self.enums[&name]
+                    .variant(*variant)
+                    .expect("the type checker makes sure variants exist");
+                let ctor = Ctor::Variant(idx);
+                let fields = fields
+                    .iter()
+                    .zip(self.field_tys(ty, ctor))
+                    .map(|(field, ty)| self.lower(field, ty))
+                    .collect();
+                Pat::Ctor(ctor, fields)
+            }
+        }
+    }
+
+    /// Every constructor of the type, `None` if it has too many to list
+    fn ctors(&self, ty: Type) -> Option<Vec<Ctor>> {
+        match ty {
+            Type::Bool => Some(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+            Type::Tuple(_) => Some(vec![Ctor::Tuple]),
+            Type::Name(name) => self
+                .enums
+                .get(&name)
+                .map(|def| (0..def.variants.len()).map(Ctor::Variant).collect()),
+            _ => None,
+        }
+    }
+
+    /// The types of the fields of a constructor
+    fn field_tys(&self, ty: Type, ctor: Ctor) -> Vec<Type> {
+        match (ty, ctor) {
+            (Type::Name(name), Ctor::Variant(idx)) => {
+                self.enums[&name].variants[idx].fields.clone()
+            }
+            (Type::Tuple(elems), Ctor::Tuple) => elems.to_vec(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The constructors of the type if the rows start with every one of them
+    fn complete_ctors(&self, rows: &[Vec<Pat>], ty: Type) -> Option<Vec<Ctor>> {
+        let ctors = self.ctors(ty)?;
+        let used = |ctor: &Ctor| {
+            rows.iter()
+                .any(|row| matches!(&row[0], Pat::Ctor(c, _) if c == ctor))
+        };
+        ctors.iter().all(used).then_some(ctors)
+    }
+
+    /// The rows that match a value built with the constructor, with the first pattern replaced by
+    /// the patterns of the constructor's fields
+    fn specialize(&self, rows: &[Vec<Pat>], ctor: Ctor, arity: usize) -> Vec<Vec<Pat>> {
+        rows.iter()
+            .filter_map(|row| {
+                let fields = match &row[0] {
+                    Pat::Ctor(c, fields) if *c == ctor => fields.clone(),
+                    Pat::Ctor(..) => return None,
+                    Pat::Wild => vec![Pat::Wild; arity],
+                };
+                Some(fields.into_iter().chain(row[1..].iter().cloned()).collect())
+            })
+            .collect()
+    }
+
+    /// The rows that start with a wildcard, without it
+    fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+        rows.iter()
+            .filter(|row| row[0] == Pat::Wild)
+            .map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    /// Returns `true` if a value matches the patterns `row` (of the types `tys`) but none of the
+    /// rows
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat], tys: &[Type]) -> bool {
+        let Some((first, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+
+        let specialized = |ctor: Ctor, fields: Vec<Pat>| {
+            let field_tys = self.field_tys(tys[0], ctor);
+            let row: Vec<Pat> = fields.into_iter().chain(rest.iter().cloned()).collect();
+            let tys: Vec<Type> = field_tys.iter().chain(&tys[1..]).copied().collect();
+            self.useful(&self.specialize(rows, ctor, field_tys.len()), &row, &tys)
+        };
+
+        match first {
+            Pat::Ctor(ctor, fields) => specialized(*ctor, fields.clone()),
+            Pat::Wild => match self.complete_ctors(rows, tys[0]) {
+                Some(ctors) => ctors.into_iter().any(|ctor| {
+                    let arity = self.field_tys(tys[0], ctor).len();
+                    specialized(ctor, vec![Pat::Wild; arity])
+                }),
+                None => self.useful(&Self::default(rows), rest, &tys[1..]),
+            },
+        }
+    }
+
+    /// Finds values (of the types `tys`) that none of the rows match
+    fn missing(&self, rows: &[Vec<Pat>], tys: &[Type]) -> Option<Vec<Pat>> {
+        let Some((&ty, rest)) = tys.split_first() else {
+            return rows.is_empty().then(Vec::new);
+        };
+
+        if let Some(ctors) = self.complete_ctors(rows, ty) {
+            return ctors.into_iter().find_map(|ctor| {
+                let field_tys = self.field_tys(ty, ctor);
+                let arity = field_tys.len();
+                let tys: Vec<Type> = field_tys.into_iter().chain(rest.iter().copied()).collect();
+                let mut witness = self.missing(&self.specialize(rows, ctor, arity), &tys)?;
+                let fields = witness.drain(..arity).collect();
+                witness.insert(0, Pat::Ctor(ctor, fields));
+                Some(witness)
+            });
+        }
+
+        let mut witness = self.missing(&Self::default(rows), rest)?;
+        // name a constructor that none of the rows start with, if the type's constructors can be
+        // listed
+        let unused = self.ctors(ty).and_then(|ctors| {
+            ctors.into_iter().find(|ctor| {
+                !rows
+                    .iter()
+                    .any(|row| matches!(&row[0], Pat::Ctor(c, _) if c == ctor))
+            })
+        });
+        let first = match unused {
+            Some(ctor) => Pat::Ctor(ctor, vec![Pat::Wild; self.field_tys(ty, ctor).len()]),
+            None => Pat::Wild,
+        };
+        witness.insert(0, first);
+        Some(witness)
+    }
+
+    /// Formats a pattern like it would be written in kip
+    fn show(&self, pat: &Pat, ty: Type) -> String {
+        match (pat, ty) {
+            (Pat::Wild, _) => "_".to_string(),
+            (Pat::Ctor(Ctor::Bool(value), _), _) => value.to_string(),
+            (Pat::Ctor(Ctor::Int(value), _), _) => value.to_string(),
+            (Pat::Ctor(ctor @ Ctor::Variant(idx), fields), Type::Name(name)) => {
+                let variant = self.enums[&name].variants[*idx].name;
+                if fields.is_empty() {
+                    return format!("{name}::{variant}");
+                }
+                let fields: Vec<String> = fields
+                    .iter()
+                    .zip(self.field_tys(ty, *ctor))
+                    .map(|(field, ty)| self.show(field, ty))
+                    .collect();
+                format!("{name}::{variant}({})", fields.join(", "))
+            }
+            (Pat::Ctor(Ctor::Variant(_), _), _) => unreachable!("variants are enums"),
+            (Pat::Ctor(Ctor::Tuple, elems), Type::Tuple(elem_tys)) => {
+                let elems: Vec<String> = elems
+                    .iter()
+                    .zip(elem_tys)
+                    .map(|(elem, ty)| self.show(elem, *ty))
+                    .collect();
+                format!("({})", elems.join(", "))
+            }
+            (Pat::Ctor(Ctor::Tuple, _), _) => unreachable!("tuple patterns match tuples"),
+        }
+    }
+}
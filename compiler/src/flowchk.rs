@@ -0,0 +1,297 @@
+//! Control flow checking: a function that returns a value must end with a `ret` on every path
+//! through it, and code that can't run because every path to it returns is reported.
+//!
+//! An `if` returns if both of its branches do, and a `match` returns if all of its arms do, the type
+//! checker makes sure the arms cover every value
+use crate::ast::stmt::{FuncProto, Stmt, StmtKind};
+use crate::ast::{Expr, ExprKind, Region, Type};
+use crate::name::Name;
+
+use std::fmt;
+use FlowErrorKind::*;
+
+#[derive(Debug)]
+pub struct FlowError {
+    pub kind: FlowErrorKind,
+    pub region: Region,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlowErrorKind {
+    /// a function that returns a value can reach the end of its body
+    MissingRet { func: Name, ty: Type },
+    /// the same, for a lambda
+    LambdaMissingRet(Type),
+    /// statements that follow a statement that always returns
+    Unreachable,
+}
+
+impl fmt::Display for FlowErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingRet { func, ty } => write!(
+                f,
+                "`{func}` must return a value of type `{ty}` but can reach the end of its body; hint: add a `ret` at the end"
+            ),
+            LambdaMissingRet(ty) => write!(
+                f,
+                "this function must return a value of type `{ty}` but can reach the end of its body; hint: add a `ret` at the end"
+            ),
+            Unreachable => "unreachable code, every path to it returns first".fmt(f),
+        }
+    }
+}
+
+pub struct FlowChecker {
+    errors: Vec<FlowError>,
+    /// unreachable code is only a warning, it doesn't stop the module from compiling
+    warnings: Vec<FlowError>,
+}
+
+impl FlowChecker {
+    pub fn new() -> Self {
+        Self {
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn check(&mut self, module: &[Box<Stmt>]) {
+        for stmt in module {
+            match &stmt.kind {
+                StmtKind::Func(proto, body) => self.check_func(proto, body),
+                StmtKind::Impl(_, methods) => self.check(methods),
+                StmtKind::Var(_, init) | StmtKind::Destructure(_, init) => self.check_lambdas(init),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn errors(&self) -> &[FlowError] {
+        &self.errors
+    }
+
+    pub fn warnings(&self) -> &[FlowError] {
+        &self.warnings
+    }
+
+    fn check_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>]) {
+        if !self.block_returns(body) && proto.ret != Type::Void {
+            self.errors.push(FlowError {
+                kind: MissingRet {
+                    func: proto.name,
+                    ty: proto.ret,
+                },
+                region: proto.region,
+            });
+        }
+    }
+
+    /// Returns `true` if every path through the block ends with a `ret`
+    fn block_returns(&mut self, block: &[Box<Stmt>]) -> bool {
+        for (idx, stmt) in block.iter().enumerate() {
+            if self.stmt_returns(stmt) {
+                if let Some((first, rest)) = block[idx + 1..].split_first() {
+                    let region = rest
+                        .last()
+                        .map_or(first.region, |last| first.region.to(last.region));
+                    self.warnings.push(FlowError {
+                        kind: Unreachable,
+                        region,
+                    });
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    fn stmt_returns(&mut self, stmt: &Stmt) -> bool {
+        match &stmt.kind {
+            StmtKind::Ret(value) => {
+                if let Some(value) = value {
+                    self.check_lambdas(value);
+                }
+                true
+            }
+            StmtKind::Block(block) => self.block_returns(block),
+            StmtKind::Expr(expr) | StmtKind::Var(_, expr) | StmtKind::Destructure(_, expr) => {
+                self.check_lambdas(expr);
+                self.expr_returns(expr)
+            }
+            StmtKind::Func(proto, body) => {
+                self.check_func(proto, body);
+                false
+            }
+            StmtKind::Impl(_, methods) => {
+                self.check(methods);
+                false
+            }
+            StmtKind::Const(..)
+            | StmtKind::Extern(_)
+            | StmtKind::Struct(_)
+            | StmtKind::Enum(_)
+            | StmtKind::Type(..)
+            | StmtKind::Impt(_)
+            | StmtKind::Error => false,
+        }
+    }
+
+    /// Returns `true` if every path through the expression ends with a `ret`. Only `if` and
+    /// `match` have blocks, and they don't have values, so they are never part of a bigger
+    /// expression
+    fn expr_returns(&mut self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Cond(_, then_block, else_block) => {
+                let then_returns = self.block_returns(then_block);
+                let else_returns = else_block
+                    .as_ref()
+                    .is_some_and(|else_block| self.block_returns(else_block));
+                then_returns && else_returns
+            }
+            ExprKind::Match(_, arms) => {
+                // every arm is checked, even after one that doesn't return
+                let mut all_return = true;
+                for arm in arms {
+                    all_return &= self.block_returns(&arm.body);
+                }
+                all_return
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks the bodies of the lambdas in an expression, the blocks of `if`s and `match`es are
+    /// checked by [`Self::expr_returns`]
+    fn check_lambdas(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Lambda(_, ret, body) => {
+                if !self.block_returns(body) && *ret != Type::Void {
+                    self.errors.push(FlowError {
+                        kind: LambdaMissingRet(*ret),
+                        region: expr.region,
+                    });
+                }
+            }
+            ExprKind::Lit(_) | ExprKind::Variable(_) | ExprKind::Error => {}
+            ExprKind::Unary(_, operand)
+            | ExprKind::Cast(operand, _)
+            | ExprKind::Field(operand, _) => self.check_lambdas(operand),
+            ExprKind::Binary(_, lhs, rhs)
+            | ExprKind::Assign(lhs, rhs)
+            | ExprKind::Index(lhs, rhs) => {
+                self.check_lambdas(lhs);
+                self.check_lambdas(rhs);
+            }
+            ExprKind::Call(base, args) | ExprKind::MethodCall(base, _, args) => {
+                self.check_lambdas(base);
+                for arg in args {
+                    self.check_lambdas(arg);
+                }
+            }
+            ExprKind::Tuple(args) | ExprKind::Array(args) | ExprKind::Variant(_, _, args) => {
+                for arg in args {
+                    self.check_lambdas(arg);
+                }
+            }
+            ExprKind::StructLit(_, fields) => {
+                for (_, value) in fields {
+                    self.check_lambdas(value);
+                }
+            }
+            ExprKind::Cond(value, ..) | ExprKind::Match(value, _) => self.check_lambdas(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::Source;
+
+    /// Returns the functions that are missing a `ret` and the number of warnings
+    fn check(source_code: &'static str) -> (Vec<String>, usize) {
+        let source = Source::new(source_code, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+        assert!(parser.errors().is_empty(), "failed to parse");
+
+        let mut flowchk = FlowChecker::new();
+        flowchk.check(&module);
+        let missing = flowchk
+            .errors()
+            .iter()
+            .map(|err| match err.kind {
+                MissingRet { func, .. } => func.to_string(),
+                LambdaMissingRet(_) => "lambda".to_string(),
+                Unreachable => unreachable!("unreachable code is a warning"),
+            })
+            .collect();
+        (missing, flowchk.warnings().len())
+    }
+
+    #[test]
+    fn missing_ret() {
+        assert_eq!(
+            check(
+                "func add(a: int32, b: int32): int32 { a + b; }
+                func sign(a: int32): int32 {
+                    if a < 0 { ret -1; } else { if a > 0 { ret 1; } else { ret 0; } }
+                }
+                func half(c: bool): int32 { if c { ret 1; } }
+                func arms(c: bool): int32 {
+                    match c {
+                        true => { ret 1; }
+                        false => { { ret 0; } }
+                    }
+                }
+                func some_arms(c: bool): int32 {
+                    match c {
+                        true => { ret 1; }
+                        false => { }
+                    }
+                }
+                func early(c: bool) {
+                    if c { ret; }
+                    g();
+                }
+                func lambdas(c: bool): int32 {
+                    var f = func(x: int32): int32 { if c { ret x; } };
+                    ret apply(func(x: int32): int32 { ret x; }, 1);
+                }"
+            ),
+            (
+                vec![
+                    "add".to_string(),
+                    "half".to_string(),
+                    "some_arms".to_string(),
+                    "lambda".to_string()
+                ],
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn unreachable_code() {
+        assert_eq!(
+            check(
+                "func f(c: bool): int32 {
+                    if c {
+                        ret 1;
+                        g();
+                    } else {
+                        ret 2;
+                    }
+                    g();
+                    h();
+                }
+                func g() { ret; ret; }"
+            ),
+            (vec![], 3)
+        );
+    }
+}
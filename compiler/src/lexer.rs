@@ -3,10 +3,13 @@ use std::str::Chars;
 
 use crate::ast::lit::Lit;
 use crate::ast::region::Region;
+use crate::generate_error_message;
 use crate::name::name;
 use crate::source::Source;
 use crate::token::{Token, TokenKind, TokenKind::*};
 
+use anyhow::anyhow;
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
     input: &'a Source,
@@ -17,6 +20,8 @@ pub struct Lexer<'a> {
     /// looked at
     current: usize,
     chars: Peekable<Chars<'a>>,
+    /// the errors found so far, the lexer skips the characters they are in and keeps going
+    errors: Vec<anyhow::Error>,
 }
 
 // identifiers can start with letters from the alphabet or underscores
@@ -37,13 +42,31 @@ impl<'a> Lexer<'a> {
             current: 0,
             start: 0,
             chars: input.contents.chars().peekable(),
+            errors: Vec::new(),
         }
     }
 
+    pub fn errors(&self) -> &[anyhow::Error] {
+        &self.errors
+    }
+
+    /// Reports an error about the characters from the offset `start` up to the current one
+    fn error(&mut self, message: &str, start: usize) {
+        let region = Region::new(start, self.current);
+        let message = generate_error_message(message, self.input, region);
+        self.errors.push(anyhow!("{message}"));
+    }
+
     pub fn lex(&mut self) -> Vec<Token> {
         while !self.is_eof() {
             self.start = self.current;
+            let (tokens, errors) = (self.tokens.len(), self.errors.len());
             self.lex_token();
+            // the characters of an error are an `Error` token, so the parser doesn't report
+            // anything about them again
+            if self.errors.len() > errors && self.tokens.len() == tokens {
+                self.add_token(Error);
+            }
         }
 
         self.add_token(Eof);
@@ -74,20 +97,8 @@ impl<'a> Lexer<'a> {
             // string literal e.g. (`"foo"`)
             '"' => self.string(),
 
-            '\'' => {
-                let c = if let Some(c) = self.eat() {
-                    c
-                } else {
-                    eprintln!("Error: Expected a character in a character literal");
-                    return;
-                };
-
-                if let Some('\'') = self.eat() {
-                    self.add_token(Literal(Lit::Char(c)));
-                } else {
-                    eprintln!("Error: Unterminated character literal.");
-                }
-            }
+            // character literal e.g. (`'a'`, `'\n'`)
+            '\'' => self.character(),
 
             '@' => {
                 self.eat();
@@ -97,18 +108,21 @@ impl<'a> Lexer<'a> {
                     }
 
                     if self.is_eof() {
-                        eprintln!("expected an identifier after a pre processor statement");
-                        return;
+                        let message = "expected an identifier after a pre processor statement";
+                        return self.error(message, self.start);
                     }
 
                     // exclude the '@'
                     match &self.input.contents[self.start + 1..self.current] {
                         "impt" => self.add_token(Impt),
                         "expt" => self.add_token(Expt),
-                        s => eprintln!("unknown preproc directive `@{}`", s),
+                        s => {
+                            let message = format!("unknown preproc directive `@{s}`");
+                            self.error(&message, self.start);
+                        }
                     }
                 } else {
-                    eprintln!("Error: expected identifier after `@`");
+                    self.error("expected identifier after `@`", self.start);
                 }
             }
 
@@ -133,20 +147,42 @@ impl<'a> Lexer<'a> {
             ')' => self.add_token(CloseParen),
             '{' => self.add_token(OpenBrace),
             '}' => self.add_token(CloseBrace),
+            '[' => self.add_token(OpenBracket),
+            ']' => self.add_token(CloseBracket),
             ',' => self.add_token(Comma),
             ';' => self.add_token(Semicolon),
             '+' => self.add_token(Plus),
             '-' => self.add_token(Minus),
             '*' => self.add_token(Star),
-            ':' => self.add_token(Colon),
+            ':' => match self.next_is(':') {
+                true => self.add_token(ColonColon),
+                false => self.add_token(Colon),
+            },
             '%' => self.add_token(Percent),
-            '=' => match self.next_is('=') {
-                true => self.add_token(DoubleEqual),
-                false => self.add_token(Equal),
+            '=' => {
+                if self.next_is('=') {
+                    self.add_token(DoubleEqual)
+                } else if self.next_is('>') {
+                    self.add_token(FatArrow)
+                } else {
+                    self.add_token(Equal)
+                }
+            }
+            '!' => match self.next_is('=') {
+                true => self.add_token(BangEqual),
+                false => self.add_token(Bang),
             },
-            '.' => self.add_token(Dot),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.eat();
+                    self.eat();
+                    self.add_token(Ellipsis)
+                } else {
+                    self.add_token(Dot)
+                }
+            }
             '/' => self.add_token(Slash),
-            _ => eprintln!("Error: unexpected character"),
+            c => self.error(&format!("unexpected character `{c}`"), self.start),
         }
     }
 
@@ -161,7 +197,7 @@ impl<'a> Lexer<'a> {
             let c = if let Some(c) = self.eat() {
                 c
             } else {
-                return eprintln!("unterminated block comment");
+                return self.error("unterminated block comment", self.start);
             };
 
             if c == '*' && self.next_is('/') {
@@ -172,34 +208,170 @@ impl<'a> Lexer<'a> {
     }
 
     fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+        self.digits();
+
+        let mut is_float = false;
+        // a fraction, the dot has to be followed by a digit (`1.5`)
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.eat();
+            self.digits();
+            is_float = true;
         }
 
-        // Handle decimal points
-        if self.peek() == '.' {
-            // TODO: add back float support, I removed it for easier code generation
-            todo!("floating-point numbers");
+        // an exponent (`1e9`, `2.5E-3`)
+        if let 'e' | 'E' = self.peek() {
+            let exponent = self.current;
+            self.eat();
+            if let '+' | '-' = self.peek() {
+                self.eat();
+            }
+
+            if !self.peek().is_ascii_digit() {
+                let message = format!(
+                    "expected digits in the exponent `{}`",
+                    self.slice(exponent, self.current)
+                );
+                return self.error(&message, self.start);
+            }
+            self.digits();
+            is_float = true;
+        }
+
+        if is_float {
+            return self.float();
         }
 
         let num = self.input.contents[self.start..self.current].parse();
         if let Ok(num) = num {
             self.add_token(Literal(Lit::Int(num)));
-        } else if let Err(e) = num {
-            eprintln!("Error: invalid integer literal: {}", e);
+        } else {
+            self.error("integer literal is too large to fit in 64 bits", self.start);
         }
     }
 
-    fn string(&mut self) {
-        while self.peek() != '"' {
+    fn digits(&mut self) {
+        while self.peek().is_ascii_digit() {
             self.eat();
         }
+    }
 
-        self.eat();
+    fn float(&mut self) {
+        let text = self.slice(self.start, self.current);
+        match text.parse::<f64>() {
+            Ok(num) if num.is_finite() => self.add_token(Literal(Lit::Float(num))),
+            _ => {
+                let message = format!("float literal `{text}` is too large to fit in 64 bits");
+                self.error(&message, self.start);
+            }
+        }
+    }
+
+    fn string(&mut self) {
+        let mut s = String::new();
+        loop {
+            match self.eat() {
+                Some('"') => break,
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        s.push(c);
+                    }
+                }
+                Some(c) => s.push(c),
+                None => return self.error("unterminated string literal", self.start),
+            }
+        }
+
+        self.add_token(Literal(Lit::Str(name(&s))));
+    }
+
+    fn character(&mut self) {
+        let c = match self.eat() {
+            Some('\\') => self.escape(),
+            Some('\'') | None => {
+                return self.error("expected a character in a character literal", self.start)
+            }
+            c => c,
+        };
+
+        if !self.next_is('\'') {
+            // `'ab'`, or a letter followed by a combining accent, is more than one character
+            let line = self.input.contents[self.current..].lines().next();
+            if let Some(end) = line.and_then(|line| line.find('\'')) {
+                let len = self.input.contents[self.current..=self.current + end]
+                    .chars()
+                    .count();
+                for _ in 0..len {
+                    self.eat();
+                }
+                let message =
+                    "a character literal holds a single character; hint: use a string literal";
+                return self.error(message, self.start);
+            }
+            return self.error("unterminated character literal", self.start);
+        }
+
+        if let Some(c) = c {
+            self.add_token(Literal(Lit::Char(c)));
+        }
+    }
 
-        // exclude the quotation marks from the string literal
-        let s = self.slice(self.start + 1, self.current - 1);
-        self.add_token(Literal(Lit::Str(name(s))));
+    /// Reads the rest of an escape sequence after the `\`, returns `None` if it isn't valid.
+    /// Only the characters that can be part of the escape are read, so an invalid one doesn't eat
+    /// the quote that closes the literal
+    fn escape(&mut self) -> Option<char> {
+        let start = self.current - 1;
+        let c = match self.eat() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(c @ ('\\' | '"' | '\'')) => c,
+            // `\x41` is an ascii character
+            Some('x') => {
+                let digits = self.hex_digits(2);
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if byte.is_ascii() && digits.len() == 2 => byte.into(),
+                    _ => {
+                        let message = "`\\x` must be followed by two hex digits up to `7f`";
+                        self.error(message, start);
+                        return None;
+                    }
+                }
+            }
+            // `\u{1F600}` is any unicode character
+            Some('u') if self.next_is('{') => {
+                let digits = self.hex_digits(6);
+                let closed = self.next_is('}');
+                let c = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| closed && digits.len() <= 6)
+                    .and_then(char::from_u32);
+                if c.is_none() {
+                    let message = format!("invalid unicode escape `\\u{{{digits}}}`");
+                    self.error(&message, start);
+                }
+                return c;
+            }
+            Some(c) => {
+                self.error(&format!("unknown escape sequence `\\{c}`"), start);
+                return None;
+            }
+            None => {
+                self.error("expected an escape sequence", start);
+                return None;
+            }
+        };
+
+        Some(c)
+    }
+
+    /// Reads up to `max` hex digits
+    fn hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
+        while digits.len() < max && self.peek().is_ascii_hexdigit() {
+            digits.extend(self.eat());
+        }
+        digits
     }
 
     fn identifier(&mut self) {
@@ -213,10 +385,22 @@ impl<'a> Lexer<'a> {
             "func" => self.add_token(Func),
             "extern" => self.add_token(Extern),
             "var" => self.add_token(Var),
+            "const" => self.add_token(Const),
+            "impl" => self.add_token(Impl),
             "if" => self.add_token(If),
             "while" => self.add_token(While),
             "else" => self.add_token(Else),
             "ret" => self.add_token(Ret),
+            "as" => self.add_token(As),
+            "struct" => self.add_token(Struct),
+            "enum" => self.add_token(Enum),
+            "type" => self.add_token(Type),
+            "newtype" => self.add_token(Newtype),
+            "match" => self.add_token(Match),
+            "move" => self.add_token(Move),
+            "clone" => self.add_token(TokenKind::Clone),
+            "true" => self.add_token(Literal(Lit::Bool(true))),
+            "false" => self.add_token(Literal(Lit::Bool(false))),
             _ => self.add_token(Ident(name(s))),
         }
     }
@@ -236,6 +420,14 @@ impl<'a> Lexer<'a> {
         *self.chars.peek().unwrap_or(&'\0')
     }
 
+    /// Looks at the character after the current un-eaten character
+    fn peek_next(&self) -> char {
+        self.input.contents[self.current..]
+            .chars()
+            .nth(1)
+            .unwrap_or('\0')
+    }
+
     /// Move to the next character
     fn eat(&mut self) -> Option<char> {
         let c = self.chars.next()?;
@@ -255,11 +447,6 @@ impl<'a> Lexer<'a> {
     fn slice(&self, start: usize, end: usize) -> &str {
         &self.input.contents[start..end]
     }
-
-    /// Get a reference to the token stream's input.
-    pub fn input(&self) -> &Source {
-        self.input
-    }
 }
 
 #[cfg(test)]
@@ -276,6 +463,79 @@ mod tests {
         assert_eq!(tokens[1], Literal('7'.into()));
     }
 
+    #[test]
+    fn multi_byte_chars() {
+        let input = "'é' '😀' 'ab' \"日本\" 1";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Literal('é'.into()));
+        assert_eq!(tokens[1], Literal('😀'.into()));
+        // regions are byte offsets
+        assert_eq!(tokens[1].region, Region::new(5, 11));
+        // `'ab'` is an error
+        assert_eq!(tokens[2], Error);
+        assert_eq!(tokens[3], Literal(Lit::Str(name("日本"))));
+        assert_eq!(tokens[4], Literal(1.into()));
+        assert_eq!(tokens[5], Eof);
+    }
+
+    #[test]
+    fn escapes() {
+        let input = r#""a\tb\n\\\"\0" '\n' '\'' "\x41\u{e9}\u{1F600}""#;
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Literal(Lit::Str(name("a\tb\n\\\"\0"))));
+        assert_eq!(tokens[1], Literal('\n'.into()));
+        assert_eq!(tokens[2], Literal('\''.into()));
+        assert_eq!(tokens[3], Literal(Lit::Str(name("Aé😀"))));
+        assert_eq!(tokens[4], Eof);
+    }
+
+    #[test]
+    fn invalid_escapes() {
+        let input = r#""\x" "\x80" '\q' "\u{41" café"#;
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        // the escapes are dropped but don't eat the quotes that close the literals
+        assert_eq!(tokens[0], Literal(Lit::Str(name(""))));
+        assert_eq!(tokens[1], Literal(Lit::Str(name(""))));
+        assert_eq!(tokens[2], Error);
+        assert_eq!(tokens[3], Literal(Lit::Str(name(""))));
+        assert_eq!(tokens[4], Ident(name("caf")));
+        assert_eq!(tokens[5], Error);
+        assert_eq!(tokens[6], Eof);
+        assert_eq!(lexer.errors().len(), 5);
+    }
+
+    #[test]
+    fn unterminated_string() {
+        let input = "\"foo";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0], Error);
+        assert_eq!(tokens[0].region, Region::new(0, 4));
+        assert_eq!(tokens[1], Eof);
+        assert_eq!(lexer.errors().len(), 1);
+    }
+
+    #[test]
+    fn booleans() {
+        let input = "!true != false truth";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Bang);
+        assert_eq!(tokens[1], Literal(true.into()));
+        assert_eq!(tokens[2], BangEqual);
+        assert_eq!(tokens[3], Literal(false.into()));
+        assert_eq!(tokens[4], Ident(name("truth")));
+    }
+
     #[test]
     fn identifiers_and_parens() {
         let input = "func foo()";
@@ -301,6 +561,90 @@ mod tests {
         assert_eq!(tokens[2], Literal(1.into()));
     }
 
+    #[test]
+    fn enums_and_match() {
+        let input = "enum match Shape::Circle => = :";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Enum);
+        assert_eq!(tokens[1], Match);
+        assert_eq!(tokens[2], Ident(name("Shape")));
+        assert_eq!(tokens[3], ColonColon);
+        assert_eq!(tokens[4], Ident(name("Circle")));
+        assert_eq!(tokens[5], FatArrow);
+        assert_eq!(tokens[6], Equal);
+        assert_eq!(tokens[7], Colon);
+        assert_eq!(tokens[8], Eof);
+    }
+
+    #[test]
+    fn move_and_clone() {
+        let input = "move clone moved";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Move);
+        assert_eq!(tokens[1], TokenKind::Clone);
+        assert_eq!(tokens[2], Ident(name("moved")));
+        assert_eq!(tokens[3], Eof);
+    }
+
+    #[test]
+    fn constants() {
+        let input = "const constant";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Const);
+        assert_eq!(tokens[1], Ident(name("constant")));
+        assert_eq!(tokens[2], Eof);
+    }
+
+    #[test]
+    fn impl_blocks() {
+        let input = "impl Point { } p.len()";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Impl);
+        assert_eq!(tokens[1], Ident(name("Point")));
+        assert_eq!(tokens[5], Dot);
+        assert_eq!(tokens[6], Ident(name("len")));
+    }
+
+    #[test]
+    fn ellipsis() {
+        let input = "(fmt: str, ...) a..b";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[5], Ellipsis);
+        assert_eq!(tokens[6], CloseParen);
+        // two dots are just two dots
+        assert_eq!(tokens[8], Dot);
+        assert_eq!(tokens[9], Dot);
+    }
+
+    #[test]
+    fn floats() {
+        let input = "1.5 0.25e2 1e-3 2E+2 3.foo 1e";
+        let source = Source::new(input, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.lex();
+        assert_eq!(tokens[0], Literal(1.5.into()));
+        assert_eq!(tokens[1], Literal(25.0.into()));
+        assert_eq!(tokens[2], Literal(0.001.into()));
+        assert_eq!(tokens[3], Literal(200.0.into()));
+        // a dot that isn't followed by a digit isn't part of the number
+        assert_eq!(tokens[4], Literal(3.into()));
+        assert_eq!(tokens[5], Dot);
+        assert_eq!(tokens[6], Ident(name("foo")));
+        // `1e` isn't a valid number
+        assert_eq!(tokens[7], Error);
+        assert_eq!(tokens[8], Eof);
+    }
+
     #[test]
     fn numbers_ops_and_commas() {
         let input = "42 + 3, 69 * 100, 1000";
@@ -7,23 +7,26 @@ extern crate lazy_static;
 mod ast;
 pub mod cli;
 mod codegen;
+mod consteval;
 pub mod driver;
+mod exhaustive;
+mod flowchk;
 mod lexer;
 pub mod logger;
+mod movechk;
 mod name;
 mod parser;
 mod scopechk;
 mod source;
 mod token;
+mod typechk;
 
-pub fn generate_error_message(
-    base_message: &'static str,
-    source: &Source,
-    region: Region,
-) -> String {
+pub fn generate_error_message(base_message: &str, source: &Source, region: Region) -> String {
     let error_source_code = source.context_of(region);
     let line = error_source_code.line;
-    let width = region.start() - error_source_code.region.start() + 1;
+    // the caret is under the start of the region on its own line, the context can start a line
+    // earlier
+    let width = source.column(region.start()) + 1;
     let error_context = source.slice(error_source_code.region);
     format!(
         "{base_message}\nin {}, line {line}\n{error_context}\n{:>width$}",
@@ -0,0 +1,466 @@
+//! Ownership checking: `move x` gives away the value of `x`, and `x` can't be used again until it's
+//! assigned a new value. `clone x` copies the value instead, so `x` keeps its own.
+//!
+//! The checker follows the order that the code runs in. A variable that is moved in only some of
+//! the branches of an `if` or a `match` counts as moved after it, unless the branch returns
+use crate::ast::stmt::{EnumDef, FuncProto, Param, Stmt, StructDef};
+use crate::ast::visit::{walk_expr, walk_stmt};
+use crate::ast::{
+    Arm, BinOp, Block, Expr, ExprKind, ExprVisitor, Lit, Pattern, Region, StmtVisitor, Type, UnOp,
+};
+use crate::name::Name;
+
+use std::collections::HashMap;
+use std::fmt;
+use MoveErrorKind::*;
+
+#[derive(Debug)]
+pub struct MoveError {
+    pub kind: MoveErrorKind,
+    pub region: Region,
+}
+
+impl MoveError {
+    /// A message about another place in the code that explains the error, and where that place is
+    pub fn note(&self) -> Option<(String, Region)> {
+        match self.kind {
+            UseAfterMove { var, moved_at } => Some((format!("`{var}` was moved here"), moved_at)),
+            NotMovable => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveErrorKind {
+    /// a variable is used after its value was moved out of it
+    UseAfterMove { var: Name, moved_at: Region },
+    /// something that isn't a variable is moved
+    NotMovable,
+}
+
+impl fmt::Display for MoveErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UseAfterMove { var, .. } => write!(
+                f,
+                "use of moved variable `{var}`; hint: assign it a new value first, or `clone` it instead of moving it"
+            ),
+            NotMovable => "only variables can be moved".fmt(f),
+        }
+    }
+}
+
+/// Which variables are moved at some point in the code
+#[derive(Debug, Clone, Default)]
+struct State {
+    /// the moved variables, by the index of their declaration, and where they were moved
+    moved: HashMap<usize, Region>,
+    /// `false` after a `ret`, the code that follows doesn't run
+    live: bool,
+}
+
+impl State {
+    /// Combines the states at the ends of branches that continue at the same place, a variable is
+    /// moved if it's moved at the end of any of the branches that get there
+    fn merge(branches: impl IntoIterator<Item = State>) -> State {
+        let mut merged = State::default();
+        for branch in branches.into_iter().filter(|branch| branch.live) {
+            merged.live = true;
+            for (var, moved_at) in branch.moved {
+                merged.moved.entry(var).or_insert(moved_at);
+            }
+        }
+        merged
+    }
+}
+
+pub struct MoveChecker {
+    /// the variables in scope, each name maps to the index of its declaration so that shadowed
+    /// variables are told apart
+    scopes: Vec<HashMap<Name, usize>>,
+    /// the number of variables that were declared
+    vars: usize,
+    state: State,
+    errors: Vec<MoveError>,
+}
+
+impl MoveChecker {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            vars: 0,
+            state: State {
+                live: true,
+                ..State::default()
+            },
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn check(&mut self, module: &[Box<Stmt>]) {
+        for stmt in module {
+            walk_stmt(self, stmt);
+        }
+    }
+
+    pub fn errors(&self) -> &[MoveError] {
+        &self.errors
+    }
+
+    fn declare(&mut self, name: Name) {
+        // NOTE: this unwrap is fine because there should always be a global scope
+        self.scopes.last_mut().unwrap().insert(name, self.vars);
+        self.vars += 1;
+    }
+
+    /// Finds the declaration of a variable, undefined variables are reported by the scope checker
+    fn lookup(&self, name: Name) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name))
+            .copied()
+    }
+
+    fn check_block(&mut self, block: &[Box<Stmt>]) {
+        self.scopes.push(HashMap::new());
+        for stmt in block {
+            walk_stmt(self, stmt);
+        }
+        self.scopes.pop();
+    }
+
+    /// Checks a branch that starts in the state `start`, and returns the state at its end
+    fn check_branch(&mut self, start: &State, check: impl FnOnce(&mut Self)) -> State {
+        self.state = start.clone();
+        check(self);
+        std::mem::take(&mut self.state)
+    }
+}
+
+impl StmtVisitor<()> for MoveChecker {
+    fn visit_expr_stmt(&mut self, expr: &Expr, _: Region) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_ret_stmt(&mut self, value: Option<&Expr>, _: Region) {
+        if let Some(value) = value {
+            walk_expr(self, value);
+        }
+        self.state.live = false;
+    }
+
+    fn visit_var_stmt(&mut self, name: Name, init: &Expr, _: Region) {
+        walk_expr(self, init);
+        self.declare(name);
+    }
+
+    fn visit_destructure_stmt(&mut self, pattern: &Pattern, init: &Expr, _: Region) {
+        walk_expr(self, init);
+        pattern.for_each_binding(&mut |name| self.declare(name));
+    }
+
+    // constants are values, they aren't owned by anything
+    fn visit_const(&mut self, _: Name, _: Type, _: &Expr, _: Region) {}
+
+    fn visit_block(&mut self, stmts: &[Box<Stmt>]) {
+        self.check_block(stmts);
+    }
+
+    fn visit_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>], _: Region) {
+        let outer = std::mem::replace(
+            &mut self.state,
+            State {
+                live: true,
+                ..State::default()
+            },
+        );
+        self.scopes.push(HashMap::new());
+        for param in &proto.params {
+            self.declare(param.name);
+        }
+        self.check_block(body);
+        self.scopes.pop();
+        self.state = outer;
+    }
+
+    fn visit_extern(&mut self, _: &FuncProto, _: Region) {}
+
+    fn visit_struct(&mut self, _: &StructDef, _: Region) {}
+
+    fn visit_enum(&mut self, _: &EnumDef, _: Region) {}
+
+    fn visit_type(&mut self, _: Name, _: Type, _: Region) {}
+
+    fn visit_impl(&mut self, _: Name, methods: &[Box<Stmt>], _: Region) {
+        for method in methods {
+            walk_stmt(self, method);
+        }
+    }
+
+    fn visit_impt(&mut self, _: Name, _: Region) {}
+
+    fn visit_error_stmt(&mut self, _: Region) {}
+}
+
+impl ExprVisitor<()> for MoveChecker {
+    fn visit_lit_expr(&mut self, _: Lit, _: Region) {}
+
+    fn visit_variable_expr(&mut self, name: Name, region: Region) {
+        let Some(var) = self.lookup(name) else {
+            return;
+        };
+        if let Some(&moved_at) = self.state.moved.get(&var) {
+            self.errors.push(MoveError {
+                kind: UseAfterMove {
+                    var: name,
+                    moved_at,
+                },
+                region,
+            });
+        }
+    }
+
+    fn visit_unary_expr(&mut self, op: UnOp, rhs: &Expr, region: Region) {
+        walk_expr(self, rhs);
+        if op != UnOp::Move {
+            return;
+        }
+
+        match rhs.kind {
+            ExprKind::Variable(name) => {
+                if let Some(var) = self.lookup(name) {
+                    self.state.moved.entry(var).or_insert(region);
+                }
+            }
+            _ => self.errors.push(MoveError {
+                kind: NotMovable,
+                region,
+            }),
+        }
+    }
+
+    fn visit_binary_expr(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, _: Region) {
+        walk_expr(self, lhs);
+        // the right operand of `&&` and `||` doesn't always run
+        if let BinOp::And | BinOp::Or = op {
+            let start = std::mem::take(&mut self.state);
+            let end = self.check_branch(&start, |this| walk_expr(this, rhs));
+            self.state = State::merge([start, end]);
+        } else {
+            walk_expr(self, rhs);
+        }
+    }
+
+    fn visit_call_expr(&mut self, callee: &Expr, args: &[Box<Expr>], _: Region) {
+        walk_expr(self, callee);
+        for arg in args {
+            walk_expr(self, arg);
+        }
+    }
+
+    fn visit_method_call_expr(&mut self, receiver: &Expr, _: Name, args: &[Box<Expr>], _: Region) {
+        walk_expr(self, receiver);
+        for arg in args {
+            walk_expr(self, arg);
+        }
+    }
+
+    fn visit_cond_expr(
+        &mut self,
+        condition: &Expr,
+        then_block: &Block,
+        else_block: Option<&Block>,
+        _: Region,
+    ) {
+        walk_expr(self, condition);
+
+        let start = std::mem::take(&mut self.state);
+        let then_end = self.check_branch(&start, |this| this.check_block(then_block));
+        let else_end = match else_block {
+            Some(else_block) => self.check_branch(&start, |this| this.check_block(else_block)),
+            None => start,
+        };
+        self.state = State::merge([then_end, else_end]);
+    }
+
+    fn visit_assign_expr(&mut self, target: &Expr, value: &Expr, _: Region) {
+        walk_expr(self, value);
+
+        // assigning to a variable gives it a new value it owns, but assigning to part of a moved
+        // value is a use of it
+        match target.kind {
+            ExprKind::Variable(name) => {
+                if let Some(var) = self.lookup(name) {
+                    self.state.moved.remove(&var);
+                }
+            }
+            _ => walk_expr(self, target),
+        }
+    }
+
+    fn visit_cast_expr(&mut self, value: &Expr, _: Type, _: Region) {
+        walk_expr(self, value);
+    }
+
+    fn visit_struct_lit_expr(&mut self, _: Name, fields: &[(Name, Box<Expr>)], _: Region) {
+        for (_, value) in fields {
+            walk_expr(self, value);
+        }
+    }
+
+    fn visit_field_expr(&mut self, base: &Expr, _: Name, _: Region) {
+        walk_expr(self, base);
+    }
+
+    fn visit_tuple_expr(&mut self, elems: &[Box<Expr>], _: Region) {
+        for elem in elems {
+            walk_expr(self, elem);
+        }
+    }
+
+    fn visit_array_expr(&mut self, elems: &[Box<Expr>], _: Region) {
+        for elem in elems {
+            walk_expr(self, elem);
+        }
+    }
+
+    fn visit_index_expr(&mut self, base: &Expr, index: &Expr, _: Region) {
+        walk_expr(self, base);
+        walk_expr(self, index);
+    }
+
+    fn visit_variant_expr(&mut self, _: Name, _: Name, args: &[Box<Expr>], _: Region) {
+        for arg in args {
+            walk_expr(self, arg);
+        }
+    }
+
+    fn visit_match_expr(&mut self, scrutinee: &Expr, arms: &[Arm], _: Region) {
+        walk_expr(self, scrutinee);
+        if arms.is_empty() {
+            return;
+        }
+
+        let start = std::mem::take(&mut self.state);
+        let ends: Vec<State> = arms
+            .iter()
+            .map(|arm| {
+                self.check_branch(&start, |this| {
+                    this.scopes.push(HashMap::new());
+                    arm.pattern.for_each_binding(&mut |name| this.declare(name));
+                    this.check_block(&arm.body);
+                    this.scopes.pop();
+                })
+            })
+            .collect();
+        self.state = State::merge(ends);
+    }
+
+    // the captured variables are copied when the lambda is created, so capturing a moved variable
+    // is a use of it, but the body runs later and can't move anything out of the enclosing function
+    fn visit_lambda_expr(&mut self, params: &[Param], _: Type, body: &Block, _: Region) {
+        let outer = self.state.clone();
+        self.state.live = true;
+        self.scopes.push(HashMap::new());
+        for param in params {
+            self.declare(param.name);
+        }
+        self.check_block(body);
+        self.scopes.pop();
+        self.state = outer;
+    }
+
+    fn visit_error_expr(&mut self, _: Region) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::Source;
+
+    /// Returns the errors as the moved variable, or `None` for anything else
+    fn check(source_code: &'static str) -> Vec<Option<String>> {
+        let source = Source::new(source_code, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+        assert!(parser.errors().is_empty(), "failed to parse");
+
+        let mut movechk = MoveChecker::new();
+        movechk.check(&module);
+        movechk
+            .errors()
+            .iter()
+            .map(|err| match err.kind {
+                UseAfterMove { var, .. } => Some(var.to_string()),
+                NotMovable => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn use_after_move() {
+        assert!(check(
+            "func f(a: [int32; 2], b: [int32; 2]): [int32; 2] {
+                var c = move a;
+                a = clone c;
+                var d = clone b;
+                g(move b, d);
+                ret move a;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "func f(a: int32, b: int32, p: P) {
+                    var c = move a;
+                    a + 1;
+                    g(move b, b);
+                    move p;
+                    p.x = 1;
+                    move (a + 1);
+                }"
+            ),
+            [
+                Some("a".to_string()),
+                Some("b".to_string()),
+                Some("p".to_string()),
+                Some("a".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_in_branches() {
+        assert!(check(
+            "func f(a: int32, c: bool): int32 {
+                if c {
+                    g(move a);
+                    ret 0;
+                }
+                if c { a = 1; } else { var a = 2; move a; }
+                match c {
+                    true => { var b = move a; a = b; }
+                    false => { }
+                }
+                ret a;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "func f(a: int32, b: int32, c: bool) {
+                    if c { g(move a); }
+                    a;
+                    c && g(move b);
+                    b;
+                }"
+            ),
+            [Some("a".to_string()), Some("b".to_string())]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-pub use ustr::{existing_ustr as existing_name, ustr as name, Ustr as Name};
+pub use ustr::{ustr as name, Ustr as Name};
 
 pub mod nm {
     use super::*;
@@ -13,5 +13,7 @@ pub mod nm {
         pub static ref INT32: Name = name("int32");
         pub static ref INT64: Name = name("int64");
         pub static ref BOOL: Name = name("bool");
+        /// the first parameter of a method
+        pub static ref SELF: Name = name("self");
     }
 }
@@ -1,8 +1,13 @@
 use super::Parser;
 
-use crate::ast::expr::{Expr, ExprKind::*};
+use crate::ast::expr::{Expr, ExprKind, ExprKind::*};
 use crate::ast::op::BinOp;
-use crate::token::TokenKind::*;
+use crate::ast::pattern::Arm;
+use crate::ast::ty::Type;
+use crate::ast::{Lit, Region};
+use crate::generate_error_message;
+use crate::name::name;
+use crate::token::{TokenKind, TokenKind::*};
 
 use anyhow::{anyhow, Result};
 
@@ -12,7 +17,7 @@ impl Parser {
         self.assigment()
     }
 
-    /// assignment -> ( IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) assignment ) | conditional
+    /// assignment -> ( place ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) assignment ) | conditional
     fn assigment(&mut self) -> Result<Box<Expr>> {
         let expr = self.conditional()?;
 
@@ -22,11 +27,24 @@ impl Parser {
             let value = self.assigment()?;
             let assignment_end = value.region;
 
-            let Variable(name) = lhs.kind else {
-                return Err(anyhow!("invalid assignment target {}", value));
-            };
+            // the rest of the statement is still parsed, only the assignment is an error
+            if !lhs.is_place() {
+                let error = anyhow!(
+                    "{}",
+                    generate_error_message(
+                        "cannot assign to this, only to a variable, a field, an element or a dereference",
+                        &self.source,
+                        lhs.region
+                    )
+                );
+                self.errors.push(error);
+                return Ok(Expr::new(
+                    ExprKind::Error,
+                    assignment_start.to(assignment_end),
+                ));
+            }
             return Ok(Expr::new(
-                Assign(name, value),
+                Assign(lhs, value),
                 assignment_start.to(assignment_end),
             ));
         }
@@ -34,10 +52,18 @@ impl Parser {
         Ok(expr)
     }
 
+    /// Parses with struct literals allowed or not, see [`Parser::no_struct_lit`]
+    fn with_struct_lits<T>(&mut self, allowed: bool, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let no_struct_lit = std::mem::replace(&mut self.no_struct_lit, !allowed);
+        let result = parse(self);
+        self.no_struct_lit = no_struct_lit;
+        result
+    }
+
     fn conditional(&mut self) -> Result<Box<Expr>> {
         if self.matches(&[If]) {
             let if_kw = self.previous();
-            let condition = self.or()?;
+            let condition = self.with_struct_lits(false, Self::or)?;
             self.expect(OpenBrace, "expected '{' after if condition")?;
             let then_branch = self.block()?;
             let else_branch = if self.matches(&[Else]) {
@@ -53,9 +79,41 @@ impl Parser {
             ));
         }
 
+        if self.check(TokenKind::Match) {
+            return self.match_expr();
+        }
+
         self.or()
     }
 
+    /// match -> "match" logic_or '{' ( pattern "=>" block ','? )* '}'
+    fn match_expr(&mut self) -> Result<Box<Expr>> {
+        let match_kw = self.eat();
+        let scrutinee = self.with_struct_lits(false, Self::or)?;
+        self.expect(OpenBrace, "expected '{' after the value being matched")?;
+
+        let mut arms = Vec::new();
+        while !self.check(CloseBrace) && !self.is_eof() {
+            let pattern = self.pattern()?;
+            self.expect(FatArrow, "expected '=>' after pattern")?;
+            self.expect(OpenBrace, "expected '{' before the arm's body")?;
+            let body = self.block()?;
+            let region = pattern.region.to(self.previous().region);
+            arms.push(Arm {
+                pattern,
+                body,
+                region,
+            });
+            self.matches(&[Comma]);
+        }
+
+        self.expect(CloseBrace, "expected '}' after the match arms")?;
+        Ok(Expr::new(
+            ExprKind::Match(scrutinee, arms),
+            self.region_since(match_kw),
+        ))
+    }
+
     fn or(&mut self) -> Result<Box<Expr>> {
         let mut lhs = self.and()?;
         let expr_start = lhs.region;
@@ -131,23 +189,38 @@ impl Parser {
         Ok(lhs)
     }
 
-    /// factor -> unary ( ( '/' | '*' ) unary )*
+    /// factor -> cast ( ( '/' | '*' ) cast )*
     fn factor(&mut self) -> Result<Box<Expr>> {
-        let mut lhs = self.unary()?;
+        let mut lhs = self.cast()?;
         let expr_start = lhs.region;
 
         while self.matches(&[Slash, Star, Percent]) {
             let op = self.previous().to_bin_op().unwrap();
-            let rhs = self.unary()?;
-            let expr_end = lhs.region;
+            let rhs = self.cast()?;
+            let expr_end = rhs.region;
             lhs = Expr::new(Binary(op, lhs, rhs), expr_start.to(expr_end));
         }
 
         Ok(lhs)
     }
 
+    /// cast -> unary ( "as" type )*
+    fn cast(&mut self) -> Result<Box<Expr>> {
+        let mut value = self.unary()?;
+        let expr_start = value.region;
+
+        while self.matches(&[As]) {
+            let ty = self.type_annotation()?;
+            let expr_end = self.previous().region;
+            value = Expr::new(Cast(value, ty), expr_start.to(expr_end));
+        }
+
+        Ok(value)
+    }
+
+    /// unary -> ( ( '!' | '-' | '&' | '*' | "move" | "clone" ) unary ) | field
     fn unary(&mut self) -> Result<Box<Expr>> {
-        if self.matches(&[Bang, Minus /* TODO:, Move, Clone */]) {
+        if self.matches(&[Bang, Minus, Ampersand, Star, Move, TokenKind::Clone]) {
             let op_token = self.previous();
             let expr_start = op_token.region;
             let op = op_token.to_unary_op().unwrap();
@@ -155,37 +228,183 @@ impl Parser {
             let expr_end = rhs.region;
             Ok(Expr::new(Unary(op, rhs), expr_start.to(expr_end)))
         } else {
-            self.call()
+            self.field()
         }
     }
 
-    fn call(&mut self) -> Result<Box<Expr>> {
-        let (fn_name_token, fn_name) = match self.peek().kind {
-            Ident(name) if self.look_ahead(1) == OpenParen => (self.eat(), name),
-            _ => return self.primary(),
-        };
+    /// field -> primary ( '.' IDENTIFIER ( '(' arguments? ')' )? | '.' NUMBER | '[' expression ']'
+    ///                  | '(' arguments? ')' )*
+    fn field(&mut self) -> Result<Box<Expr>> {
+        let mut base = self.primary()?;
+        let expr_start = base.region;
+
+        loop {
+            if self.matches(&[OpenParen]) {
+                let args = self.arguments()?;
+                let expr_end = self.previous().region;
+                base = Expr::new(Call(base, args), expr_start.to(expr_end));
+            } else if self.matches(&[Dot]) {
+                if let Literal(Lit::Int(_) | Lit::Float(_)) = self.peek().kind {
+                    base = self.tuple_field(base)?;
+                    continue;
+                }
+                let field = self.expect_ident("expected field name after '.'")?;
+                if self.matches(&[OpenParen]) {
+                    let args = self.arguments()?;
+                    let expr_end = self.previous().region;
+                    base = Expr::new(MethodCall(base, field, args), expr_start.to(expr_end));
+                    continue;
+                }
+                let expr_end = self.previous().region;
+                base = Expr::new(Field(base, field), expr_start.to(expr_end));
+            } else if self.matches(&[OpenBracket]) {
+                let index = self.with_struct_lits(true, Self::expression)?;
+                let close_bracket = self.expect(CloseBracket, "expected ']' after index")?;
+                base = Expr::new(Index(base, index), expr_start.to(close_bracket.region));
+            } else {
+                return Ok(base);
+            }
+        }
+    }
 
-        // eat open paren '('
-        self.eat();
+    /// Parses the index of a tuple's element after the '.', the element is a field named by its
+    /// index (`pair.0`). `pair.0.1` is lexed as a float, it's two fields
+    fn tuple_field(&mut self, mut base: Box<Expr>) -> Result<Box<Expr>> {
+        let token = self.eat();
+        let text = self.source.slice(token.region).to_string();
+        let mut end = token.region.start();
+        for idx in text.split('.') {
+            if idx.is_empty() || !idx.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(anyhow!(
+                    "{}",
+                    self.generate_error_message(
+                        "expected the index of a tuple's element after '.'"
+                    )
+                ));
+            }
+            // every field gets its own region, the type checker tells them apart by it
+            end += idx.len();
+            let region = Region::new(base.region.start(), end);
+            base = Expr::new(Field(base, name(idx)), region);
+            end += 1;
+        }
+        Ok(base)
+    }
 
+    /// Parses the arguments of a call after the '(', up to and including the ')'
+    #[allow(clippy::vec_box)]
+    fn arguments(&mut self) -> Result<Vec<Box<Expr>>> {
         let mut args = Vec::new();
 
         if !self.check(CloseParen) {
-            args.push(self.expression()?);
+            args.push(self.with_struct_lits(true, Self::expression)?);
             while self.matches(&[Comma]) {
-                args.push(self.expression()?);
+                args.push(self.with_struct_lits(true, Self::expression)?);
             }
         }
-        let close_paren = self.expect(CloseParen, "expected ')' after argument list")?;
+        self.expect(CloseParen, "expected ')' after argument list")?;
+
+        Ok(args)
+    }
+
+    /// variant -> IDENTIFIER "::" IDENTIFIER ( '(' arguments? ')' )?
+    fn variant(&mut self) -> Result<Box<Expr>> {
+        let enum_token = self.eat();
+        let Ident(enum_name) = enum_token.kind else {
+            unreachable!("variants start with the enum's name");
+        };
+        // eat '::'
+        self.eat();
+        let variant = self.expect_ident("expected variant name after '::'")?;
+
+        let args = if self.matches(&[OpenParen]) {
+            self.arguments()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Expr::new(
+            Variant(enum_name, variant, args),
+            self.region_since(enum_token),
+        ))
+    }
+
+    /// struct_lit -> IDENTIFIER '{' ( IDENTIFIER ':' expression ),* '}'
+    fn struct_lit(&mut self) -> Result<Box<Expr>> {
+        let name_token = self.eat();
+        let Ident(name) = name_token.kind else {
+            unreachable!("struct literals start with the struct's name");
+        };
+        // eat '{'
+        self.eat();
+
+        let mut fields = Vec::new();
+        while !self.check(CloseBrace) {
+            let field = self.expect_ident("expected field name in struct literal")?;
+            self.expect(Colon, "expected ':' after field name")?;
+            fields.push((field, self.with_struct_lits(true, Self::expression)?));
+
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+
+        let close_brace = self.expect(CloseBrace, "expected '}' to close struct literal")?;
+
+        Ok(Expr::new(
+            StructLit(name, fields),
+            self.region_from(name_token, close_brace),
+        ))
+    }
+
+    /// array_lit -> '[' ( expression ( ',' expression )* ','? )? ']'
+    fn array_lit(&mut self) -> Result<Box<Expr>> {
+        let open_bracket = self.eat();
+
+        let mut elems = Vec::new();
+        while !self.check(CloseBracket) {
+            elems.push(self.with_struct_lits(true, Self::expression)?);
+
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+
+        let close_bracket = self.expect(CloseBracket, "expected ']' to close array literal")?;
 
         Ok(Expr::new(
-            Call(fn_name, args),
-            self.region_from(fn_name_token, close_paren),
+            Array(elems),
+            self.region_from(open_bracket, close_bracket),
+        ))
+    }
+
+    /// lambda -> "func" '(' parameter_list? ')' type_annotation? block
+    fn lambda(&mut self) -> Result<Box<Expr>> {
+        let func_token = self.eat();
+        self.expect(OpenParen, "expected '(' after 'func'")?;
+        let params = self.parameter_list(None)?;
+        self.expect(CloseParen, "expected ')' after parameter list")?;
+
+        let ret = if self.matches(&[Colon]) {
+            self.type_annotation()?
+        } else {
+            Type::Void
+        };
+
+        self.expect(OpenBrace, "expected '{' before function body")?;
+        // struct literals are allowed again inside of the body
+        let body = self.with_struct_lits(true, Self::block)?;
+
+        Ok(Expr::new(
+            Lambda(params, ret, body),
+            self.region_since(func_token),
         ))
     }
 
     fn primary(&mut self) -> Result<Box<Expr>> {
         match self.peek().kind {
+            Ident(_) if self.look_ahead(1) == ColonColon => self.variant(),
+            Ident(_) if self.look_ahead(1) == OpenBrace && !self.no_struct_lit => self.struct_lit(),
             Ident(name) => {
                 self.eat();
                 Ok(Expr::new(Variable(name), self.previous()))
@@ -197,13 +416,33 @@ impl Parser {
 
             // NOTE: i might consider creating an ast object for parenthesised expressions
             OpenParen => {
-                self.eat();
-                let expr = self.expression();
-                self.expect(CloseParen, "expected ')' to close expression")?;
-                expr
+                let open_paren = self.eat();
+                // struct literals are allowed in parentheses, even in an `if` condition
+                let expr = self.with_struct_lits(true, Self::expression)?;
+                if !self.check(Comma) {
+                    self.expect(CloseParen, "expected ')' to close expression")?;
+                    return Ok(expr);
+                }
+
+                // tuple -> '(' expression ( ',' expression )+ ')'
+                let mut elems = vec![expr];
+                while self.matches(&[Comma]) {
+                    elems.push(self.with_struct_lits(true, Self::expression)?);
+                }
+                let close_paren = self.expect(CloseParen, "expected ')' to close tuple")?;
+                Ok(Expr::new(
+                    Tuple(elems),
+                    self.region_from(open_paren, close_paren),
+                ))
             }
 
-            _ => Err(anyhow!("expected an expression {}", self.peek())),
+            OpenBracket => self.array_lit(),
+            Func => self.lambda(),
+
+            _ => Err(anyhow!(
+                "{}",
+                self.generate_error_message("expected an expression")
+            )),
         }
     }
 }
@@ -9,23 +9,41 @@
 //! my opinion this leads for a simple, yet robust way of implementing kip's parser.
 
 pub mod expr;
+mod pattern;
+mod resolve;
 pub mod stmt;
 #[cfg(test)]
 mod tests;
 pub mod ty;
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 
-use crate::ast::{Region, Stmt};
+use std::collections::HashMap;
+
+use crate::ast::stmt::Module;
+use crate::ast::{Region, Stmt, StmtKind, Type};
 use crate::generate_error_message;
 use crate::name::Name;
 use crate::source::Source;
 use crate::token::{Token, TokenKind, TokenKind::*};
+use crate::typechk::TypeChecker;
 
 pub struct Parser {
     source: Source,
     tokens: Vec<Token>,
     current: usize,
+    /// set while parsing an `if` condition, where a '{' after a name starts the then block rather
+    /// than a struct literal (`if x { ... }`)
+    no_struct_lit: bool,
+    /// the aliases and newtypes declared so far, with the types they are declared as and the
+    /// regions of those types. They are resolved after the whole module is parsed
+    aliases: HashMap<Name, (Type, Region)>,
+    /// the syntax errors found so far, the parser skips the declarations they are in and keeps
+    /// going
+    errors: Vec<anyhow::Error>,
+    /// checks the module-level constants parsed so far, the lengths of array types are evaluated
+    /// with them
+    consts: TypeChecker,
 }
 
 impl Parser {
@@ -35,34 +53,135 @@ impl Parser {
             source: source.clone(),
             tokens,
             current: 0,
+            no_struct_lit: false,
+            aliases: HashMap::new(),
+            errors: Vec::new(),
+            consts: TypeChecker::new(),
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Result<Box<Stmt>>> {
+    /// Parses the whole module. The declarations with syntax errors are [`StmtKind::Error`]s, and
+    /// the errors are in [`Parser::errors`]
+    pub fn parse(&mut self) -> Module {
         let mut decls = Vec::new();
 
         while !self.is_eof() {
-            decls.push(self.declaration());
+            if self.check(CloseBrace) {
+                let error = anyhow!(
+                    "{}",
+                    self.generate_error_message("unexpected '}', it doesn't close any block")
+                );
+                self.errors.push(error);
+                self.eat();
+                continue;
+            }
+            let mut decl = self.declaration();
+            if let StmtKind::Ret(_) = decl.kind {
+                let error = anyhow!(
+                    "{}",
+                    generate_error_message(
+                        "`ret` can only be used in a function",
+                        &self.source,
+                        decl.region
+                    )
+                );
+                self.errors.push(error);
+                decl.kind = StmtKind::Error;
+            }
+            if let StmtKind::Const(_, ty, _) = &mut decl.kind {
+                *ty = self.resolve_type(*ty);
+                self.consts.check_const(&decl);
+            }
+            decls.push(decl);
         }
 
+        self.resolve_types(&mut decls);
         decls
     }
 
-    // Synchronizes the parser so that it can continue parsing
-    fn sync(&mut self) {
-        self.eat();
+    pub fn errors(&self) -> &[anyhow::Error] {
+        &self.errors
+    }
 
-        while !self.is_eof() {
-            if self.previous().kind == Semicolon {
-                return;
+    /// Reports the syntax error of a declaration that starts at the token `start` and skips the
+    /// rest of it, the declaration becomes a [`StmtKind::Error`]. An error at a token the lexer
+    /// couldn't read isn't reported, the lexer already did
+    fn recover(&mut self, start: usize, decl: Result<Box<Stmt>>) -> Box<Stmt> {
+        match decl {
+            Ok(decl) => decl,
+            Err(error) => {
+                if !self.at_lexer_error() {
+                    self.errors.push(error);
+                }
+                self.sync(start);
+                Stmt::new(StmtKind::Error, self.region_since(self.tokens[start]))
             }
+        }
+    }
+
+    /// Synchronizes the parser so that it can continue parsing after a syntax error in the
+    /// declaration that starts at the token `start`. Skips to the end of the declaration: after
+    /// its ';' or the '}' of a block it opened, or before the next declaration or the '}' of the
+    /// block it's in. A ';' or keyword in parentheses or brackets (`[int32; 3]`) only ends the
+    /// declaration if they aren't closed after it
+    fn sync(&mut self, start: usize) {
+        // the blocks that the declaration opened and didn't close yet, and the parentheses and
+        // brackets it opened outside of them
+        let (mut depth, mut nesting) = (0, 0);
+        for token in &self.tokens[start..self.current] {
+            nest(token.kind, &mut depth, &mut nesting);
+        }
+
+        while !self.is_eof() {
             match self.peek().kind {
-                Extern | Func | Var | If | Ret => return,
-                _ => {
-                    self.eat();
+                CloseBrace if depth == 0 => return,
+                // the declaration's first token can be a keyword, it's skipped
+                Extern | Func | Struct | Enum | Type | Newtype | Impl | Var | Const | If
+                | While | Match | Ret
+                    if depth == 0 && self.current > start && !self.closes_ahead(nesting) =>
+                {
+                    return
                 }
-            };
+                _ => {}
+            }
+
+            let token = self.eat();
+            nest(token.kind, &mut depth, &mut nesting);
+            match token.kind {
+                Semicolon if depth == 0 && !self.closes_ahead(nesting) => return,
+                CloseBrace if depth == 0 && !self.closes_ahead(nesting) => {
+                    // a block can end an expression that is followed by its ';'
+                    self.matches(&[Semicolon]);
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether the innermost of the `nesting` parentheses and brackets the declaration opened is
+    /// closed before the next ';' or '}' that isn't in a nested pair
+    fn closes_ahead(&self, nesting: usize) -> bool {
+        if nesting == 0 {
+            return false;
+        }
+
+        let mut inner = 0;
+        for token in &self.tokens[self.current..] {
+            match token.kind {
+                OpenParen | OpenBracket | OpenBrace => inner += 1,
+                CloseParen | CloseBracket if inner == 0 => return true,
+                CloseParen | CloseBracket | CloseBrace if inner > 0 => inner -= 1,
+                Semicolon | CloseBrace | Eof if inner == 0 => return false,
+                _ => {}
+            }
         }
+        false
+    }
+
+    /// Whether the parser is at a token the lexer couldn't read, or right after one
+    fn at_lexer_error(&self) -> bool {
+        self.check(Error) || self.current > 0 && self.previous() == Error
     }
 
     // if the next token is any of token kinds, the token is eaten and true is returned
@@ -116,7 +235,7 @@ impl Parser {
         generate_error_message(base_message, &self.source, self.peek().region)
     }
 
-    ///
+    /// Eats the next token if it is of the given kind, otherwise fails with the given message
     fn expect(&mut self, kind: TokenKind, message: &'static str) -> Result<Token> {
         if self.check(kind) {
             Ok(self.eat())
@@ -149,3 +268,15 @@ impl Parser {
     //  &self.sym_tbl
     // }
 }
+
+/// Keeps count of the blocks (`depth`) and of the parentheses and brackets outside of them
+/// (`nesting`) that are open after the token
+fn nest(kind: TokenKind, depth: &mut i32, nesting: &mut usize) {
+    match kind {
+        OpenBrace => *depth += 1,
+        CloseBrace => *depth -= 1,
+        OpenParen | OpenBracket if *depth == 0 => *nesting += 1,
+        CloseParen | CloseBracket if *depth == 0 => *nesting = nesting.saturating_sub(1),
+        _ => {}
+    }
+}
@@ -0,0 +1,89 @@
+use super::Parser;
+
+use crate::ast::pattern::{Pattern, PatternKind};
+use crate::ast::Lit;
+use crate::token::TokenKind::*;
+
+use anyhow::{bail, Result};
+
+impl Parser {
+    /// pattern -> '_' | IDENTIFIER | '-'? literal | IDENTIFIER "::" IDENTIFIER ( '(' pattern,* ')' )?
+    ///          | '(' pattern ( ',' pattern )+ ')'
+    pub(super) fn pattern(&mut self) -> Result<Pattern> {
+        let start = self.peek();
+
+        if self.matches(&[Minus]) {
+            let Literal(lit @ (Lit::Int(_) | Lit::Float(_))) = self.peek().kind else {
+                bail!(
+                    "{}",
+                    self.generate_error_message("expected a number after '-' in a pattern")
+                );
+            };
+            self.eat();
+            return Ok(Pattern {
+                kind: PatternKind::Lit {
+                    lit,
+                    negative: true,
+                },
+                region: self.region_since(start),
+            });
+        }
+
+        let kind = match start.kind {
+            Literal(lit) => {
+                self.eat();
+                PatternKind::Lit {
+                    lit,
+                    negative: false,
+                }
+            }
+            Ident(name) if name == "_" => {
+                self.eat();
+                PatternKind::Wildcard
+            }
+            Ident(enum_name) if self.look_ahead(1) == ColonColon => {
+                self.eat();
+                self.eat();
+                let variant = self.expect_ident("expected variant name after '::'")?;
+
+                let mut fields = Vec::new();
+                if self.matches(&[OpenParen]) {
+                    while !self.check(CloseParen) {
+                        fields.push(self.pattern()?);
+                        if !self.matches(&[Comma]) {
+                            break;
+                        }
+                    }
+                    self.expect(CloseParen, "expected ')' after the variant's patterns")?;
+                }
+                PatternKind::Variant(enum_name, variant, fields)
+            }
+            Ident(name) => {
+                self.eat();
+                PatternKind::Binding(name)
+            }
+            OpenParen => {
+                self.eat();
+                let mut elems = vec![self.pattern()?];
+                self.expect(
+                    Comma,
+                    "expected ',' in tuple pattern, a tuple has at least two elements",
+                )?;
+                loop {
+                    elems.push(self.pattern()?);
+                    if !self.matches(&[Comma]) {
+                        break;
+                    }
+                }
+                self.expect(CloseParen, "expected ')' after the tuple's patterns")?;
+                PatternKind::Tuple(elems)
+            }
+            _ => bail!("{}", self.generate_error_message("expected a pattern")),
+        };
+
+        Ok(Pattern {
+            kind,
+            region: self.region_since(start),
+        })
+    }
+}
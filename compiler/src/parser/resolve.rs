@@ -0,0 +1,245 @@
+use super::Parser;
+
+use crate::ast::expr::ExprKind;
+use crate::ast::stmt::{Block, FuncProto, Module};
+use crate::ast::ty::{self, Type};
+use crate::ast::{Expr, Stmt, StmtKind};
+use crate::generate_error_message;
+use crate::name::Name;
+
+use anyhow::anyhow;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+impl Parser {
+    /// Replaces the names of aliases and newtypes with the types they stand for. This runs after
+    /// the whole module is parsed, so like structs they can be used before they are declared
+    pub(super) fn resolve_types(&mut self, module: &mut Module) {
+        let (resolved, cycles) = self.resolve_aliases();
+        for cycle in cycles {
+            let path: Vec<_> = cycle
+                .iter()
+                .chain(&cycle[..1])
+                .map(|name| format!("`{name}`"))
+                .collect();
+            let message = format!(
+                "the type `{}` is declared in terms of itself: {}",
+                cycle[0],
+                path.join(" -> ")
+            );
+            let (_, region) = self.aliases[&cycle[0]];
+            let error = anyhow!("{}", generate_error_message(&message, &self.source, region));
+            self.errors.push(error);
+        }
+
+        for stmt in module {
+            resolve_stmt(stmt, &resolved);
+        }
+    }
+
+    /// The type that a type annotation stands for with the aliases and newtypes declared so far
+    pub(super) fn resolve_type(&self, ty: Type) -> Type {
+        ty.subst(&self.resolve_aliases().0)
+    }
+
+    /// Resolves every alias and newtype declared so far, in the order they are declared. Returns
+    /// the types they stand for and the cycles of aliases that are declared in terms of
+    /// themselves, the aliases on a cycle are left as names
+    fn resolve_aliases(&self) -> (HashMap<Name, Type>, Vec<Vec<Name>>) {
+        let mut names: Vec<_> = self.aliases.keys().copied().collect();
+        names.sort_by_key(|name| self.aliases[name].1.start());
+
+        let mut resolved = HashMap::new();
+        let mut cycles = Vec::new();
+        for name in names {
+            while let Err(cycle) = self.resolve_alias(name, &mut resolved, &mut Vec::new()) {
+                for &name in &cycle {
+                    resolved.insert(name, Type::Name(name));
+                }
+                cycles.push(cycle);
+            }
+        }
+        (resolved, cycles)
+    }
+
+    /// Resolves the alias `name` and the aliases its type refers to. `path` holds the aliases
+    /// being resolved, when `name` is one of them the aliases from it on are returned as a cycle
+    fn resolve_alias(
+        &self,
+        name: Name,
+        resolved: &mut HashMap<Name, Type>,
+        path: &mut Vec<Name>,
+    ) -> Result<Type, Vec<Name>> {
+        if let Some(&ty) = resolved.get(&name) {
+            return Ok(ty);
+        }
+        if let Some(start) = path.iter().position(|&other| other == name) {
+            return Err(path[start..].to_vec());
+        }
+
+        let (ty, _) = self.aliases[&name];
+        let mut refs = Vec::new();
+        type_names(ty, &mut refs);
+
+        path.push(name);
+        let mut args = HashMap::new();
+        for other in refs {
+            if self.aliases.contains_key(&other) {
+                args.insert(other, self.resolve_alias(other, resolved, path)?);
+            }
+        }
+        path.pop();
+
+        let ty = match ty {
+            Type::Newtype(name, inner) => Type::Newtype(name, ty::intern(inner.subst(&args))),
+            _ => ty.subst(&args),
+        };
+        resolved.insert(name, ty);
+        Ok(ty)
+    }
+}
+
+/// Collects the names that appear in the type
+fn type_names(ty: Type, names: &mut Vec<Name>) {
+    match ty {
+        Type::Name(name) => names.push(name),
+        Type::Array(elem, _) | Type::Slice(elem) | Type::Ptr(elem) | Type::Newtype(_, elem) => {
+            type_names(*elem, names)
+        }
+        Type::Tuple(elems) | Type::Generic(_, elems) => {
+            for &elem in elems {
+                type_names(elem, names);
+            }
+        }
+        Type::Func(params, ret) => {
+            for &param in params {
+                type_names(param, names);
+            }
+            type_names(*ret, names);
+        }
+        _ => {}
+    }
+}
+
+/// The aliases that aren't hidden by the type parameters of a generic function or struct
+fn visible<'a>(
+    aliases: &'a HashMap<Name, Type>,
+    generics: &[Name],
+) -> Cow<'a, HashMap<Name, Type>> {
+    if generics.iter().any(|name| aliases.contains_key(name)) {
+        let mut aliases = aliases.clone();
+        for name in generics {
+            aliases.remove(name);
+        }
+        Cow::Owned(aliases)
+    } else {
+        Cow::Borrowed(aliases)
+    }
+}
+
+fn resolve_stmt(stmt: &mut Stmt, aliases: &HashMap<Name, Type>) {
+    match &mut stmt.kind {
+        StmtKind::Expr(expr) | StmtKind::Var(_, expr) | StmtKind::Destructure(_, expr) => {
+            resolve_expr(expr, aliases)
+        }
+        StmtKind::Ret(value) => {
+            if let Some(value) = value {
+                resolve_expr(value, aliases);
+            }
+        }
+        StmtKind::Const(_, ty, value) => {
+            *ty = ty.subst(aliases);
+            resolve_expr(value, aliases);
+        }
+        StmtKind::Block(block) | StmtKind::Impl(_, block) => resolve_block(block, aliases),
+        StmtKind::Func(proto, body) => {
+            let aliases = visible(aliases, &proto.generics);
+            resolve_proto(proto, &aliases);
+            resolve_block(body, &aliases);
+        }
+        StmtKind::Extern(proto) => resolve_proto(proto, aliases),
+        StmtKind::Struct(def) => {
+            let aliases = visible(aliases, &def.generics);
+            for field in &mut def.fields {
+                field.ty = field.ty.subst(&aliases);
+            }
+        }
+        StmtKind::Enum(def) => {
+            for variant in &mut def.variants {
+                for ty in &mut variant.fields {
+                    *ty = ty.subst(aliases);
+                }
+            }
+        }
+        StmtKind::Type(name, ty, _) => {
+            if let Some(&resolved) = aliases.get(name) {
+                *ty = resolved;
+            }
+        }
+        StmtKind::Impt(_) | StmtKind::Error => {}
+    }
+}
+
+fn resolve_proto(proto: &mut FuncProto, aliases: &HashMap<Name, Type>) {
+    for param in &mut proto.params {
+        param.ty = param.ty.subst(aliases);
+    }
+    proto.ret = proto.ret.subst(aliases);
+}
+
+fn resolve_block(block: &mut Block, aliases: &HashMap<Name, Type>) {
+    for stmt in block {
+        resolve_stmt(stmt, aliases);
+    }
+}
+
+fn resolve_expr(expr: &mut Expr, aliases: &HashMap<Name, Type>) {
+    match &mut expr.kind {
+        ExprKind::Unary(_, operand) | ExprKind::Field(operand, _) => resolve_expr(operand, aliases),
+        ExprKind::Binary(_, lhs, rhs) | ExprKind::Assign(lhs, rhs) | ExprKind::Index(lhs, rhs) => {
+            resolve_expr(lhs, aliases);
+            resolve_expr(rhs, aliases);
+        }
+        ExprKind::Call(callee, args) | ExprKind::MethodCall(callee, _, args) => {
+            resolve_expr(callee, aliases);
+            for arg in args {
+                resolve_expr(arg, aliases);
+            }
+        }
+        ExprKind::Tuple(elems) | ExprKind::Array(elems) | ExprKind::Variant(_, _, elems) => {
+            for elem in elems {
+                resolve_expr(elem, aliases);
+            }
+        }
+        ExprKind::StructLit(_, fields) => {
+            for (_, value) in fields {
+                resolve_expr(value, aliases);
+            }
+        }
+        ExprKind::Cond(cond, then, otherwise) => {
+            resolve_expr(cond, aliases);
+            resolve_block(then, aliases);
+            if let Some(otherwise) = otherwise {
+                resolve_block(otherwise, aliases);
+            }
+        }
+        ExprKind::Cast(value, ty) => {
+            resolve_expr(value, aliases);
+            *ty = ty.subst(aliases);
+        }
+        ExprKind::Match(scrutinee, arms) => {
+            resolve_expr(scrutinee, aliases);
+            for arm in arms {
+                resolve_block(&mut arm.body, aliases);
+            }
+        }
+        ExprKind::Lambda(params, ret, body) => {
+            for param in params {
+                param.ty = param.ty.subst(aliases);
+            }
+            *ret = ret.subst(aliases);
+            resolve_block(body, aliases);
+        }
+        ExprKind::Lit(_) | ExprKind::Variable(_) | ExprKind::Error => {}
+    }
+}
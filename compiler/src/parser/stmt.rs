@@ -1,38 +1,49 @@
+use super::ty::builtin_type;
 use super::Parser;
 
 use crate::ast::lit::Lit;
-use crate::ast::stmt::{FuncProto, Param, Stmt, StmtKind};
-use crate::ast::ty::Type;
-use crate::token::TokenKind::*;
+use crate::ast::stmt::{
+    method_name, Block, EnumDef, Field, FuncProto, Param, Stmt, StmtKind, StructDef, Variant,
+};
+use crate::ast::ty::{self, Type};
+use crate::generate_error_message;
+use crate::name::{nm::SELF, Name};
+use crate::token::{Token, TokenKind, TokenKind::*};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
 type StmtResult = Result<Box<Stmt>>;
 
 impl Parser {
     // NOTE: might rename to definition
-    pub(super) fn declaration(&mut self) -> StmtResult {
+    pub(super) fn declaration(&mut self) -> Box<Stmt> {
+        let start = self.current;
         let decl = match self.peek().kind {
             Extern => self.extern_decl(),
             Func => self.func_decl(),
+            Struct => self.struct_decl(),
+            Enum => self.enum_decl(),
+            TokenKind::Type | Newtype => self.type_decl(),
+            Impl => self.impl_block(),
             Var => self.var_decl(),
+            Const => self.const_decl(),
             Impt => self.impt(),
             _ => self.statement(),
         };
-
-        if let Err(ref _parse_error) = decl {
-            self.sync();
-            // self.errors.push(parse_error);
-        }
-
-        decl
+        self.recover(start, decl)
     }
 
     fn extern_decl(&mut self) -> StmtResult {
         // eat 'extern'
         let extern_kw = self.eat();
         self.expect(Func, "expected 'func'")?;
-        let proto = self.proto()?;
+        let proto = self.proto(None)?;
+        if !proto.generics.is_empty() {
+            bail!(
+                "{}",
+                self.generate_error_message("extern functions can't have type parameters")
+            );
+        }
         let semicolon = self.expect(Semicolon, "expected ';' after function prototype")?;
         Ok(Stmt::new(
             StmtKind::Extern(proto),
@@ -42,9 +53,17 @@ impl Parser {
 
     // Parse function declarations
     fn func_decl(&mut self) -> StmtResult {
+        self.func_decl_in(None)
+    }
+
+    /// Parses a function declaration, `impl_ty` is the type of the `impl` block it's in
+    fn func_decl_in(&mut self, impl_ty: Option<Name>) -> StmtResult {
         // eat 'func'
         let func_kw = self.eat();
-        let proto = self.proto()?;
+        let mut proto = self.proto(impl_ty)?;
+        if let Some(ty) = impl_ty {
+            proto.name = method_name(ty, proto.name);
+        }
         self.expect(OpenBrace, "expected '{' before function body")?;
         let body = self.block()?;
         let close_brace = self.previous();
@@ -55,24 +74,211 @@ impl Parser {
         ))
     }
 
+    // Parse struct declarations
+    fn struct_decl(&mut self) -> StmtResult {
+        // eat 'struct'
+        let struct_kw = self.eat();
+        let name = self.expect_ident("expected struct name")?;
+        let generics = self.type_params()?;
+        self.expect(OpenBrace, "expected '{' after struct name")?;
+
+        let mut fields = Vec::new();
+        while !self.check(CloseBrace) {
+            let name = self.expect_ident("expected field name")?;
+            self.expect(Colon, "expected type annotation after field name")?;
+            let ty = self.type_annotation()?;
+            fields.push(Field { name, ty });
+
+            // the last field can have a trailing comma
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+
+        let close_brace = self.expect(CloseBrace, "expected '}' after struct fields")?;
+
+        Ok(Stmt::new(
+            StmtKind::Struct(StructDef {
+                name,
+                generics,
+                fields,
+            }),
+            self.region_from(struct_kw, close_brace),
+        ))
+    }
+
+    // Parse enum declarations
+    fn enum_decl(&mut self) -> StmtResult {
+        // eat 'enum'
+        let enum_kw = self.eat();
+        let name = self.expect_ident("expected enum name")?;
+        self.expect(OpenBrace, "expected '{' after enum name")?;
+
+        let mut variants = Vec::new();
+        while !self.check(CloseBrace) {
+            let name = self.expect_ident("expected variant name")?;
+            let mut fields = Vec::new();
+            if self.matches(&[OpenParen]) {
+                while !self.check(CloseParen) {
+                    fields.push(self.type_annotation()?);
+                    if !self.matches(&[Comma]) {
+                        break;
+                    }
+                }
+                self.expect(CloseParen, "expected ')' after the variant's types")?;
+            }
+            variants.push(Variant { name, fields });
+
+            // the last variant can have a trailing comma
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+
+        let close_brace = self.expect(CloseBrace, "expected '}' after enum variants")?;
+
+        Ok(Stmt::new(
+            StmtKind::Enum(EnumDef { name, variants }),
+            self.region_from(enum_kw, close_brace),
+        ))
+    }
+
+    /// Parses a type alias (`type Meters = int32;`) or a newtype (`newtype UserId = int64;`). The
+    /// name stands for the type anywhere in the module
+    fn type_decl(&mut self) -> StmtResult {
+        // eat 'type' or 'newtype'
+        let type_kw = self.eat();
+        let name = self.expect_ident("expected a type name")?;
+        let message = if builtin_type(name).is_some() {
+            Some(format!("`{name}` is a built-in type"))
+        } else if self.aliases.contains_key(&name) {
+            Some(format!("the type `{name}` is already declared"))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            let region = self.previous().region;
+            bail!("{}", generate_error_message(&message, &self.source, region));
+        }
+
+        self.expect(Equal, "expected '=' after the type's name")?;
+        let start = self.peek();
+        let mut ty = self.type_annotation()?;
+        let ty_region = self.region_since(start);
+        if type_kw.kind == Newtype {
+            ty = Type::Newtype(name, ty::intern(ty));
+        }
+        let semicolon = self.expect(Semicolon, "expected ';' after the type declaration")?;
+
+        self.aliases.insert(name, (ty, ty_region));
+        Ok(Stmt::new(
+            StmtKind::Type(name, ty, ty_region),
+            self.region_from(type_kw, semicolon),
+        ))
+    }
+
+    /// type_params -> '<' IDENTIFIER ( ',' IDENTIFIER )* '>'
+    fn type_params(&mut self) -> Result<Vec<Name>> {
+        let mut generics = Vec::new();
+        if !self.matches(&[Lt]) {
+            return Ok(generics);
+        }
+
+        loop {
+            generics.push(self.expect_ident("expected type parameter name")?);
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+
+        self.expect(Gt, "expected '>' after type parameters")?;
+        Ok(generics)
+    }
+
     // helper function to parse punction prototypes
-    fn proto(&mut self) -> Result<FuncProto> {
+    /// impl_block -> "impl" IDENTIFIER '{' func_decl* '}'
+    fn impl_block(&mut self) -> StmtResult {
+        // eat 'impl'
+        let impl_kw = self.eat();
+        let ty = self.expect_ident("expected type name after 'impl'")?;
+        self.expect(OpenBrace, "expected '{' after type name")?;
+
+        let mut methods = Vec::new();
+        while self.check(Func) {
+            let start = self.current;
+            let method = self.func_decl_in(Some(ty));
+            methods.push(self.recover(start, method));
+        }
+        let close_brace = self.expect(CloseBrace, "expected '}' after methods")?;
+
+        Ok(Stmt::new(
+            StmtKind::Impl(ty, methods),
+            self.region_from(impl_kw, close_brace),
+        ))
+    }
+
+    /// Parses a function prototype. The first parameter of a method can be a bare `self`, which
+    /// has the type of the `impl` block
+    fn proto(&mut self, impl_ty: Option<Name>) -> Result<FuncProto> {
         // note where the function prototype starts
         let proto_start = self.peek();
         // get the function name
         let name = self.expect_ident("expected function name")?;
+        let generics = self.type_params()?;
         self.expect(OpenParen, "expected '(' after function name")?;
+        let params = self.parameter_list(impl_ty)?;
+        // the type checker makes sure only extern functions are variadic
+        let variadic = self.matches(&[Ellipsis]);
+        let close_paren = self.expect(CloseParen, "expected ')' after parameter list")?;
+
+        let (ret, ret_region) = if self.matches(&[Colon]) {
+            let start = self.peek();
+            (self.type_annotation()?, self.region_since(start))
+        } else {
+            (Type::Void, close_paren.region)
+        };
+
+        Ok(FuncProto {
+            name,
+            generics,
+            params,
+            variadic,
+            ret,
+            ret_region,
+            region: self.region_from(proto_start, close_paren), //
+        })
+    }
 
+    /// parameter_list -> parameter ( ',' parameter )*
+    /// Stops before the ')' or the '...' of a variadic function, the parameters of a method in an
+    /// impl block can start with a bare "self"
+    pub(super) fn parameter_list(&mut self, impl_ty: Option<Name>) -> Result<Vec<Param>> {
         let mut params = Vec::new();
 
         // check if there is a parameter list
         if !self.check(CloseParen) {
             loop {
+                if self.check(Ellipsis) {
+                    break;
+                }
+
                 // eat the parameter name
                 let name = self.expect_ident("expected parameter list")?;
-                self.expect(Colon, "expected type annotation after parameter name")?;
-                let ty = self.type_annotation()?;
-                params.push(Param { ty, name });
+                let (ty, ty_region) = match impl_ty {
+                    Some(ty) if params.is_empty() && name == *SELF && !self.check(Colon) => {
+                        (Type::Name(ty), self.previous().region)
+                    }
+                    _ => {
+                        self.expect(Colon, "expected type annotation after parameter name")?;
+                        let start = self.peek();
+                        (self.type_annotation()?, self.region_since(start))
+                    }
+                };
+                params.push(Param {
+                    name,
+                    ty,
+                    ty_region,
+                });
 
                 if !self.matches(&[Comma]) {
                     break;
@@ -80,20 +286,7 @@ impl Parser {
             }
         }
 
-        let close_paren = self.expect(CloseParen, "expected ')' after parameter list")?;
-
-        let ret = if self.matches(&[Colon]) {
-            self.type_annotation()?
-        } else {
-            Type::Void
-        };
-
-        Ok(FuncProto {
-            name,
-            params,
-            ret,
-            region: self.region_from(proto_start, close_paren), //
-        })
+        Ok(params)
     }
 
     fn statement(&mut self) -> StmtResult {
@@ -115,7 +308,12 @@ impl Parser {
     fn ret_stmt(&mut self) -> StmtResult {
         // eat 'ret'
         let ret_kw = self.eat();
-        let value = self.expression()?;
+        // void functions return without a value
+        let value = if self.check(Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
         let semicolon = self.expect(Semicolon, "expected ';' at end of return statement")?;
         Ok(Stmt::new(
             StmtKind::Ret(value),
@@ -123,11 +321,12 @@ impl Parser {
         ))
     }
 
-    pub fn block(&mut self) -> Result<Vec<Box<Stmt>>> {
+    pub fn block(&mut self) -> Result<Block> {
         assert_eq!(self.previous(), OpenBrace);
         let mut stmts = Vec::new();
+        // a statement with a syntax error is skipped, the rest of the block is still parsed
         while !self.check(CloseBrace) && !self.is_eof() {
-            stmts.push(self.declaration()?);
+            stmts.push(self.declaration());
         }
 
         // eat '}'
@@ -156,6 +355,9 @@ impl Parser {
     fn var_decl(&mut self) -> StmtResult {
         // eat 'var'
         let var_kw = self.eat();
+        if self.check(OpenParen) {
+            return self.destructure(var_kw);
+        }
         let name = self.expect_ident("expected variable name")?;
 
         self.expect(Equal, "expected '=' in variable declaration")?;
@@ -171,6 +373,36 @@ impl Parser {
         ))
     }
 
+    /// Parses a declaration of the variables in a tuple pattern (`var (q, r) = divmod(a, b);`)
+    /// after the "var"
+    fn destructure(&mut self, var_kw: Token) -> StmtResult {
+        let pattern = self.pattern()?;
+        self.expect(Equal, "expected '=' in variable declaration")?;
+        let init = self.expression()?;
+        let semicolon = self.expect(Semicolon, "expected ';' at end of variable declaration")?;
+
+        Ok(Stmt::new(
+            StmtKind::Destructure(pattern, init),
+            self.region_from(var_kw, semicolon),
+        ))
+    }
+
+    fn const_decl(&mut self) -> StmtResult {
+        // eat 'const'
+        let const_kw = self.eat();
+        let name = self.expect_ident("expected constant name")?;
+        self.expect(Colon, "expected type annotation after constant name")?;
+        let ty = self.type_annotation()?;
+        self.expect(Equal, "expected '=' in constant declaration")?;
+        let value = self.expression()?;
+        let semicolon = self.expect(Semicolon, "expected ';' at end of constant declaration")?;
+
+        Ok(Stmt::new(
+            StmtKind::Const(name, ty, value),
+            self.region_from(const_kw, semicolon),
+        ))
+    }
+
     fn impt(&mut self) -> StmtResult {
         use Lit::Str;
 
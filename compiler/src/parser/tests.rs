@@ -1,18 +1,18 @@
 use super::Parser;
+use crate::ast::stmt::Module;
 use crate::ast::{Expr, Stmt, StmtKind};
 use crate::lexer::Lexer;
 use crate::name::Name;
 use crate::source::Source;
 
-
-
-pub fn parse(source_code: &'static str) -> Vec<Box<Stmt>> {
+pub fn parse(source_code: &'static str) -> Module {
     let source = Source::new(source_code, "<string literal>");
     let mut lexer = Lexer::new(&source);
     let mut parser = Parser::new(lexer.lex(), &source);
 
-    let stmts: Result<Vec<_>, _> = parser.parse().into_iter().collect();
-    stmts.expect("failed to parse")
+    let module = parser.parse();
+    assert!(parser.errors().is_empty(), "failed to parse");
+    module
 }
 
 fn extract_stmt(module: &[Box<Stmt>]) -> &Stmt {
@@ -46,6 +46,103 @@ mod stmt {
         assert_eq!(value.kind, 32.into());
     }
 
+    #[test]
+    fn ret_outside_functions() {
+        let source = Source::new("ret 1; func f() { ret; }", "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+
+        let errors: Vec<_> = parser.errors().iter().map(ToString::to_string).collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("`ret` can only be used in a function"));
+        assert_eq!(module[0].kind, StmtKind::Error);
+    }
+
+    #[test]
+    fn error_recovery() {
+        let source = Source::new(
+            "struct P { x: , y: int32 }
+            func f(a: int32): int32 {
+                var x = (a + ;
+                if a > { ret 1; }
+                var y = a;
+                ret y
+            }
+            func g( { var q = 1; }
+            var s = P { x: , };
+            }
+            impl P { func m(self) { var = 1; } func n(self) { } }
+            func h() { }",
+            "<string literal>",
+        );
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+
+        // every statement with an error is skipped up to the end of the statement or the block
+        // it's in, and parsing goes on after it
+        assert_eq!(parser.errors().len(), 8);
+        let kinds: Vec<_> = module
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StmtKind::Error => "error",
+                StmtKind::Func(..) => "func",
+                StmtKind::Impl(..) => "impl",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, ["error", "func", "error", "error", "impl", "func"]);
+
+        let StmtKind::Func(_, ref body) = module[1].kind else {
+            panic!("expected func");
+        };
+        let body: Vec<_> = body
+            .iter()
+            .map(|stmt| matches!(stmt.kind, StmtKind::Error))
+            .collect();
+        assert_eq!(body, [true, true, false, true]);
+
+        let StmtKind::Impl(_, ref methods) = module[4].kind else {
+            panic!("expected impl");
+        };
+        assert_eq!(methods.len(), 2);
+    }
+
+    #[test]
+    fn recovery_in_brackets_and_lexer_errors() {
+        let source = Source::new(
+            "func f() {
+                var a: [int32; 3] = [1, 2, 3];
+                var b = g(a b, func() { ret 1; });
+                var c = \"abc;
+            }",
+            "<string literal>",
+        );
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        parser.parse();
+
+        // the ';' in the array type and the lambda in the call don't end the declarations, and
+        // the unterminated string is only reported by the lexer
+        let errors: Vec<_> = parser.errors().iter().map(ToString::to_string).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("expected '=' in variable declaration"));
+        assert!(errors[1].starts_with("expected ')' after argument list"));
+        assert_eq!(lexer.errors().len(), 1);
+    }
+
+    #[test]
+    fn const_decl() {
+        let module = parse("const MAX: uint8 = 255;");
+        let StmtKind::Const(name, ty, ref value) = extract_stmt(&module).kind else {
+            panic!("expected a const decl");
+        };
+        assert_eq!(name, "MAX");
+        assert_eq!(ty.to_string(), "uint8");
+        assert_eq!(value.kind, 255.into());
+    }
+
     #[test]
     fn basic_func_decl() {
         let module = parse("func main() { }");
@@ -62,6 +159,45 @@ mod stmt {
         assert!(block.is_empty());
     }
 
+    #[test]
+    fn void_ret() {
+        let module = parse("func f() { ret; }");
+        let StmtKind::Func(_, ref body) = extract_stmt(&module).kind else {
+            panic!("expected func");
+        };
+        assert_eq!(body[0].kind, StmtKind::Ret(None));
+    }
+
+    #[test]
+    fn impl_block() {
+        let module = parse(
+            "impl Point {
+                func new(): Point { }
+                func len(self): int32 { }
+                func scale(self: *Point, by: int32) { }
+            }",
+        );
+        let StmtKind::Impl(ty, ref methods) = extract_stmt(&module).kind else {
+            panic!("expected impl block");
+        };
+        assert_eq!(ty, "Point");
+
+        let protos: Vec<_> = methods
+            .iter()
+            .map(|method| match &method.kind {
+                StmtKind::Func(proto, _) => proto,
+                _ => panic!("expected func"),
+            })
+            .collect();
+        assert_eq!(protos[0].name, "Point::new");
+        assert!(protos[0].params.is_empty());
+        assert_eq!(protos[1].name, "Point::len");
+        assert_eq!(protos[1].params[0].name, "self");
+        assert_eq!(protos[1].params[0].ty.to_string(), "Point");
+        assert_eq!(protos[2].params[0].ty.to_string(), "*Point");
+        assert_eq!(protos[2].params[1].name, "by");
+    }
+
     #[test]
     fn complex_func_decl() {
         let module = parse(
@@ -85,7 +221,7 @@ mod stmt {
             param_1.ty,
             Type::Int {
                 signed: true,
-                size: IntSize::new(32)
+                size: IntSize::new(32).unwrap()
             }
         );
 
@@ -95,7 +231,7 @@ mod stmt {
             param_2.ty,
             Type::Int {
                 signed: false,
-                size: IntSize::new(8)
+                size: IntSize::new(8).unwrap()
             }
         );
 
@@ -103,6 +239,246 @@ mod stmt {
         assert_eq!(proto.ret, Type::Name(Name::from("String")));
         assert_eq!(block.len(), 2);
     }
+
+    #[test]
+    fn array_types() {
+        let module = parse("func f(a: [int32; 4], s: [[uint8; 2]]) { }");
+        let StmtKind::Func(ref proto, _) = extract_stmt(&module).kind else {
+            panic!("expected func");
+        };
+
+        assert_eq!(proto.params[0].ty.to_string(), "[int32; 4]");
+        assert_eq!(proto.params[1].ty.to_string(), "[[uint8; 2]]");
+    }
+
+    #[test]
+    fn constant_array_lengths() {
+        let module = parse(
+            "const N: uint32 = 4;
+            const M: int64 = N as int64 * 2 - 1;
+            func f(a: [int32; N], b: [[int8; M + 1]; 3]) { }",
+        );
+        let StmtKind::Func(ref proto, _) = module[2].kind else {
+            panic!("expected func");
+        };
+
+        assert_eq!(proto.params[0].ty.to_string(), "[int32; 4]");
+        assert_eq!(proto.params[1].ty.to_string(), "[[int8; 8]; 3]");
+    }
+
+    #[test]
+    fn invalid_array_lengths() {
+        let source = Source::new(
+            "const NEG: int32 = -2;
+            func f(a: [int32; NEG]) { }
+            func g(a: [int32; LATER]) { }
+            const LATER: uint8 = 1;
+            func h(a: [int32; 1.5]) { }
+            func i(a: [int32; 1 / 0]) { }",
+            "<string literal>",
+        );
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        parser.parse();
+
+        let errors: Vec<_> = parser
+            .errors()
+            .iter()
+            .map(|err| err.to_string().lines().next().unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "the length of an array can't be negative but is -2",
+                "`LATER` is not defined",
+                "expected `uint64` but found a float",
+                "this divides by zero at compile time",
+            ]
+        );
+    }
+
+    #[test]
+    fn pointer_types() {
+        let module = parse("extern func f(p: *int32, pp: **[uint8; 2]): *Point;");
+        let StmtKind::Extern(ref proto) = extract_stmt(&module).kind else {
+            panic!("expected extern");
+        };
+
+        assert_eq!(proto.params[0].ty.to_string(), "*int32");
+        assert_eq!(proto.params[1].ty.to_string(), "**[uint8; 2]");
+        assert_eq!(proto.ret.to_string(), "*Point");
+    }
+
+    #[test]
+    fn func_types() {
+        let module =
+            parse("extern func f(cb: func(int32, *uint8): bool, g: func()): func(): [int32];");
+        let StmtKind::Extern(ref proto) = extract_stmt(&module).kind else {
+            panic!("expected extern");
+        };
+
+        assert_eq!(proto.params[0].ty.to_string(), "func(int32, *uint8): bool");
+        assert_eq!(proto.params[1].ty.to_string(), "func()");
+        assert_eq!(proto.ret.to_string(), "func(): [int32]");
+    }
+
+    #[test]
+    fn variadic_protos() {
+        let module = parse("extern func printf(fmt: str, ...): int32; extern func f(...);");
+        let StmtKind::Extern(ref proto) = module[0].kind else {
+            panic!("expected extern");
+        };
+        assert!(proto.variadic);
+        assert_eq!(proto.params.len(), 1);
+
+        let StmtKind::Extern(ref proto) = module[1].kind else {
+            panic!("expected extern");
+        };
+        assert!(proto.variadic);
+        assert!(proto.params.is_empty());
+    }
+
+    #[test]
+    fn tuples() {
+        let module = parse(
+            "func divmod(a: int32, b: int32): (int32, (bool, [uint8])) { }
+            var (q, (_, r)) = divmod(7, 2);",
+        );
+        let StmtKind::Func(ref proto, _) = module[0].kind else {
+            panic!("expected func");
+        };
+        assert_eq!(proto.ret.to_string(), "(int32, (bool, [uint8]))");
+
+        let StmtKind::Destructure(ref pattern, _) = module[1].kind else {
+            panic!("expected a destructuring var decl");
+        };
+        assert_eq!(pattern.to_string(), "(q, (_, r))");
+    }
+
+    #[test]
+    fn type_decls() {
+        let module = parse(
+            "func f(m: Meters, users: Users, p: Point): UserId { }
+            type Meters = int32;
+            type Users = [UserId];
+            newtype UserId = int64;",
+        );
+        let StmtKind::Type(name, ty, _) = module[3].kind else {
+            panic!("expected a type decl");
+        };
+        assert_eq!(name, "UserId");
+        assert!(
+            matches!(ty, Type::Newtype(_, &inner) if inner == Type::Int {
+                signed: true,
+                size: IntSize::new(64).unwrap()
+            })
+        );
+
+        // aliases and newtypes are replaced anywhere in the module, even before their declaration,
+        // other names are left to the type checker
+        let StmtKind::Type(_, users, _) = module[2].kind else {
+            panic!("expected a type decl");
+        };
+        assert_eq!(users, Type::Slice(crate::ast::ty::intern(ty)));
+        let StmtKind::Func(ref proto, _) = module[0].kind else {
+            panic!("expected func");
+        };
+        assert_eq!(proto.params[0].ty, Type::DEFAULT_INT);
+        assert_eq!(proto.params[1].ty, Type::Slice(crate::ast::ty::intern(ty)));
+        assert_eq!(proto.params[2].ty, Type::Name(Name::from("Point")));
+        assert_eq!(proto.ret, ty);
+    }
+
+    #[test]
+    fn type_decl_cycles() {
+        let source = Source::new(
+            "type A = B;
+            type B = *A;
+            newtype Id = Id;
+            type C = [A];
+            func f(c: C) { }",
+            "<string literal>",
+        );
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        parser.parse();
+
+        // every cycle is reported once, at the type of its first declaration
+        let errors: Vec<_> = parser.errors().iter().map(|err| err.to_string()).collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].lines().next().unwrap(),
+            "the type `A` is declared in terms of itself: `A` -> `B` -> `A`"
+        );
+        assert!(errors[0].ends_with("type A = B;\n         ^"));
+        assert_eq!(
+            errors[1].lines().next().unwrap(),
+            "the type `Id` is declared in terms of itself: `Id` -> `Id`"
+        );
+    }
+
+    #[test]
+    fn generics() {
+        let module = parse(
+            "struct Pair<A, B> { first: A, second: B }
+            func swap<A, B>(p: Pair<A, B>, boxes: [Box<Pair<A, int32>>]): Pair<B, A> { }",
+        );
+        let StmtKind::Struct(ref def) = module[0].kind else {
+            panic!("expected a struct decl");
+        };
+        assert_eq!(def.generics, [Name::from("A"), Name::from("B")]);
+
+        let StmtKind::Func(ref proto, _) = module[1].kind else {
+            panic!("expected func");
+        };
+        assert_eq!(proto.generics, [Name::from("A"), Name::from("B")]);
+        assert_eq!(proto.params[0].ty.to_string(), "Pair<A, B>");
+        assert_eq!(proto.params[1].ty.to_string(), "[Box<Pair<A, int32>>]");
+        assert_eq!(proto.ret.to_string(), "Pair<B, A>");
+    }
+
+    #[test]
+    fn enum_decl() {
+        let module = parse("enum Shape { Circle(int32), Rect(int32, [uint8; 2]), Empty, }");
+        let StmtKind::Enum(ref def) = extract_stmt(&module).kind else {
+            panic!("expected an enum decl");
+        };
+
+        assert_eq!(def.name, "Shape");
+        let variants: Vec<_> = def
+            .variants
+            .iter()
+            .map(|v| (v.name.to_string(), v.fields.len()))
+            .collect();
+        assert_eq!(
+            variants,
+            [
+                ("Circle".to_string(), 1),
+                ("Rect".to_string(), 2),
+                ("Empty".to_string(), 0)
+            ]
+        );
+        assert_eq!(def.variants[1].fields[1].to_string(), "[uint8; 2]");
+    }
+
+    #[test]
+    fn struct_decl() {
+        let module = parse("struct Point { x: int32, y: int32, }");
+        let stmt = extract_stmt(&module);
+
+        let StmtKind::Struct(ref def) = stmt.kind else {
+            panic!("expected a struct decl");
+        };
+        assert_eq!(def.name, "Point");
+        let fields: Vec<_> = def.fields.iter().map(|f| (f.name, f.ty)).collect();
+        assert_eq!(
+            fields,
+            [
+                (Name::from("x"), Type::DEFAULT_INT),
+                (Name::from("y"), Type::DEFAULT_INT)
+            ]
+        );
+    }
 }
 
 mod expr {
@@ -117,18 +493,241 @@ mod expr {
         let module = parse("a = b = c;");
         let expr = extract_expr(&module);
 
-        let Assign(var_name, ref expr) = expr.kind else {
+        let Assign(ref target, ref expr) = expr.kind else {
             panic!("expected assignment expression");
         };
-        assert_eq!(var_name, "a");
+        assert_eq!(target.kind, Variable(Name::from("a")));
 
-        let Assign(var_name, ref expr) = expr.kind else {
+        let Assign(ref target, ref expr) = expr.kind else {
             panic!("expected assignment expression");
         };
-        assert_eq!(var_name, "b");
+        assert_eq!(target.kind, Variable(Name::from("b")));
         assert_eq!(expr.kind, Variable(Name::from("c")));
     }
 
+    #[test]
+    fn fields() {
+        use ExprKind::{Assign, Field, StructLit, Variable};
+
+        let module = parse("a.b.c = Point { x: 1, y: a.x };");
+        let expr = extract_expr(&module);
+
+        let Assign(ref target, ref value) = expr.kind else {
+            panic!("expected assignment expression");
+        };
+        let Field(ref base, c) = target.kind else {
+            panic!("expected field access");
+        };
+        assert_eq!(c, "c");
+        let Field(ref base, b) = base.kind else {
+            panic!("expected field access");
+        };
+        assert_eq!(b, "b");
+        assert_eq!(base.kind, Variable(Name::from("a")));
+
+        let StructLit(name, ref fields) = value.kind else {
+            panic!("expected struct literal");
+        };
+        assert_eq!(name, "Point");
+        assert_eq!(fields[0].0, "x");
+        assert_eq!(fields[1].0, "y");
+        assert!(matches!(fields[1].1.kind, Field(_, x) if x == "x"));
+    }
+
+    #[test]
+    fn method_calls() {
+        use ExprKind::{Field, MethodCall, Variable, Variant};
+
+        let module = parse("a.b.len(1, 2).x + Point::new();");
+        let ExprKind::Binary(_, ref lhs, ref rhs) = extract_expr(&module).kind else {
+            panic!("expected binary expression");
+        };
+
+        let Field(ref call, x) = lhs.kind else {
+            panic!("expected field access");
+        };
+        assert_eq!(x, "x");
+        let MethodCall(ref receiver, method, ref args) = call.kind else {
+            panic!("expected method call");
+        };
+        assert_eq!(method, "len");
+        assert_eq!(args.len(), 2);
+        let Field(ref base, _) = receiver.kind else {
+            panic!("expected field access");
+        };
+        assert_eq!(base.kind, Variable(Name::from("a")));
+
+        // associated functions are parsed like variants, the type checker tells them apart
+        assert!(matches!(rhs.kind, Variant(ty, new, _) if ty == "Point" && new == "new"));
+    }
+
+    #[test]
+    fn chained_calls_and_lambdas() {
+        use ExprKind::{Call, Index, Lambda, Variable};
+
+        let module = parse("make(1)(2)[0](func(x: int32): int32 { ret x; });");
+        let Call(ref callee, ref args) = extract_expr(&module).kind else {
+            panic!("expected call expr");
+        };
+        let Lambda(ref params, ret, ref body) = args[0].kind else {
+            panic!("expected lambda");
+        };
+        assert_eq!(params.len(), 1);
+        assert_eq!(ret.to_string(), "int32");
+        assert_eq!(body.len(), 1);
+
+        let Index(ref base, _) = callee.kind else {
+            panic!("expected index expression");
+        };
+        let Call(ref inner, _) = base.kind else {
+            panic!("expected call expr");
+        };
+        let Call(ref make, _) = inner.kind else {
+            panic!("expected call expr");
+        };
+        assert_eq!(make.kind, Variable(Name::from("make")));
+    }
+
+    #[test]
+    fn indexing() {
+        use ExprKind::{Array, Assign, Field, Index, Variable};
+
+        let module = parse("a[i].xs[0] = [1, 2, 3,];");
+        let expr = extract_expr(&module);
+
+        let Assign(ref target, ref value) = expr.kind else {
+            panic!("expected assignment expression");
+        };
+        let Index(ref base, ref index) = target.kind else {
+            panic!("expected index expression");
+        };
+        assert_eq!(index.kind, 0.into());
+        let Field(ref base, _) = base.kind else {
+            panic!("expected field access");
+        };
+        let Index(ref base, ref index) = base.kind else {
+            panic!("expected index expression");
+        };
+        assert_eq!(base.kind, Variable(Name::from("a")));
+        assert_eq!(index.kind, Variable(Name::from("i")));
+
+        let Array(ref elems) = value.kind else {
+            panic!("expected array literal");
+        };
+        assert_eq!(elems.len(), 3);
+    }
+
+    #[test]
+    fn pointers() {
+        use crate::ast::UnOp;
+        use ExprKind::{Assign, Binary, Field, Unary, Variable};
+
+        // `*` is a dereference at the start of an operand and a multiplication between them
+        let module = parse("*p = a * *&b.c;");
+        let expr = extract_expr(&module);
+
+        let Assign(ref target, ref value) = expr.kind else {
+            panic!("expected assignment expression");
+        };
+        let Unary(UnOp::Deref, ref ptr) = target.kind else {
+            panic!("expected a dereference");
+        };
+        assert_eq!(ptr.kind, Variable(Name::from("p")));
+
+        let Binary(BinOp::Mul, _, ref rhs) = value.kind else {
+            panic!("expected a multiplication");
+        };
+        let Unary(UnOp::Deref, ref rhs) = rhs.kind else {
+            panic!("expected a dereference");
+        };
+        let Unary(UnOp::Ref, ref rhs) = rhs.kind else {
+            panic!("expected an address-of");
+        };
+        assert!(matches!(rhs.kind, Field(_, c) if c == "c"));
+    }
+
+    #[test]
+    fn move_and_clone() {
+        use crate::ast::UnOp;
+        use ExprKind::{Call, Unary, Variable};
+
+        let module = parse("f(move a, clone move b);");
+        let expr = extract_expr(&module);
+
+        let Call(_, ref args) = expr.kind else {
+            panic!("expected a call");
+        };
+        let Unary(UnOp::Move, ref a) = args[0].kind else {
+            panic!("expected a move");
+        };
+        assert_eq!(a.kind, Variable(Name::from("a")));
+        let Unary(UnOp::Clone, ref b) = args[1].kind else {
+            panic!("expected a clone");
+        };
+        assert!(matches!(b.kind, Unary(UnOp::Move, _)));
+    }
+
+    #[test]
+    fn struct_lit_in_condition() {
+        use ExprKind::{Cond, Variable};
+
+        // the `{` after `x` starts the then branch, not a struct literal
+        let module = parse("if x { y; }");
+        let expr = extract_expr(&module);
+
+        let Cond(ref condition, ref then_branch, _) = expr.kind else {
+            panic!("expected a conditional expression");
+        };
+        assert_eq!(condition.kind, Variable(Name::from("x")));
+        assert_eq!(then_branch.len(), 1);
+    }
+
+    #[test]
+    fn match_expression() {
+        use ExprKind::{Match, Variant};
+
+        let module = parse(
+            "match Shape::Rect(w, 2) {
+                Shape::Rect(w, _) => { w; },
+                Shape::Circle(-1) => { }
+                'a' => { }
+                other => { }
+            }",
+        );
+        let expr = extract_expr(&module);
+
+        let Match(ref scrutinee, ref arms) = expr.kind else {
+            panic!("expected a match expression");
+        };
+        let Variant(enum_name, variant, ref args) = scrutinee.kind else {
+            panic!("expected a variant");
+        };
+        assert_eq!(
+            (enum_name, variant),
+            (Name::from("Shape"), Name::from("Rect"))
+        );
+        assert_eq!(args.len(), 2);
+
+        let patterns: Vec<String> = arms.iter().map(|arm| arm.pattern.to_string()).collect();
+        assert_eq!(
+            patterns,
+            ["Shape::Rect(w, _)", "Shape::Circle(-1)", "'a'", "other"]
+        );
+        assert_eq!(arms[0].body.len(), 1);
+    }
+
+    #[test]
+    fn invalid_assign_target() {
+        let source = Source::new("f() = 1;", "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+        assert_eq!(parser.errors().len(), 1);
+
+        // the assignment is replaced, the statement around it is still parsed
+        assert_eq!(extract_expr(&module).kind, ExprKind::Error);
+    }
+
     #[test]
     fn call() {
         use ExprKind::{Call, Variable};
@@ -139,10 +738,10 @@ mod expr {
         // TODO: this should be possible :)
         // assert_eq!(expr.to_string(), "Call([Variable(\"bar\"), Variable(\"baz\")])");
 
-        let Call(fn_name, ref args) = expr.kind else {
+        let Call(ref callee, ref args) = expr.kind else {
             panic!("expected call expr");
         };
-        assert_eq!(fn_name, "foo");
+        assert_eq!(callee.kind, Variable(Name::from("foo")));
 
         let mut args = args.iter();
 
@@ -179,6 +778,31 @@ mod expr {
         }
     }
 
+    #[test]
+    fn tuple_expressions() {
+        use ExprKind::{Field, Tuple, Variable};
+
+        let module = parse("(a, (b, 1)).1.0;");
+        let expr = extract_expr(&module);
+
+        // `.1.0` is lexed as a float
+        let Field(ref base, field) = expr.kind else {
+            panic!("expected field expression");
+        };
+        assert_eq!(field, "0");
+        let Field(ref tuple, field) = base.kind else {
+            panic!("expected field expression");
+        };
+        assert_eq!(field, "1");
+
+        let Tuple(ref elems) = tuple.kind else {
+            panic!("expected tuple expression");
+        };
+        assert_eq!(elems.len(), 2);
+        assert_eq!(elems[0].kind, Variable(Name::from("a")));
+        assert!(matches!(elems[1].kind, Tuple(ref inner) if inner.len() == 2));
+    }
+
     #[test]
     fn conditionals() {
         use crate::ast::Lit::*;
@@ -217,8 +841,8 @@ mod expr {
             panic!("expected expr in then branch");
         };
         match expr.kind {
-            Call(name, ref args) => {
-                assert_eq!(name, "positive");
+            Call(ref callee, ref args) => {
+                assert_eq!(callee.kind, Variable(Name::from("positive")));
                 assert!(args.is_empty());
             }
             _ => panic!("expected call expr"),
@@ -229,10 +853,10 @@ mod expr {
             panic!("expected expr in else block");
         };
 
-        let Call(name, ref args) = expr.kind else {
+        let Call(ref callee, ref args) = expr.kind else {
             panic!("expected call expr");
         };
-        assert_eq!(name, "negative");
+        assert_eq!(callee.kind, Variable(Name::from("negative")));
         assert!(args.is_empty());
     }
 }
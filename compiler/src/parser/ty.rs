@@ -1,35 +1,160 @@
 use super::Parser;
-use crate::ast::ty::IntSize;
+use crate::ast::ty::{self, FloatSize, IntSize};
 use crate::ast::Type;
+use crate::generate_error_message;
+use crate::token::TokenKind::*;
 // use crate::interner::sym;
 use crate::name::*;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 
 impl Parser {
-    /// type_annotation -> `:` ident
+    /// type_annotation -> `:` type
+    /// type -> IDENTIFIER ( '<' type ( ',' type )* '>' )? | '[' type ( ';' expression )? ']' | '*' type
+    ///       | "func" '(' ( type ( ',' type )* )? ')' type_annotation? | '(' type ( ',' type )+ ')'
     pub(super) fn type_annotation(&mut self) -> Result<Type> {
+        if self.matches(&[OpenBracket]) {
+            return self.array_type();
+        }
+
+        if self.matches(&[OpenParen]) {
+            return self.tuple_type();
+        }
+
+        if self.matches(&[Func]) {
+            return self.func_type();
+        }
+
+        if self.matches(&[Star]) {
+            return Ok(Type::Ptr(ty::intern(self.type_annotation()?)));
+        }
+
         let type_name = self.expect_ident("expected a type name")?;
 
-        if type_name == name("bool") {
-            return Ok(Type::Bool);
+        // the type arguments of a generic struct (`Pair<int32, bool>`)
+        if self.matches(&[Lt]) {
+            let mut args = Vec::new();
+            loop {
+                args.push(self.type_annotation()?);
+                if !self.matches(&[Comma]) {
+                    break;
+                }
+            }
+            self.expect(Gt, "expected '>' after type arguments")?;
+            return Ok(Type::Generic(type_name, ty::intern_list(args)));
+        }
+
+        if let Some(ty) = builtin_type(type_name) {
+            return Ok(ty);
+        }
+
+        // aliases and newtypes are resolved after the module is parsed, the names of structs, enums
+        // and type parameters by the type checker
+        Ok(Type::Name(type_name))
+    }
+
+    /// Parses a function type (`func(int32): bool`) after the "func"
+    fn func_type(&mut self) -> Result<Type> {
+        self.expect(OpenParen, "expected '(' after 'func'")?;
+        let mut params = Vec::new();
+        if !self.check(CloseParen) {
+            loop {
+                params.push(self.type_annotation()?);
+                if !self.matches(&[Comma]) {
+                    break;
+                }
+            }
         }
+        self.expect(CloseParen, "expected ')' after parameter types")?;
 
-        let (size, signed) = match type_name {
-            t if t == name("uint8") => (8, false),
-            t if t == name("uint16") => (16, false),
-            t if t == name("uint32") => (32, false),
-            t if t == name("uint64") => (64, false),
-            t if t == name("int8") => (8, true),
-            t if t == name("int16") => (16, true),
-            t if t == name("int32") => (32, true),
-            t if t == name("int64") => (64, true),
-            _ => return Ok(Type::Name(type_name)),
+        let ret = if self.matches(&[Colon]) {
+            self.type_annotation()?
+        } else {
+            Type::Void
         };
+        Ok(Type::Func(ty::intern_list(params), ty::intern(ret)))
+    }
+
+    /// Parses a tuple type (`(int32, bool)`) after the '('
+    fn tuple_type(&mut self) -> Result<Type> {
+        let mut elems = vec![self.type_annotation()?];
+        self.expect(
+            Comma,
+            "expected ',' in tuple type, a tuple has at least two elements",
+        )?;
+        loop {
+            elems.push(self.type_annotation()?);
+            if !self.matches(&[Comma]) {
+                break;
+            }
+        }
+        self.expect(CloseParen, "expected ')' after the tuple's element types")?;
+        Ok(Type::Tuple(ty::intern_list(elems)))
+    }
 
-        Ok(Type::Int {
-            signed,
-            size: IntSize::new(size),
+    /// Parses an array type (`[int32; 4]`) or a slice type (`[int32]`) after the '['.
+    /// The length can be any constant integer expression that uses the module-level constants
+    /// declared before it (`[int32; N * 2]`)
+    fn array_type(&mut self) -> Result<Type> {
+        let elem = ty::intern(self.type_annotation()?);
+
+        if self.matches(&[Semicolon]) {
+            let len = self.array_len()?;
+            self.expect(CloseBracket, "expected ']' after the array's length")?;
+            return Ok(Type::Array(elem, len));
+        }
+
+        self.expect(CloseBracket, "expected ']' after the slice's element type")?;
+        Ok(Type::Slice(elem))
+    }
+
+    fn array_len(&mut self) -> Result<u64> {
+        if self.check(CloseBracket) {
+            bail!(
+                "{}",
+                self.generate_error_message("expected the length of the array")
+            );
+        }
+        let len = self.expression()?;
+        self.consts.eval_len(&len).map_err(|errors| {
+            let messages: Vec<_> = errors
+                .iter()
+                .map(|err| generate_error_message(&err.kind.to_string(), &self.source, err.region))
+                .collect();
+            anyhow!("{}", messages.join("\n"))
         })
     }
 }
+
+/// The type with the name, if it's one of the types that are always there
+pub(crate) fn builtin_type(type_name: Name) -> Option<Type> {
+    if type_name == name("bool") {
+        return Some(Type::Bool);
+    }
+
+    if type_name == name("char") {
+        return Some(Type::Char);
+    }
+
+    if type_name == name("str") {
+        return Some(Type::Str);
+    }
+
+    if let Some(bits) = type_name.strip_prefix("float") {
+        return bits.parse().ok().and_then(FloatSize::new).map(Type::Float);
+    }
+
+    // integer types are named `int8` to `int64` and `uint8` to `uint64`
+    let (signed, bits) = if let Some(bits) = type_name.strip_prefix("uint") {
+        (false, bits)
+    } else if let Some(bits) = type_name.strip_prefix("int") {
+        (true, bits)
+    } else {
+        return None;
+    };
+
+    bits.parse()
+        .ok()
+        .and_then(IntSize::new)
+        .map(|size| Type::Int { signed, size })
+}
@@ -2,36 +2,109 @@ use crate::ast::BinOp;
 use crate::ast::Block;
 use crate::ast::Lit;
 use crate::ast::Region;
+use crate::ast::Type;
 use crate::ast::UnOp;
 use std::collections::HashMap;
 
-use crate::ast::stmt::{FuncProto, Stmt};
-use crate::ast::visit::{walk_expr, walk_stmt, StmtVisitor};
+use crate::ast::stmt::{EnumDef, FuncProto, Param, Stmt, StmtKind, StructDef};
+use crate::ast::visit::{walk_expr, walk_stmt};
+use crate::ast::Arm;
 use crate::ast::Expr;
-use crate::ast::ExprVisitor;
+use crate::ast::Pattern;
+use crate::ast::{ExprVisitor, StmtVisitor};
 use crate::name::Name as Symbol;
 
+use std::fmt;
+use ScopeErrorKind::*;
+
 type Scope = HashMap<Symbol, bool>;
 
+#[derive(Debug)]
+pub struct ScopeError {
+    pub kind: ScopeErrorKind,
+    pub region: Region,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScopeErrorKind {
+    /// a name that isn't declared in any scope around its use
+    Undefined(Symbol),
+    /// a name that is declared twice in the same scope
+    AlreadyDeclared(Symbol),
+    /// a variable or constant that is used in its own initializer
+    UsedInOwnInit(Symbol),
+}
+
+impl fmt::Display for ScopeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Undefined(name) => write!(f, "`{name}` is not defined"),
+            AlreadyDeclared(name) => write!(f, "`{name}` already exists in this scope"),
+            UsedInOwnInit(name) => write!(f, "`{name}` can't be used in its own initializer"),
+        }
+    }
+}
+
 pub struct ScopeChecker {
     /// a stack of accesible scopes
     /// each scope is a hashmap where the keys are the symbol names and the values store whether or
     /// not they are initialized
     scopes: Vec<Scope>,
+    errors: Vec<ScopeError>,
 }
 
 // FIXME: This current implementation has a lot of memory overhead
-// FIXME: Return errors instead of just printing them
 impl ScopeChecker {
     pub fn new() -> ScopeChecker {
         Self {
             // init the global scope
             scopes: vec![Scope::new()], // sym_tbl: SymbolTable::new(),
+            errors: Vec::new(),
         }
     }
 
+    pub fn errors(&self) -> &[ScopeError] {
+        &self.errors
+    }
+
     pub fn check(&mut self, module: &[Box<Stmt>]) {
-        for stmt in module {
+        // globals can be used in functions declared before them, but the initializer of a global
+        // only sees the globals declared before it, and so do the statements at the top of the
+        // module, which run in order with the initializers
+        let (globals, rest): (Vec<_>, Vec<_>) = module.iter().partition(|stmt| {
+            matches!(
+                stmt.kind,
+                StmtKind::Var(..)
+                    | StmtKind::Destructure(..)
+                    | StmtKind::Const(..)
+                    | StmtKind::Expr(..)
+                    | StmtKind::Block(..)
+            )
+        });
+
+        // functions can be used anywhere in the module, by globals and by functions declared before
+        // them (`even` and `odd` can call each other)
+        let methods = module.iter().flat_map(|stmt| match &stmt.kind {
+            StmtKind::Impl(_, methods) => methods.as_slice(),
+            _ => &[],
+        });
+        for stmt in module.iter().chain(methods) {
+            if let StmtKind::Func(proto, _) | StmtKind::Extern(proto) = &stmt.kind {
+                self.declare(proto.name, proto.region);
+                self.define(proto.name);
+            }
+        }
+        for stmt in globals {
+            walk_stmt(self, stmt)
+        }
+
+        for stmt in rest {
+            walk_stmt(self, stmt)
+        }
+    }
+
+    fn check_block(&mut self, block: &[Box<Stmt>]) {
+        for stmt in block {
             walk_stmt(self, stmt)
         }
     }
@@ -48,9 +121,13 @@ impl ScopeChecker {
         self.scopes.pop();
     }
 
-    fn declare(&mut self, name: Symbol) {
+    fn scope_error(&mut self, kind: ScopeErrorKind, region: Region) {
+        self.errors.push(ScopeError { kind, region });
+    }
+
+    fn declare(&mut self, name: Symbol, region: Region) {
         if self.current_scope().contains_key(&name) {
-            eprintln!("variable `{}` already exists in this scope", name);
+            self.scope_error(AlreadyDeclared(name), region);
         } else {
             self.current_scope_mut().insert(name, false);
         }
@@ -62,15 +139,15 @@ impl ScopeChecker {
 
     fn current_scope(&self) -> &Scope {
         // NOTE: this unwrap is fine because there should always be a global scope
-        return self.scopes.first().unwrap();
+        self.scopes.last().unwrap()
     }
 
     fn current_scope_mut(&mut self) -> &mut Scope {
         // NOTE: this unwrap is fine because there should always be a global scope
-        return self.scopes.first_mut().unwrap();
+        self.scopes.last_mut().unwrap()
     }
 
-    fn check_local(&mut self, sym: Symbol) {
+    fn check_local(&mut self, sym: Symbol, region: Region) {
         // go thru every scope, starting from the innermost
         for scope in self.scopes.iter().rev() {
             if scope.contains_key(&sym) {
@@ -78,7 +155,16 @@ impl ScopeChecker {
             }
         }
 
-        eprintln!("'{}' is not defined", sym)
+        self.scope_error(Undefined(sym), region);
+    }
+
+    /// Declares a function that is nested in a block, the functions of the module are declared
+    /// before anything in it is checked
+    fn declare_func(&mut self, proto: &FuncProto) {
+        if self.scopes.len() > 1 {
+            self.declare(proto.name, proto.region);
+            self.define(proto.name);
+        }
     }
 
     fn check_function(&mut self, proto: &FuncProto, body: &[Box<Stmt>]) {
@@ -86,11 +172,11 @@ impl ScopeChecker {
 
         // bind each parameter to a local variable in the function
         for param in &proto.params {
-            self.declare(param.name);
+            self.declare(param.name, proto.region);
             self.define(param.name);
         }
 
-        self.check(body);
+        self.check_block(body);
 
         self.end_scope();
     }
@@ -101,38 +187,64 @@ impl StmtVisitor<()> for ScopeChecker {
         walk_expr(self, expr)
     }
 
-    fn visit_ret_stmt(&mut self, value: &Expr, _: Region) {
-        self.check_expr(value);
+    fn visit_ret_stmt(&mut self, value: Option<&Expr>, _: Region) {
+        if let Some(value) = value {
+            self.check_expr(value);
+        }
     }
 
-    fn visit_var_stmt(&mut self, name: Symbol, init: &Expr, _: Region) {
-        self.declare(name);
+    fn visit_var_stmt(&mut self, name: Symbol, init: &Expr, region: Region) {
+        self.declare(name, region);
         // check the initializer before we define the variable
         self.check_expr(init);
         self.define(name);
     }
 
-    fn visit_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>], _: Region) {
-        self.declare(proto.name);
-        self.define(proto.name);
+    fn visit_destructure_stmt(&mut self, pattern: &Pattern, init: &Expr, region: Region) {
+        pattern.for_each_binding(&mut |name| self.declare(name, region));
+        self.check_expr(init);
+        pattern.for_each_binding(&mut |name| self.define(name));
+    }
+
+    fn visit_const(&mut self, name: Symbol, _: Type, value: &Expr, region: Region) {
+        self.declare(name, region);
+        self.check_expr(value);
+        self.define(name);
+    }
 
+    fn visit_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>], _: Region) {
+        self.declare_func(proto);
         self.check_function(proto, body);
     }
 
     fn visit_extern(&mut self, proto: &FuncProto, _: Region) {
         // extern just declares this function
         // its the linkers job to actually go and find this function
-        self.declare(proto.name);
-        self.define(proto.name);
+        self.declare_func(proto);
     }
 
     fn visit_impt(&mut self, _: Symbol, _: Region) {
         todo!()
     }
 
+    fn visit_error_stmt(&mut self, _: Region) {}
+
+    // struct names live in their own namespace, the type checker makes sure they exist
+    fn visit_struct(&mut self, _: &StructDef, _: Region) {}
+
+    // and so do enum names
+    fn visit_enum(&mut self, _: &EnumDef, _: Region) {}
+
+    // and the names of aliases and newtypes, which the parser has already replaced
+    fn visit_type(&mut self, _: Symbol, _: Type, _: Region) {}
+
+    fn visit_impl(&mut self, _: Symbol, methods: &[Box<Stmt>], _: Region) {
+        self.check_block(methods);
+    }
+
     fn visit_block(&mut self, stmts: &[Box<Stmt>]) {
         self.start_scope();
-        self.check(stmts);
+        self.check_block(stmts);
         self.end_scope();
     }
 }
@@ -140,13 +252,13 @@ impl StmtVisitor<()> for ScopeChecker {
 impl ExprVisitor<()> for ScopeChecker {
     fn visit_lit_expr(&mut self, _: Lit, _: Region) {}
 
-    fn visit_variable_expr(&mut self, var: Symbol, _: Region) {
-        let initialized = self.current_scope().get(&var).unwrap_or(&true);
+    fn visit_variable_expr(&mut self, var: Symbol, region: Region) {
+        let initialized = *self.current_scope().get(&var).unwrap_or(&true);
         if !initialized {
-            eprintln!("cannot reference variable in its own initializer");
+            self.scope_error(UsedInOwnInit(var), region);
         }
 
-        self.check_local(var);
+        self.check_local(var, region);
     }
 
     fn visit_unary_expr(&mut self, _: UnOp, rhs: &Expr, _: Region) {
@@ -158,8 +270,23 @@ impl ExprVisitor<()> for ScopeChecker {
         self.check_expr(rhs);
     }
 
-    fn visit_call_expr(&mut self, func_name: Symbol, args: &[Box<Expr>], _: Region) {
-        self.check_local(func_name);
+    fn visit_call_expr(&mut self, callee: &Expr, args: &[Box<Expr>], _: Region) {
+        self.check_expr(callee);
+
+        for arg in args {
+            self.check_expr(arg);
+        }
+    }
+
+    // methods are looked up in the receiver's type by the type checker
+    fn visit_method_call_expr(
+        &mut self,
+        receiver: &Expr,
+        _: Symbol,
+        args: &[Box<Expr>],
+        _: Region,
+    ) {
+        self.check_expr(receiver);
 
         for arg in args {
             self.check_expr(arg);
@@ -174,17 +301,83 @@ impl ExprVisitor<()> for ScopeChecker {
         _: Region,
     ) {
         self.check_expr(condition);
-        self.check(then_block);
+        self.check_block(then_block);
 
         if let Some(else_block) = else_block {
-            self.check(else_block);
+            self.check_block(else_block);
         }
     }
 
-    fn visit_assign_expr(&mut self, var_name: Symbol, value: &Expr, _: Region) {
+    fn visit_assign_expr(&mut self, target: &Expr, value: &Expr, _: Region) {
         // scope check the value
         self.check_expr(value);
-        // check that this variable exists
-        self.check_local(var_name);
+        // check that the variable being assigned to exists
+        self.check_expr(target);
+    }
+
+    fn visit_cast_expr(&mut self, value: &Expr, _: Type, _: Region) {
+        self.check_expr(value);
+    }
+
+    fn visit_struct_lit_expr(&mut self, _: Symbol, fields: &[(Symbol, Box<Expr>)], _: Region) {
+        for (_, value) in fields {
+            self.check_expr(value);
+        }
+    }
+
+    fn visit_field_expr(&mut self, base: &Expr, _: Symbol, _: Region) {
+        self.check_expr(base);
+    }
+
+    fn visit_tuple_expr(&mut self, elems: &[Box<Expr>], _: Region) {
+        for elem in elems {
+            self.check_expr(elem);
+        }
     }
+
+    fn visit_array_expr(&mut self, elems: &[Box<Expr>], _: Region) {
+        for elem in elems {
+            self.check_expr(elem);
+        }
+    }
+
+    fn visit_index_expr(&mut self, base: &Expr, index: &Expr, _: Region) {
+        self.check_expr(base);
+        self.check_expr(index);
+    }
+
+    fn visit_variant_expr(&mut self, _: Symbol, _: Symbol, args: &[Box<Expr>], _: Region) {
+        for arg in args {
+            self.check_expr(arg);
+        }
+    }
+
+    fn visit_match_expr(&mut self, scrutinee: &Expr, arms: &[Arm], _: Region) {
+        self.check_expr(scrutinee);
+
+        // the variables an arm's pattern binds are only in scope in the arm's body
+        for arm in arms {
+            self.start_scope();
+            arm.pattern.for_each_binding(&mut |name| {
+                self.declare(name, arm.region);
+                self.define(name);
+            });
+            self.check_block(&arm.body);
+            self.end_scope();
+        }
+    }
+
+    // a lambda can use the variables of the scopes around it, the type checker works out which
+    // ones it captures
+    fn visit_lambda_expr(&mut self, params: &[Param], _: Type, body: &Block, region: Region) {
+        self.start_scope();
+        for param in params {
+            self.declare(param.name, region);
+            self.define(param.name);
+        }
+        self.check_block(body);
+        self.end_scope();
+    }
+
+    fn visit_error_expr(&mut self, _: Region) {}
 }
@@ -22,9 +22,13 @@ impl Source {
         }
     }
 
-    /// Retrieves the character at the provided index
+    /// Retrieves the character that starts at the byte offset `idx`, like the offsets in a
+    /// [`Region`]. Returns `'\0'` past the end or in the middle of a multi-byte character
     pub fn char_at(&self, idx: usize) -> char {
-        self.contents.chars().nth(idx).unwrap_or('\0')
+        self.contents
+            .get(idx..)
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or('\0')
     }
 
     pub fn lines(&self) -> Vec<&str> {
@@ -35,42 +39,36 @@ impl Source {
         &self.contents[region.start()..region.end()]
     }
 
-    /// Retreives the length in `char`s of the source's contents.
+    /// Retreives the length in bytes of the source's contents.
     pub fn len(&self) -> usize {
-        self.contents.chars().count()
+        self.contents.len()
     }
 
-    // this is a crappy way of trying to get the surrounding source code of a "Region" of code
+    /// The number of columns from the start of the line to the byte offset `idx`, every
+    /// character takes up one column however many bytes it is encoded in
+    pub fn column(&self, idx: usize) -> usize {
+        self.contents[self.line_start(idx)..idx].chars().count()
+    }
+
+    /// The byte offset of the start of the line that the byte offset `idx` is on
+    fn line_start(&self, idx: usize) -> usize {
+        self.contents[..idx]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1)
+    }
+
+    /// Finds the lines around a region: the lines it's on, and also the line before it if it
+    /// starts a line
     pub fn context_of(&self, region: Region) -> Context {
-        let mut context_start = region.start();
-        let mut context_end = region.end();
-
-        if self.char_at(context_start - 1) == '\n' {
-            context_start -= 2;
-
-            for i in (0..context_start).rev() {
-                if self.char_at(i).is_ascii() && (i == 0 || self.char_at(i - 1) == '\n') {
-                    context_start = i;
-                    break;
-                }
-            }
-        } else {
-            // find the first newline before the region
-            for i in (0..region.start()).rev() {
-                if self.char_at(i) == '\n' {
-                    context_start = i + 1;
-                    break;
-                }
-            }
+        let mut context_start = self.line_start(region.start());
+        if context_start == region.start() && context_start > 0 {
+            context_start = self.line_start(context_start - 1);
         }
 
-        // find the first newline after the region
-        for idx in region.end()..self.len() {
-            if self.char_at(idx) == '\n' {
-                context_end = idx;
-                break;
-            }
-        }
+        // the context ends at the first newline after the region
+        let context_end = self.contents[region.end()..]
+            .find('\n')
+            .map_or(self.len(), |newline| region.end() + newline);
 
         let line_number = self.contents[0..context_end].lines().count();
 
@@ -81,3 +79,23 @@ impl Source {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_offsets() {
+        let source = Source::new("var é = 'ü';\nf(é);", "<string literal>");
+        assert_eq!(source.char_at(4), 'é');
+        // the second byte of `é`
+        assert_eq!(source.char_at(5), '\0');
+        assert_eq!(source.char_at(10), 'ü');
+        assert_eq!(source.column(10), 9);
+        assert_eq!(source.column(17), 2);
+
+        let context = source.context_of(Region::new(17, 19));
+        assert_eq!(source.slice(context.region), "f(é);");
+        assert_eq!(context.line, 2);
+    }
+}
@@ -46,6 +46,10 @@ impl Token {
         match self.kind {
             Bang => Some(UnOp::Not),
             Minus => Some(UnOp::Neg),
+            Ampersand => Some(UnOp::Ref),
+            Star => Some(UnOp::Deref),
+            Move => Some(UnOp::Move),
+            TokenKind::Clone => Some(UnOp::Clone),
             _ => None,
         }
     }
@@ -68,7 +72,17 @@ impl fmt::Display for Token {
             Func => "func".fmt(f),
             Extern => "extern".fmt(f),
             Var => "var".fmt(f),
+            Const => "const".fmt(f),
+            Impl => "impl".fmt(f),
             Ret => "return".fmt(f),
+            As => "as".fmt(f),
+            Struct => "struct".fmt(f),
+            Enum => "enum".fmt(f),
+            Type => "type".fmt(f),
+            Newtype => "newtype".fmt(f),
+            Match => "match".fmt(f),
+            Move => "move".fmt(f),
+            TokenKind::Clone => "clone".fmt(f),
             If => "if".fmt(f),
             Else => "else".fmt(f),
             While => "while".fmt(f),
@@ -80,7 +94,11 @@ impl fmt::Display for Token {
             CloseParen => '('.fmt(f),
             OpenBrace => '{'.fmt(f),
             CloseBrace => '}'.fmt(f),
+            OpenBracket => '['.fmt(f),
+            CloseBracket => ']'.fmt(f),
             Colon => ':'.fmt(f),
+            ColonColon => "::".fmt(f),
+            FatArrow => "=>".fmt(f),
             Comma => ','.fmt(f),
             Semicolon => ';'.fmt(f),
             Plus => '+'.fmt(f),
@@ -88,6 +106,7 @@ impl fmt::Display for Token {
             Star => '*'.fmt(f),
             Slash => '/'.fmt(f),
             Dot => '.'.fmt(f),
+            Ellipsis => "...".fmt(f),
             Percent => "%".fmt(f),
             Gt => '>'.fmt(f),
             Ge => ">=".fmt(f),
@@ -99,6 +118,7 @@ impl fmt::Display for Token {
             Bar => '|'.fmt(f),
             DoubleBar => "||".fmt(f),
             Eof => "end of file".fmt(f),
+            Error => "invalid token".fmt(f),
             DoubleEqual => "==".fmt(f),
             Bang => '!'.fmt(f),
             BangEqual => "!=".fmt(f),
@@ -106,16 +126,26 @@ impl fmt::Display for Token {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
     // key / reserved words
     Func,
     Extern,
     Var,
+    Const,
+    Impl,
     If,
     Else,
     While,
     Ret,
+    As,
+    Struct,
+    Enum,
+    Type,
+    Newtype,
+    Match,
+    Move,
+    Clone,
     Impt,
     Expt,
 
@@ -130,10 +160,18 @@ pub enum TokenKind {
     OpenBrace,
     /// `}`
     CloseBrace,
+    /// `[`
+    OpenBracket,
+    /// `]`
+    CloseBracket,
     /// `,`
     Comma,
     /// `:`
     Colon,
+    /// `::`
+    ColonColon,
+    /// `=>`
+    FatArrow,
     /// `;`
     Semicolon,
 
@@ -162,6 +200,8 @@ pub enum TokenKind {
     Le,
     /// '.'
     Dot,
+    /// `...`
+    Ellipsis,
     /// `&`
     Ampersand,
     /// `&&`
@@ -175,6 +215,6 @@ pub enum TokenKind {
     /// `!=`
     BangEqual,
     Eof,
-    // TODO: add an error token type
-    // Err(LexError)
+    /// characters the lexer couldn't make a token of, it already reported them
+    Error,
 }
@@ -1,293 +1,3783 @@
 //! Type checking
-use crate::ast::visit::{walk_expr, Visitor as AstVisitor};
-use crate::ast::*;
-use crate::source::Source;
-use crate::symbol::{Symbol, SymbolTable};
-use log::error;
-use std::{fmt, io};
+use crate::ast::stmt::{method_name, EnumDef, FuncProto, Param, Stmt, StructDef};
+use crate::ast::ty::{self, int_lit_fits, FloatSize};
+use crate::ast::visit::{walk_expr, walk_stmt};
+use crate::ast::{
+    Arm, BinOp, Block, Expr, ExprKind, ExprVisitor, Lit, Pattern, PatternKind, Region, StmtKind,
+    StmtVisitor, Type, UnOp,
+};
+use crate::consteval::{self, EvalError, Value};
+use crate::exhaustive;
+use crate::name::{nm::SELF, Name};
+use crate::parser::ty::builtin_type;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
 use TypeErrorKind::*;
 
+/// Instantiating generic functions stops after this many instances. A generic function that calls
+/// itself with a bigger type every time (`f<[T; 1]>` inside `f<T>`) would have infinitely many
+const MAX_INSTANCES: usize = 1000;
+
+/// What the type checker found out about the code outside of generic functions, or about the body
+/// of one instance of a generic function
+#[derive(Debug, Default)]
+pub struct TypeTable {
+    /// the type of every expression, keyed by the expression's region
+    pub types: HashMap<Region, Type>,
+    /// the value of every use of a constant, keyed by the use's region
+    pub consts: HashMap<Region, Value>,
+    /// every use of a module-level variable, which lives in the data section instead of the
+    /// function using it
+    pub globals: HashSet<Region>,
+    /// every use of the name of a function, as the callee of a call or as a value
+    pub funcs: HashSet<Region>,
+    /// the local variables that each lambda captures, keyed by the lambda's region. Each one is
+    /// paired with the region of a use of it in the lambda, which has its type
+    pub captures: HashMap<Region, Vec<(Name, Region)>>,
+    /// the instance that each call to a generic function calls, keyed by the call's region
+    pub callees: HashMap<Region, Name>,
+    /// every instance of a generic function that is called somewhere, in the order they were
+    /// found. Only the table of the whole module has instances
+    pub instances: Vec<Instance>,
+}
+
+/// A generic function with its type parameters replaced by type arguments
 #[derive(Debug)]
-pub struct TypeError<'a> {
-    kind: TypeErrorKind,
-    source: &'a Source,
-    region: Region,
+pub struct Instance {
+    /// the instance's mangled name, see [`mangle`]
+    pub name: Name,
+    /// the name of the generic function
+    pub func: Name,
+    pub args: Vec<Type>,
+    /// the types in the instance's body
+    pub table: TypeTable,
 }
 
-impl<'a> TypeError<'a> {
-    pub fn display(&self) -> io::Result<()> {
-        error!(target: "type_checker", "{}: {}", self.source.name, self.kind);
+/// The name of an instance of a generic function, `max$uint8` for `max<uint8>`.
+/// `$` can't be part of an identifier, so instances can't clash with other functions
+pub fn mangle(func: Name, args: &[Type]) -> Name {
+    fn mangle_type(ty: Type, out: &mut String) {
+        match ty {
+            Type::Array(elem, len) => {
+                let _ = write!(out, "arr{len}$");
+                mangle_type(*elem, out);
+            }
+            Type::Slice(elem) => {
+                out.push_str("slice$");
+                mangle_type(*elem, out);
+            }
+            Type::Ptr(pointee) => {
+                out.push_str("ptr$");
+                mangle_type(*pointee, out);
+            }
+            Type::Tuple(elems) => {
+                let _ = write!(out, "tup{}", elems.len());
+                for elem in elems {
+                    out.push('$');
+                    mangle_type(*elem, out);
+                }
+            }
+            Type::Func(params, ret) => {
+                let _ = write!(out, "func{}", params.len());
+                for param in params {
+                    out.push('$');
+                    mangle_type(*param, out);
+                }
+                out.push('$');
+                mangle_type(*ret, out);
+            }
+            // every generic struct has a fixed number of type arguments, so they don't need to be
+            // delimited
+            Type::Generic(name, args) => {
+                out.push_str(&name);
+                for arg in args {
+                    out.push('$');
+                    mangle_type(*arg, out);
+                }
+            }
+            _ => {
+                let _ = write!(out, "{ty}");
+            }
+        }
+    }
+
+    let mut mangled = func.to_string();
+    for arg in args {
+        mangled.push('$');
+        mangle_type(*arg, &mut mangled);
+    }
+    Name::from(mangled.as_str())
+}
 
-        let string = self
-            .source
-            .contents
-            .chars()
-            .skip(self.region.start)
-            .take(self.region.len)
-            .collect::<String>();
+/// Returns `true` if the type refers to any of the type parameters
+fn mentions(ty: Type, params: &[Name]) -> bool {
+    match ty {
+        Type::Name(name) => params.contains(&name),
+        Type::Array(elem, _) | Type::Slice(elem) | Type::Ptr(elem) => mentions(*elem, params),
+        Type::Generic(_, args) | Type::Tuple(args) => args.iter().any(|arg| mentions(*arg, params)),
+        Type::Func(args, ret) => {
+            args.iter().any(|arg| mentions(*arg, params)) || mentions(*ret, params)
+        }
+        _ => false,
+    }
+}
 
-        eprintln!("here:");
-        eprintln!("{}", string);
-        for _ in 0..self.region.len {
-            eprint!("^");
+/// Finds the types that the type parameters in `param` stand for, by matching it against the type
+/// of the value that was given for it. Any mismatch is reported when the value is checked against
+/// the instantiated type
+fn match_type_args(param: Type, found: Type, params: &[Name], bound: &mut HashMap<Name, Type>) {
+    match (param, found) {
+        (Type::Name(name), _) if params.contains(&name) => {
+            bound.entry(name).or_insert(found);
+        }
+        (Type::Array(param, _), Type::Array(found, _))
+        | (Type::Slice(param), Type::Slice(found) | Type::Array(found, _))
+        | (Type::Ptr(param), Type::Ptr(found)) => match_type_args(*param, *found, params, bound),
+        (Type::Generic(param, param_args), Type::Generic(found, found_args)) if param == found => {
+            for (param, found) in param_args.iter().zip(found_args) {
+                match_type_args(*param, *found, params, bound);
+            }
+        }
+        (Type::Tuple(param_elems), Type::Tuple(found_elems)) => {
+            for (param, found) in param_elems.iter().zip(found_elems) {
+                match_type_args(*param, *found, params, bound);
+            }
+        }
+        (Type::Func(param_args, param_ret), Type::Func(found_args, found_ret)) => {
+            for (param, found) in param_args.iter().zip(found_args) {
+                match_type_args(*param, *found, params, bound);
+            }
+            match_type_args(*param_ret, *found_ret, params, bound);
         }
+        _ => {}
+    }
+}
 
-        eprintln!();
+#[derive(Debug)]
+pub struct TypeError {
+    pub kind: TypeErrorKind,
+    pub region: Region,
+}
 
-        Ok(())
+impl TypeError {
+    /// A message about another place in the code that explains the error, and where that place is
+    pub fn note(&self) -> Option<(String, Region)> {
+        match self.kind {
+            InferredMismatch {
+                found,
+                var,
+                inferred_at,
+                ..
+            } => Some((
+                format!("`{var}` got the type `{found}` from this use"),
+                inferred_at,
+            )),
+            _ => None,
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypeErrorKind {
-    Unknown,
-    UndefinedSymbol,
-    UndefinedFunc,
-    InvalidArgs,
-    CannotInfer,
     /// type mismatch
-    Mismatch,
+    Mismatch {
+        expected: Type,
+        found: Type,
+    },
+    /// a variable whose type was inferred from one use is used as a value of another type
+    InferredMismatch {
+        expected: Type,
+        found: Type,
+        var: Name,
+        inferred_at: Region,
+    },
+    /// an integer literal was used where a value of another type was expected
+    NotAnInt(Type),
+    /// a float literal was used where a value of another type was expected
+    NotAFloat(Type),
+    /// signed and unsigned integers were mixed in an operation
+    MixedSignedness(Type, Type),
+    /// integers of different sizes were mixed in an operation
+    MixedSizes(Type, Type),
+    /// an integer literal doesn't fit in its type
+    OutOfRange {
+        value: u64,
+        negative: bool,
+        ty: Type,
+    },
+    /// a float literal is too large for `float32`
+    FloatOutOfRange(f64),
     /// invalid operation on a type
-    InvalidOp,
-    /// the return type doesn't match the type in the function prototype
-    RetTyMismatch,
+    InvalidOp(BinOp, Type),
+    InvalidUnaryOp(UnOp, Type),
+    InvalidCast {
+        from: Type,
+        to: Type,
+    },
+    /// a constant cast to `char` isn't the code point of a character
+    InvalidCodePoint(i128),
+    /// a tuple pattern matches a value that isn't a tuple
+    NotATuple(Type),
+    /// a tuple pattern has a different number of elements than the tuple it matches
+    TupleLen {
+        expected: usize,
+        found: usize,
+    },
+    /// the pattern of a `var` doesn't match every value
+    RefutablePattern,
+    /// a call has the wrong number of arguments
+    InvalidArgs {
+        expected: usize,
+        found: usize,
+    },
+    /// a call to a variadic function has fewer arguments than the function has parameters
+    TooFewArgs {
+        expected: usize,
+        found: usize,
+    },
+    /// a value that C's variadic functions can't take is passed to the `...` of one
+    InvalidVarArg(Type),
+    /// a function that isn't an extern is declared with `...`
+    VariadicNotExtern(Name),
+    /// a method that the type doesn't have
+    NoMethod {
+        ty: Type,
+        method: Name,
+    },
+    /// a function without a `self` parameter is called like a method
+    NotAMethod(Name),
+    /// a value that isn't a function is called
+    NotCallable(Type),
+    /// a generic function is used as a value, which one of its instances it stands for isn't
+    /// known
+    GenericFuncValue(Name),
+    /// a variadic function is used as a value, function types can't be variadic
+    VariadicFuncValue(Name),
+    /// the `self` parameter of a method in `impl ty` isn't a `ty` or a `*ty`
+    InvalidSelf {
+        ty: Name,
+        found: Type,
+    },
+    /// a void expression was used as a value
+    VoidValue,
+    /// `ret;` in a function that returns a value of this type
+    MissingRetValue(Type),
+    /// an integer was used as a condition
+    IntCondition(Type),
+    /// a name that doesn't refer to any variable, constant or function
+    UndefinedName(Name),
+    /// a type name that doesn't refer to any type
+    UnknownType(Name),
+    /// an `impl` block for a type that isn't a struct or an enum
+    InvalidImpl(Name),
+    /// a newtype wraps a type that isn't a scalar
+    InvalidNewtype(Type),
+    /// a field that the type doesn't have
+    NoField {
+        ty: Type,
+        field: Name,
+    },
+    /// a struct literal doesn't give the field a value
+    MissingField {
+        ty: Type,
+        field: Name,
+    },
+    /// a field is declared or given a value more than once
+    DuplicateField(Name),
+    /// a struct or enum contains itself, so it would have an infinite size
+    RecursiveStruct(Name),
+    /// a variant that the enum doesn't have
+    UnknownVariant {
+        ty: Name,
+        variant: Name,
+    },
+    /// a variant is declared more than once
+    DuplicateVariant(Name),
+    /// a pattern compares with a literal that can't be matched on, like a float or a string
+    InvalidLitPattern(Lit),
+    /// an arm of a match can never run because the arms above it match every value it matches
+    UnreachableArm,
+    /// no arm of a match matches some value, the pattern shows one of them
+    NonExhaustive(String),
+    /// a value that isn't an array or a slice was indexed
+    NotIndexable(Type),
+    /// a constant index is past the end of an array
+    IndexOutOfBounds {
+        index: u64,
+        len: u64,
+    },
+    /// the length of an array type is a negative constant
+    NegativeLen(i128),
+    /// an empty array literal is used where its element type can't be known
+    EmptyArray,
+    /// the length of an array or slice is assigned to
+    LenIsReadOnly,
+    /// the address of a value that isn't stored anywhere is taken
+    NotAPlace,
+    /// a constant is assigned to or its address is taken
+    ConstNotPlace(Name),
+    /// the initializer of a constant can't be evaluated at compile time
+    NotConstant,
+    /// the result of an operation in the initializer of a constant doesn't fit in its type
+    ConstOverflow(Type),
+    /// the initializer of a constant divides by zero
+    ConstDivByZero,
+    /// a generic struct is used with the wrong number of type arguments, or a struct that isn't
+    /// generic is given some
+    WrongTypeArgs {
+        name: Name,
+        expected: usize,
+        found: usize,
+    },
+    /// the type that a type parameter stands for can't be found from the values passed to a
+    /// generic function or struct literal
+    CannotInfer(Name),
+    /// a generic function is instantiated too many times, it probably calls itself with a bigger
+    /// type every time
+    TooManyInstances(Name),
 }
 
 impl fmt::Display for TypeErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Unknown => "unknown type".fmt(f),
-            UndefinedSymbol => "undefined symbol".fmt(f),
-            UndefinedFunc => "undefined function; hint: try defining or importing it".fmt(f),
-            InvalidArgs => "invalid arguments".fmt(f),
-            CannotInfer => "cannot infer type".fmt(f),
-            Mismatch => "type mismatch".fmt(f),
-            InvalidOp => "invalid operation".fmt(f),
-            RetTyMismatch => "the return type mismatches the type in the function prototype".fmt(f),
+            Mismatch { expected, found } => {
+                write!(f, "type mismatch, expected `{expected}` but found `{found}`")
+            }
+            InferredMismatch {
+                expected,
+                found,
+                var,
+                ..
+            } => write!(
+                f,
+                "type mismatch, expected `{expected}` but `{var}` has type `{found}`"
+            ),
+            NotAnInt(expected @ Type::Newtype(..)) | NotAFloat(expected @ Type::Newtype(..)) => {
+                write!(
+                    f,
+                    "expected `{expected}` but found a number; hint: convert it with `as {expected}`"
+                )
+            }
+            NotAnInt(expected) => write!(f, "expected `{expected}` but found an integer"),
+            NotAFloat(expected) => write!(f, "expected `{expected}` but found a float"),
+            MixedSignedness(lhs, rhs) => write!(
+                f,
+                "cannot mix signed and unsigned integers (`{lhs}` and `{rhs}`); hint: convert one of them with `as`"
+            ),
+            MixedSizes(lhs, rhs) => write!(
+                f,
+                "cannot mix integers of different sizes (`{lhs}` and `{rhs}`); hint: convert one of them with `as`"
+            ),
+            OutOfRange {
+                value,
+                negative,
+                ty,
+            } => {
+                let sign = if *negative { "-" } else { "" };
+                write!(f, "the literal `{sign}{value}` doesn't fit in `{ty}`")
+            }
+            FloatOutOfRange(value) => {
+                write!(f, "the literal `{value:?}` doesn't fit in `float32`")
+            }
+            InvalidOp(op, Type::Char) => write!(
+                f,
+                "cannot apply `{op}` to `char`; hint: convert it to an integer with `as`"
+            ),
+            InvalidOp(op, ty @ Type::Newtype(_, inner)) => write!(
+                f,
+                "cannot apply `{op}` to `{ty}`; hint: convert it to `{inner}` with `as`"
+            ),
+            InvalidOp(op, ty) => write!(f, "cannot apply `{op}` to `{ty}`"),
+            InvalidUnaryOp(op, ty) => write!(f, "cannot apply unary `{op}` to `{ty}`"),
+            InvalidCast {
+                from: from @ Type::Int { .. },
+                to: Type::Char,
+            } => write!(
+                f,
+                "cannot cast `{from}` as `char`, not every integer is a character; hint: only `uint8`s and `uint32`s can be cast to `char`s"
+            ),
+            InvalidCast { from, to } => write!(f, "cannot cast `{from}` as `{to}`"),
+            InvalidCodePoint(code) => write!(
+                f,
+                "`{code:#x}` isn't a character; hint: code points go up to `0x10ffff` and skip the surrogates `0xd800` to `0xdfff`"
+            ),
+            NotATuple(ty) => write!(f, "expected a tuple but found `{ty}`"),
+            TupleLen { expected, found } => write!(
+                f,
+                "expected a tuple of {} but the pattern has {found}",
+                plural(*expected, "element")
+            ),
+            RefutablePattern => {
+                "the pattern of a `var` must match every value; hint: use a `match` instead".fmt(f)
+            }
+            InvalidArgs { expected, found } => {
+                write!(f, "expected {} but found {found}", plural(*expected, "argument"))
+            }
+            TooFewArgs { expected, found } => {
+                write!(
+                    f,
+                    "expected at least {} but found {found}",
+                    plural(*expected, "argument")
+                )
+            }
+            InvalidVarArg(ty) => write!(
+                f,
+                "cannot pass a value of type `{ty}` to a variadic function; hint: pass a pointer to it instead"
+            ),
+            VariadicNotExtern(func) => write!(
+                f,
+                "`{func}` can't be variadic, only extern functions can take `...`"
+            ),
+            NoMethod { ty, method } => write!(f, "`{ty}` has no method named `{method}`"),
+            NotAMethod(func) => write!(
+                f,
+                "`{func}` has no `self` parameter; hint: call it as `{func}(...)`"
+            ),
+            NotCallable(ty) => write!(f, "cannot call a value of type `{ty}`"),
+            GenericFuncValue(func) => write!(
+                f,
+                "the generic function `{func}` can only be called, it can't be used as a value"
+            ),
+            VariadicFuncValue(func) => write!(
+                f,
+                "the variadic function `{func}` can only be called, it can't be used as a value"
+            ),
+            InvalidSelf { ty, found } => write!(
+                f,
+                "`self` must have type `{ty}` or `*{ty}` but has type `{found}`"
+            ),
+            VoidValue => "this expression doesn't produce a value".fmt(f),
+            MissingRetValue(ty) => write!(f, "this function must return a value of type `{ty}`"),
+            IntCondition(ty) => write!(
+                f,
+                "expected `bool` but found `{ty}`; hint: compare it with zero (`!= 0`)"
+            ),
+            UndefinedName(name) => write!(f, "`{name}` is not defined"),
+            UnknownType(name)
+                if ["int", "uint", "float"]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix)) =>
+            {
+                write!(
+                    f,
+                    "unknown type `{name}`; hint: the number types are `int8` to `int64`, `uint8` to `uint64`, `float32` and `float64`"
+                )
+            }
+            UnknownType(name) => write!(f, "unknown type `{name}`"),
+            InvalidImpl(name) => write!(
+                f,
+                "cannot declare methods on `{name}`, methods can only be declared on structs and enums"
+            ),
+            InvalidNewtype(ty) => write!(
+                f,
+                "a newtype can't wrap `{ty}`, only numbers, `bool`, `char`, `str` and pointers"
+            ),
+            NoField { ty, field } => write!(f, "`{ty}` has no field named `{field}`"),
+            MissingField { ty, field } => write!(f, "missing field `{field}` in `{ty}`"),
+            DuplicateField(field) => write!(f, "field `{field}` is given more than once"),
+            RecursiveStruct(name) => write!(
+                f,
+                "`{name}` contains itself, so it would have an infinite size"
+            ),
+            UnknownVariant { ty, variant } => write!(f, "`{ty}` has no variant named `{variant}`"),
+            DuplicateVariant(variant) => {
+                write!(f, "variant `{variant}` is declared more than once")
+            }
+            InvalidLitPattern(lit) => write!(f, "cannot match on the literal `{lit}`"),
+            UnreachableArm => {
+                "unreachable arm, the arms above it already match every value it matches".fmt(f)
+            }
+            NonExhaustive(missing) => write!(
+                f,
+                "the match doesn't handle every value, `{missing}` isn't matched; hint: add an arm with `_`"
+            ),
+            NotIndexable(ty) => write!(f, "cannot index into a value of type `{ty}`"),
+            IndexOutOfBounds { index, len } => write!(
+                f,
+                "index {index} is out of bounds for an array of length {len}"
+            ),
+            NegativeLen(len) => write!(f, "the length of an array can't be negative but is {len}"),
+            EmptyArray => "cannot infer the element type of an empty array".fmt(f),
+            LenIsReadOnly => "cannot assign to the length of an array or slice".fmt(f),
+            NotAPlace => {
+                "can only take the address of a variable, a field, an element or a dereference"
+                    .fmt(f)
+            }
+            ConstNotPlace(name) => write!(
+                f,
+                "`{name}` is a constant, it can't be assigned to and has no address"
+            ),
+            NotConstant => write!(
+                f,
+                "this can't be evaluated at compile time; hint: constants can only use literals, other constants, operators and casts"
+            ),
+            ConstOverflow(ty) => write!(f, "this overflows `{ty}` at compile time"),
+            ConstDivByZero => "this divides by zero at compile time".fmt(f),
+            WrongTypeArgs {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{name}` takes {} but was given {found}",
+                plural(*expected, "type argument")
+            ),
+            CannotInfer(param) => write!(
+                f,
+                "cannot infer the type of `{param}`; hint: use it in the type of a parameter or field"
+            ),
+            TooManyInstances(func) => write!(
+                f,
+                "`{func}` is instantiated with too many different types; does it call itself with a bigger type every time?"
+            ),
+        }
+    }
+}
+
+/// `count` followed by `noun`, which is plural unless `count` is one
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+/// Finds the declaration of a function, methods are in `impl` blocks
+fn find_func(module: &[Box<Stmt>], func: Name) -> Option<(&FuncProto, &Block)> {
+    module.iter().find_map(|stmt| match &stmt.kind {
+        StmtKind::Func(proto, body) if proto.name == func => Some((proto, body)),
+        StmtKind::Impl(_, methods) => find_func(methods, func),
+        _ => None,
+    })
+}
+
+/// The type of an expression while it's being checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ty {
+    Known(Type),
+    /// integer literals (and arithmetic on them) take the type of whatever they are used with
+    IntLit,
+    /// float literals are like integer literals but can only become floats
+    FloatLit,
+    /// the expression has an error that was already reported
+    Error,
+}
+
+/// A variable in scope
+#[derive(Debug, Clone, Copy)]
+enum Local {
+    Known(Type),
+    /// a variable initialized with a literal, which takes its type from how it's used. Holds the
+    /// index of the variable in [`TypeChecker::vars`]
+    Infer(usize),
+    /// a constant and its value, which is `None` if its initializer has an error
+    Const(Type, Option<Value>),
+}
+
+/// A variable initialized with a literal (`var x = 1;`). Variables that are used together
+/// (`var y = x;`, `x < y`) are put in the same group and get the same type, the first use that
+/// decides the type of one of them decides it for the whole group. A group that no use decides
+/// gets the default type when its variables go out of scope
+#[derive(Debug)]
+struct InferVar {
+    name: Name,
+    /// the variable's initializer
+    region: Region,
+    /// the number of scopes when the variable was declared
+    depth: usize,
+    /// the variable in the same group that stands for the group, the group's type and
+    /// expressions are kept there
+    parent: usize,
+    /// [`Ty::IntLit`] or [`Ty::FloatLit`]
+    kind: Ty,
+    /// the group's type once it's known, and the use that decided it
+    ty: Option<(Type, Region)>,
+    /// the literal expressions that get the group's type once it's known
+    pending: Vec<LitTree>,
+}
+
+/// An expression whose type is a literal type, with only the parts that get its type once it's
+/// known. It doesn't borrow the AST, so it can wait for the type of a variable in it
+#[derive(Debug)]
+enum LitTree {
+    Int {
+        value: u64,
+        region: Region,
+    },
+    Float {
+        value: f64,
+        region: Region,
+    },
+    Neg {
+        rhs: Box<LitTree>,
+        region: Region,
+    },
+    /// an expression that has the type of its operand, `move x` and `x = 1`
+    Same {
+        value: Box<LitTree>,
+        region: Region,
+    },
+    Binary {
+        lhs: Box<LitTree>,
+        rhs: Box<LitTree>,
+        region: Region,
+    },
+    /// a use of an inferred variable
+    Var {
+        id: usize,
+        name: Name,
+        region: Region,
+    },
+}
+
+impl LitTree {
+    fn region(&self) -> Region {
+        match *self {
+            Self::Int { region, .. }
+            | Self::Float { region, .. }
+            | Self::Neg { region, .. }
+            | Self::Same { region, .. }
+            | Self::Binary { region, .. }
+            | Self::Var { region, .. } => region,
+        }
+    }
+
+    /// Collects the inferred variables used in the expression
+    fn vars(&self, out: &mut Vec<usize>) {
+        match self {
+            Self::Int { .. } | Self::Float { .. } => {}
+            Self::Neg { rhs: value, .. } | Self::Same { value, .. } => value.vars(out),
+            Self::Binary { lhs, rhs, .. } => {
+                lhs.vars(out);
+                rhs.vars(out);
+            }
+            Self::Var { id, .. } => out.push(*id),
         }
     }
 }
 
-// type TypeChkResult = Result<(), TypeError>;
+/// A lambda whose body is being checked
+#[derive(Debug)]
+struct LambdaScope {
+    /// the number of scopes outside of the lambda, variables declared in them (except for globals)
+    /// are captured
+    depth: usize,
+    /// the captured variables and the first use of each one
+    captures: Vec<(Name, Region)>,
+}
 
-pub struct TypeChecker<'a, 'b> {
-    // input: &'a Source,
-    sym_tbl: &'b SymbolTable,
-    local_scope: &'b SymbolTable,
-    errors: Vec<TypeError<'a>>,
+pub struct TypeChecker {
+    scopes: Vec<HashMap<Name, Local>>,
+    /// the lambdas around the expression being checked, innermost last
+    lambdas: Vec<LambdaScope>,
+    /// the variables whose types are inferred, see [`InferVar`]
+    vars: Vec<InferVar>,
+    funcs: HashMap<Name, FuncProto>,
+    structs: HashMap<Name, StructDef>,
+    enums: HashMap<Name, EnumDef>,
+    /// the names of the aliases and newtypes in the module
+    type_decls: HashSet<Name>,
+    /// the return type of the function being checked
+    ret_ty: Type,
+    /// the type parameters of the function or struct whose declaration is being checked
+    generics: Vec<Name>,
+    /// the types that the type parameters of the instance being checked stand for
+    subst: HashMap<Name, Type>,
+    /// the instances of generic functions that are called, as (mangled name, function, type
+    /// arguments)
+    instances: Vec<(Name, Name, Vec<Type>)>,
+    table: TypeTable,
+    errors: Vec<TypeError>,
 }
 
-impl<'a, 'b> TypeChecker<'a, 'b> {
-    pub fn new(input: &'a Source, sym_tbl: &'b SymbolTable) -> Self {
+impl TypeChecker {
+    pub fn new() -> Self {
         Self {
-            errors: vec![],
-            sym_tbl,
-            local_scope: sym_tbl,
-            input,
+            scopes: vec![HashMap::new()],
+            lambdas: Vec::new(),
+            vars: Vec::new(),
+            funcs: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            type_decls: HashSet::new(),
+            ret_ty: Type::Void,
+            generics: Vec::new(),
+            subst: HashMap::new(),
+            instances: Vec::new(),
+            table: TypeTable::default(),
+            errors: Vec::new(),
         }
     }
 
-    pub fn get_var_type(&self, name: &str) -> Option<&Type> {
-        self.local_scope
-            .get(name)
-            .and_then(|sym| sym.as_var())
-            .or_else(|| self.sym_tbl.get(name).and_then(|sym| sym.as_var()))
+    pub fn check(&mut self, module: &[Box<Stmt>]) {
+        // functions can be called and structs and enums can be used before they are defined
+        for stmt in module {
+            match &stmt.kind {
+                StmtKind::Func(proto, _) | StmtKind::Extern(proto) => {
+                    self.funcs.insert(proto.name, proto.clone());
+                }
+                // methods are functions named after their type
+                StmtKind::Impl(_, methods) => {
+                    for method in methods {
+                        if let StmtKind::Func(proto, _) = &method.kind {
+                            self.funcs.insert(proto.name, proto.clone());
+                        }
+                    }
+                }
+                StmtKind::Struct(def) => {
+                    self.structs.insert(def.name, def.clone());
+                }
+                StmtKind::Enum(def) => {
+                    self.enums.insert(def.name, def.clone());
+                }
+                StmtKind::Type(name, ..) => {
+                    self.type_decls.insert(*name);
+                }
+                _ => {}
+            }
+        }
+
+        // constants are evaluated in the order they are declared, before the globals and the
+        // functions that use them
+        for stmt in module {
+            if let StmtKind::Const(..) = stmt.kind {
+                walk_stmt(self, stmt);
+            }
+        }
+        for stmt in module {
+            if let StmtKind::Var(..) | StmtKind::Destructure(..) = stmt.kind {
+                walk_stmt(self, stmt);
+            }
+        }
+        for stmt in module {
+            if !matches!(
+                stmt.kind,
+                StmtKind::Const(..) | StmtKind::Var(..) | StmtKind::Destructure(..)
+            ) {
+                walk_stmt(self, stmt);
+            }
+        }
+        self.default_vars(0);
+
+        self.check_instances(module);
     }
 
-    pub fn var_exists(&self, name: &str) -> bool {
-        self.local_scope
-            .get(name)
-            .map(|sym| sym.is_var())
-            .unwrap_or_else(|| self.sym_tbl.get(name).map_or(false, |sym| sym.is_var()))
+    /// Checks the body of every instance of a generic function, with the type parameters replaced
+    /// by the instance's type arguments. The bodies can call more instances, which are checked
+    /// too
+    fn check_instances(&mut self, module: &[Box<Stmt>]) {
+        let mut next = 0;
+        while let Some((name, func, args)) = self.instances.get(next).cloned() {
+            next += 1;
+            let Some((proto, body)) = find_func(module, func) else {
+                continue;
+            };
+
+            let outer = std::mem::take(&mut self.table);
+            self.subst = ty::bind(&proto.generics, &args);
+            self.check_body(proto, body);
+            self.subst.clear();
+            let table = std::mem::replace(&mut self.table, outer);
+            self.table.instances.push(Instance {
+                name,
+                func,
+                args,
+                table,
+            });
+        }
     }
 
     pub fn errors(&self) -> &[TypeError] {
         &self.errors
     }
 
+    /// Get the type of every expression that was checked.
+    pub fn into_types(self) -> TypeTable {
+        self.table
+    }
+
+    /// Checks a module-level constant on its own, the parser does this as soon as it parses one
+    /// so that the lengths of the array types after it can use it
+    pub fn check_const(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    /// Evaluates the length of an array type (`[int32; N * 2]`), a constant integer that can use
+    /// the constants given to [`TypeChecker::check_const`]. Returns the errors in it if it isn't
+    /// one
+    pub fn eval_len(&mut self, len: &Expr) -> Result<u64, Vec<TypeError>> {
+        let errors = self.errors.len();
+        match self.infer(len) {
+            Ty::IntLit => self.type_lits(len, Type::UINT64),
+            Ty::Known(Type::Int { .. }) | Ty::Error => {}
+            Ty::Known(found) => self.type_error(
+                Mismatch {
+                    expected: Type::UINT64,
+                    found,
+                },
+                len.region,
+            ),
+            Ty::FloatLit => self.type_error(NotAFloat(Type::UINT64), len.region),
+        }
+
+        let value = if self.errors.len() == errors {
+            self.eval_const(len)
+        } else {
+            None
+        };
+        match value {
+            // integer types have at most 64 bits
+            Some(Value::Int(value)) if value >= 0 => return Ok(value as u64),
+            Some(Value::Int(value)) => self.type_error(NegativeLen(value), len.region),
+            // a constant whose initializer has an error has no value
+            _ if self.errors.len() == errors => self.type_error(NotConstant, len.region),
+            _ => {}
+        }
+        Err(self.errors.split_off(errors))
+    }
+
     fn type_error(&mut self, kind: TypeErrorKind, region: Region) {
-        self.errors.push(TypeError {
-            kind,
-            region,
-            source: self.input,
-        })
-    }
-
-    fn infer_type(&self, e: &Expr) -> Option<Type> {
-        use ExprKind::*;
-        use Lit::*;
-        match &e.kind {
-            Lit(kind) => match kind {
-                Int(_) | Char(_) => Some(Type::Int),
-                Str(_) => Some(Type::Str),
-            },
+        // every instance of a generic function would report the same errors again
+        let reported = !self.subst.is_empty()
+            && self
+                .errors
+                .iter()
+                .any(|err| err.kind == kind && err.region == region);
+        if !reported {
+            self.errors.push(TypeError { kind, region })
+        }
+    }
 
-            Assign(ref name, _) => match self.sym_tbl.get(name) {
-                // TODO: remove this clone?
-                Some(Symbol::Var(ty)) => Some(ty.clone()),
-                _ => None,
-            },
-            Variable(ref name) => self.get_var_type(name).cloned(),
-            Binary(_, ref lhs, ref rhs) => match (self.infer_type(lhs), self.infer_type(rhs)) {
-                (Some(lty), Some(rty)) if lty == rty => Some(lty),
-                _ => None,
-            },
-            Call(ref name, _) => match self.sym_tbl.get(name) {
-                // TODO: remove this clone?
-                Some(Symbol::Func(proto, _)) => Some(proto.ret.clone()),
-                _ => None,
-            },
-            Cond(_, _, _) => todo!(),
+    /// Replaces the type parameters in a type written in the instance being checked
+    fn resolve(&self, ty: Type) -> Type {
+        ty.subst(&self.subst)
+    }
+
+    /// Finds the fields of a struct type, with the type parameters of a generic struct replaced by
+    /// its type arguments
+    fn struct_def(&self, ty: Type) -> Option<StructDef> {
+        match ty {
+            Type::Name(name) => self.structs.get(&name).cloned(),
+            Type::Generic(name, args) => self.structs.get(&name).map(|def| def.instantiate(args)),
+            _ => None,
         }
     }
-}
 
-impl<'a, 'b> AstVisitor<()> for TypeChecker<'a, 'b> {
-    fn visit_expr(&mut self, e: &Expr) {
-        use ExprKind::*;
-        use TypeErrorKind::*;
-        match &e.kind {
-            Lit(_) => {}
+    /// Records that a generic function is called with the type arguments and returns the name of
+    /// the instance
+    fn instantiate(&mut self, func: Name, args: Vec<Type>, region: Region) -> Name {
+        let name = mangle(func, &args);
+        if !self
+            .instances
+            .iter()
+            .any(|(instance, ..)| *instance == name)
+        {
+            if self.instances.len() >= MAX_INSTANCES {
+                self.type_error(TooManyInstances(func), region);
+            } else {
+                self.instances.push((name, func, args));
+            }
+        }
+        name
+    }
+
+    /// Infers the type arguments of a generic function or struct from the values given for its
+    /// parameters or fields (paired with their declared types), then checks the values against
+    /// the instantiated types. `bound` holds the type arguments that are already known, and
+    /// `expected` the declared type of the result with the type it is expected to have.
+    /// A value whose type doesn't depend on the unknown type parameters is checked like any other
+    /// value, and a literal only decides a type parameter that neither the other values nor the
+    /// expected type decide
+    fn infer_type_args(
+        &mut self,
+        generics: &[Name],
+        values: &[(&Expr, Type)],
+        mut bound: HashMap<Name, Type>,
+        expected: Option<(Type, Type)>,
+        region: Region,
+    ) -> Option<Vec<Type>> {
+        let unbound: Vec<Name> = generics
+            .iter()
+            .copied()
+            .filter(|param| !bound.contains_key(param))
+            .collect();
+
+        let mut found = Vec::with_capacity(values.len());
+        for (value, ty) in values {
+            if mentions(*ty, &unbound) {
+                found.push(Some(self.infer(value)));
+            } else {
+                self.expect(value, ty.subst(&bound));
+                found.push(None);
+            }
+        }
 
-            Assign(ref name, _) => {
-                if !self.var_exists(name) {
-                    self.type_error(UndefinedSymbol, e.region);
+        for ((_, ty), found) in values.iter().zip(&found) {
+            if let Some(Ty::Known(found)) = found {
+                match_type_args(*ty, *found, generics, &mut bound);
+            }
+        }
+        // `ret id(5);` in a function that returns an `int8` calls `id<int8>`
+        if let Some((result, expected)) = expected {
+            match_type_args(result, expected, generics, &mut bound);
+        }
+        for ((_, ty), found) in values.iter().zip(&found) {
+            let default = match found {
+                Some(Ty::IntLit) => Type::DEFAULT_INT,
+                Some(Ty::FloatLit) => Type::DEFAULT_FLOAT,
+                _ => continue,
+            };
+            if let Type::Name(param) = ty {
+                if unbound.contains(param) {
+                    bound.entry(*param).or_insert(default);
                 }
             }
+        }
 
-            Variable(ref name) => {
-                if !self.var_exists(name) {
-                    self.type_error(UndefinedSymbol, e.region);
+        let args: Option<Vec<Type>> = generics
+            .iter()
+            .map(|param| bound.get(param).copied())
+            .collect();
+        if args.is_none() {
+            // a value with an error could have decided the type parameter
+            if !found.contains(&Some(Ty::Error)) {
+                for param in generics.iter().filter(|param| !bound.contains_key(param)) {
+                    self.type_error(CannotInfer(*param), region);
+                }
+            }
+            for ((value, _), found) in values.iter().zip(found) {
+                if let Some(found) = found {
+                    self.default_lit(value, found);
                 }
             }
+            return None;
+        }
 
-            Binary(op, ref lhs, ref rhs) => {
-                use BinOp::*;
+        for ((value, ty), found) in values.iter().zip(found) {
+            if let Some(found) = found {
+                self.coerce(value, found, ty.subst(&bound));
+            }
+        }
+        args
+    }
 
-                self.visit_expr(lhs);
-                self.visit_expr(rhs);
+    fn infer(&mut self, expr: &Expr) -> Ty {
+        let ty = walk_expr(self, expr);
+        if let Ty::Known(ty) = ty {
+            self.table.types.insert(expr.region, ty);
+        }
+        ty
+    }
 
-                let ty = match (self.infer_type(lhs), self.infer_type(rhs)) {
-                    (Some(lty), Some(rty)) if lty == rty => lty,
-                    _ => return self.type_error(Mismatch, e.region),
-                };
+    /// Checks an expression that is used where a value of type `ty` is expected.
+    /// Unlike inferring it first, the elements of an array literal get the expected element type
+    /// (`[1, 2]` can be a `[uint8; 2]`)
+    fn expect(&mut self, expr: &Expr, ty: Type) {
+        match (&expr.kind, ty) {
+            (ExprKind::Array(elems), Type::Array(elem_ty, _) | Type::Slice(elem_ty)) => {
+                for elem in elems {
+                    self.expect(elem, *elem_ty);
+                }
 
-                match ty {
-                    Type::Str if *op != Eq || *op != Ne => self.type_error(InvalidOp, e.region),
-                    Type::Other(_) | Type::Void => self.type_error(InvalidOp, e.region),
-                    _ => {}
+                let found = Type::Array(elem_ty, elems.len() as u64);
+                self.table.types.insert(expr.region, found);
+                self.coerce(expr, Ty::Known(found), ty);
+            }
+            // the elements of a tuple get the types of the expected tuple's elements
+            (ExprKind::Tuple(elems), Type::Tuple(elem_tys)) if elems.len() == elem_tys.len() => {
+                for (elem, elem_ty) in elems.iter().zip(elem_tys) {
+                    self.expect(elem, *elem_ty);
+                }
+                self.table.types.insert(expr.region, ty);
+            }
+            // a generic struct literal gets its type arguments from the expected type
+            // (`Box { value: 1 }` can be a `Box<uint8>`)
+            (ExprKind::StructLit(name, fields), Type::Generic(ty_name, args))
+                if *name == ty_name =>
+            {
+                let found = self.check_struct_lit(*name, fields, args, expr.region);
+                if let Ty::Known(found) = found {
+                    self.table.types.insert(expr.region, found);
+                }
+                self.coerce(expr, found, ty);
+            }
+            // the type parameters of a generic function that only literals would decide come from
+            // the expected type (`id(5)` can be an `int8`)
+            (ExprKind::Call(callee, args), _) if self.is_direct_call(callee) => {
+                let found = self.check_direct_call(callee, args, Some(ty), expr.region);
+                if let Ty::Known(found) = found {
+                    self.table.types.insert(expr.region, found);
                 }
+                self.coerce(expr, found, ty);
+            }
+            // the address of a variable whose type isn't known yet gives it the pointee type
+            // (`scan(&n)` makes `n` an `int32` if `scan` takes an `*int32`)
+            (ExprKind::Unary(UnOp::Ref, rhs), Type::Ptr(pointee)) if self.is_unbound(rhs) => {
+                self.expect(rhs, *pointee);
+                self.table.types.insert(expr.region, ty);
             }
+            _ => {
+                let found = self.infer(expr);
+                self.coerce(expr, found, ty);
+            }
+        }
+    }
 
-            Call(ref name, args) => {
-                use Symbol::*;
+    /// Returns `true` if the expression is an inferred variable whose type isn't known yet
+    fn is_unbound(&self, expr: &Expr) -> bool {
+        match expr.kind {
+            ExprKind::Variable(name) => match self.lookup(name) {
+                Some(Local::Infer(id)) => self.vars[self.find(id)].ty.is_none(),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
 
-                let params = match self.sym_tbl.get(name) {
-                    Some(Func(proto, _)) => &proto.params,
-                    _ => return self.type_error(UndefinedFunc, e.region),
+    /// Checks that an expression has the type `ty`.
+    /// Integer and float literals take the type if it's an integer or float type respectively,
+    /// and arrays can be used as slices of the same element type
+    fn coerce(&mut self, expr: &Expr, found: Ty, ty: Type) {
+        match found {
+            Ty::Known(Type::Array(elem, _)) if ty == Type::Slice(elem) => {}
+            Ty::Known(found) if found != ty => {
+                let kind = match self.inferred_at(expr) {
+                    Some((var, inferred_at)) => InferredMismatch {
+                        expected: ty,
+                        found,
+                        var,
+                        inferred_at,
+                    },
+                    None => Mismatch {
+                        expected: ty,
+                        found,
+                    },
                 };
+                self.type_error(kind, expr.region)
+            }
+            Ty::IntLit if ty.is_int() => self.type_lits(expr, ty),
+            Ty::IntLit => self.type_error(NotAnInt(ty), expr.region),
+            Ty::FloatLit if ty.is_float() => self.type_lits(expr, ty),
+            Ty::FloatLit => self.type_error(NotAFloat(ty), expr.region),
+            _ => {}
+        }
+    }
 
-                if args.len() != params.len() {
-                    return self.type_error(InvalidArgs, e.region);
-                }
+    /// Gives literals that aren't used with any other number the default integer or float type
+    fn default_lit(&mut self, expr: &Expr, found: Ty) -> Ty {
+        let ty = match found {
+            Ty::IntLit => Type::DEFAULT_INT,
+            Ty::FloatLit => Type::DEFAULT_FLOAT,
+            _ => return found,
+        };
 
-                for (arg, param) in args.iter().zip(params) {
-                    self.visit_expr(arg);
-                    if let Some(arg_ty) = self.infer_type(arg) {
-                        if arg_ty != param.ty {
-                            self.type_error(InvalidArgs, e.region);
-                        }
-                    }
+        self.type_lits(expr, ty);
+        Ty::Known(ty)
+    }
+
+    /// Gives every node of a literal expression the type `ty` and checks that each literal fits
+    /// in it
+    fn type_lits(&mut self, expr: &Expr, ty: Type) {
+        let tree = self.lit_tree(expr);
+        self.apply_lits(&tree, ty);
+    }
+
+    fn apply_lits(&mut self, tree: &LitTree, ty: Type) {
+        self.table.types.insert(tree.region(), ty);
+
+        let mut check_int = |value: u64, negative: bool, region: Region| {
+            let Type::Int { signed, size } = ty else {
+                unreachable!("integer literals can only have integer types");
+            };
+            if !int_lit_fits(value, negative, signed, size) {
+                self.type_error(
+                    OutOfRange {
+                        value,
+                        negative,
+                        ty,
+                    },
+                    region,
+                );
+            }
+        };
+
+        match *tree {
+            LitTree::Int { value, region } => check_int(value, false, region),
+            LitTree::Float { value, region } => {
+                if ty == Type::Float(FloatSize::F32) && (value as f32).is_infinite() {
+                    self.type_error(FloatOutOfRange(value), region);
+                }
+            }
+            LitTree::Neg { ref rhs, region } => match **rhs {
+                // a negative literal like `-128`
+                LitTree::Int {
+                    value,
+                    region: lit_region,
+                } => {
+                    check_int(value, true, region);
+                    self.table.types.insert(lit_region, ty);
+                }
+                _ if matches!(ty, Type::Int { signed: false, .. }) => {
+                    self.type_error(InvalidUnaryOp(UnOp::Neg, ty), region)
                 }
+                _ => self.apply_lits(rhs, ty),
+            },
+            LitTree::Same { ref value, .. } => self.apply_lits(value, ty),
+            LitTree::Binary {
+                ref lhs, ref rhs, ..
+            } => {
+                self.apply_lits(lhs, ty);
+                self.apply_lits(rhs, ty);
             }
+            LitTree::Var { id, name, region } => self.bind(id, ty, name, region),
+        }
+    }
 
-            Cond(_, _, _) => {
-                walk_expr(self, e);
+    /// Mirrors an expression whose type is a literal type, see [`LitTree`]
+    fn lit_tree(&self, expr: &Expr) -> LitTree {
+        let region = expr.region;
+        match &expr.kind {
+            ExprKind::Lit(Lit::Int(value)) => LitTree::Int {
+                value: *value,
+                region,
+            },
+            ExprKind::Lit(Lit::Float(value)) => LitTree::Float {
+                value: *value,
+                region,
+            },
+            ExprKind::Unary(UnOp::Neg, rhs) => LitTree::Neg {
+                rhs: Box::new(self.lit_tree(rhs)),
+                region,
+            },
+            ExprKind::Unary(UnOp::Move | UnOp::Clone, value) | ExprKind::Assign(value, _) => {
+                LitTree::Same {
+                    value: Box::new(self.lit_tree(value)),
+                    region,
+                }
             }
+            ExprKind::Binary(_, lhs, rhs) => LitTree::Binary {
+                lhs: Box::new(self.lit_tree(lhs)),
+                rhs: Box::new(self.lit_tree(rhs)),
+                region,
+            },
+            ExprKind::Variable(name) => match self.lookup(*name) {
+                Some(Local::Infer(id)) => LitTree::Var {
+                    id,
+                    name: *name,
+                    region,
+                },
+                _ => unreachable!("only inferred variables have literal types"),
+            },
+            _ => unreachable!("only literals, variables and arithmetic on them are literals"),
+        }
+    }
+
+    /// Finds the variable that stands for the group of an inferred variable
+    fn find(&self, mut id: usize) -> usize {
+        while self.vars[id].parent != id {
+            id = self.vars[id].parent;
         }
+        id
     }
 
-    fn visit_stmt(&mut self, s: &Stmt) {
-        use StmtKind::*;
-        match &s.kind {
-            Expr(e) => self.visit_expr(e),
-            Var(name, init) => {
-                self.visit_expr(init);
-                if let Some(inferred_ty) = self.infer_type(init) {
-                    if let Some(ty) = self.get_var_type(name) {
-                        if *ty != inferred_ty {
-                            self.type_error(Mismatch, s.region);
+    /// Puts two inferred variables in the same group
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+
+        match (self.vars[a].ty, self.vars[b].ty) {
+            (ty, None) => {
+                self.vars[b].parent = a;
+                let pending = std::mem::take(&mut self.vars[b].pending);
+                match ty {
+                    Some((ty, _)) => {
+                        for tree in &pending {
+                            self.apply_lits(tree, ty);
                         }
-                    } else {
-                        self.type_error(UndefinedSymbol, s.region);
                     }
-                } else {
-                    self.type_error(CannotInfer, s.region);
+                    None => self.vars[a].pending.extend(pending),
                 }
             }
-            Ret(_) => {}
+            (None, Some(_)) => self.union(b, a),
+            // the expressions that tie them are checked against both types
+            (Some(_), Some(_)) => {}
         }
     }
 
-    fn visit_func(&mut self, f: &FuncDef) {
-        use StmtKind::Ret;
+    /// Gives the group of an inferred variable the type `ty`, because of the use of the variable
+    /// `name` at `region`
+    fn bind(&mut self, id: usize, ty: Type, name: Name, region: Region) {
+        let root = self.find(id);
+        match self.vars[root].ty {
+            Some((found, inferred_at)) if found != ty => self.type_error(
+                InferredMismatch {
+                    expected: ty,
+                    found,
+                    var: name,
+                    inferred_at,
+                },
+                region,
+            ),
+            Some(_) => {}
+            None => {
+                self.vars[root].ty = Some((ty, region));
+                for tree in std::mem::take(&mut self.vars[root].pending) {
+                    self.apply_lits(&tree, ty);
+                }
+            }
+        }
+    }
 
-        if let Some(Symbol::Func(_, local)) = self.sym_tbl.get(&f.proto.name) {
-            self.local_scope = local;
-        } else {
-            self.type_error(UndefinedFunc, f.region);
+    /// Makes a literal expression get the type of the group of the inferred variable `id`, and
+    /// puts the variables used in it in the same group
+    fn tie(&mut self, tree: LitTree, id: usize) {
+        let mut vars = Vec::new();
+        tree.vars(&mut vars);
+        for var in vars {
+            self.union(id, var);
         }
 
-        for stmt in &f.body.stmts {
-            match &stmt.kind {
-                Ret(e) => {
-                    self.visit_expr(e);
-                    if let Some(ty) = self.infer_type(e) {
-                        if ty != f.proto.ret {
-                            self.type_error(RetTyMismatch, f.region);
-                        }
-                    } else {
-                        self.type_error(CannotInfer, f.region);
-                    }
+        let root = self.find(id);
+        match self.vars[root].ty {
+            Some((ty, _)) => self.apply_lits(&tree, ty),
+            None => self.vars[root].pending.push(tree),
+        }
+    }
+
+    /// Literal expressions that are used where nothing decides their type (`x;`, `x < 10`) get
+    /// the type of the inferred variables used in them, once it's known. Returns `false` if they
+    /// don't use any, they then need the default type
+    fn defer_lits(&mut self, exprs: &[&Expr], ty: Ty) -> bool {
+        if !matches!(ty, Ty::IntLit | Ty::FloatLit) {
+            return false;
+        }
+
+        let trees: Vec<LitTree> = exprs.iter().map(|expr| self.lit_tree(expr)).collect();
+        let mut vars = Vec::new();
+        for tree in &trees {
+            tree.vars(&mut vars);
+        }
+        let Some(&id) = vars.first() else {
+            return false;
+        };
+        for tree in trees {
+            self.tie(tree, id);
+        }
+        true
+    }
+
+    /// Gives the default type to the groups of the inferred variables declared deeper than
+    /// `depth` scopes that no use decided the type of
+    fn default_vars(&mut self, depth: usize) {
+        let start = self
+            .vars
+            .iter()
+            .rposition(|var| var.depth <= depth)
+            .map_or(0, |idx| idx + 1);
+
+        for id in start..self.vars.len() {
+            let root = self.find(id);
+            let var = &self.vars[root];
+            if var.ty.is_none() {
+                let ty = match var.kind {
+                    Ty::FloatLit => Type::DEFAULT_FLOAT,
+                    _ => Type::DEFAULT_INT,
+                };
+                self.bind(root, ty, var.name, var.region);
+            }
+        }
+    }
+
+    /// Finds the variable and the use that decided its type if the expression is an inferred
+    /// variable, so that a mismatch can point at both
+    fn inferred_at(&self, expr: &Expr) -> Option<(Name, Region)> {
+        let ExprKind::Variable(name) = expr.kind else {
+            return None;
+        };
+        let Some(Local::Infer(id)) = self.lookup(name) else {
+            return None;
+        };
+        let (_, inferred_at) = self.vars[self.find(id)].ty?;
+        Some((name, inferred_at))
+    }
+
+    /// Finds the number type that both operands of an arithmetic operation have
+    fn unify_numbers(
+        &mut self,
+        op: BinOp,
+        lhs: (&Expr, Ty),
+        rhs: (&Expr, Ty),
+        region: Region,
+    ) -> Ty {
+        match (lhs.1, rhs.1) {
+            (Ty::Error, _) | (_, Ty::Error) => Ty::Error,
+            (Ty::IntLit, Ty::IntLit) => Ty::IntLit,
+            (Ty::FloatLit, Ty::FloatLit) => Ty::FloatLit,
+            (Ty::Known(ty), _) | (_, Ty::Known(ty)) if !ty.is_number() => {
+                self.type_error(InvalidOp(op, ty), region);
+                Ty::Error
+            }
+            (lit @ (Ty::IntLit | Ty::FloatLit), Ty::Known(ty)) => {
+                self.coerce(lhs.0, lit, ty);
+                Ty::Known(ty)
+            }
+            (Ty::Known(ty), lit @ (Ty::IntLit | Ty::FloatLit)) => {
+                self.coerce(rhs.0, lit, ty);
+                Ty::Known(ty)
+            }
+            // an integer literal and a float literal (`1 + 0.5`)
+            (Ty::IntLit | Ty::FloatLit, _) => {
+                let lty = self.default_lit(lhs.0, lhs.1);
+                let rty = self.default_lit(rhs.0, rhs.1);
+                self.unify_numbers(op, (lhs.0, lty), (rhs.0, rty), region)
+            }
+            (Ty::Known(lty), Ty::Known(rty)) if lty == rty => Ty::Known(lty),
+            (
+                Ty::Known(lty @ Type::Int { signed: l, .. }),
+                Ty::Known(rty @ Type::Int { signed: r, .. }),
+            ) if l != r => {
+                self.type_error(MixedSignedness(lty, rty), region);
+                Ty::Error
+            }
+            (Ty::Known(lty @ Type::Int { .. }), Ty::Known(rty @ Type::Int { .. })) => {
+                self.type_error(MixedSizes(lty, rty), region);
+                Ty::Error
+            }
+            (Ty::Known(lty), Ty::Known(rty)) => {
+                self.type_error(
+                    Mismatch {
+                        expected: lty,
+                        found: rty,
+                    },
+                    rhs.0.region,
+                );
+                Ty::Error
+            }
+        }
+    }
+
+    /// Checks a condition, or an operand of `&&`, `||` and `!`
+    fn check_condition(&mut self, condition: &Expr) {
+        let ty = self.infer(condition);
+        match self.default_lit(condition, ty) {
+            Ty::Known(Type::Bool) | Ty::Error => {}
+            Ty::Known(ty @ Type::Int { .. }) => self.type_error(IntCondition(ty), condition.region),
+            Ty::Known(found) => self.type_error(
+                Mismatch {
+                    expected: Type::Bool,
+                    found,
+                },
+                condition.region,
+            ),
+            Ty::IntLit | Ty::FloatLit => unreachable!(),
+        }
+    }
+
+    /// Checks that every type name in a type refers to a type or to a type parameter in scope, and
+    /// that generic structs get the right number of type arguments
+    fn check_type(&mut self, ty: Type, region: Region) {
+        let (name, args) = match ty {
+            Type::Name(name) if self.generics.contains(&name) => return,
+            Type::Name(name) => (name, &[][..]),
+            Type::Generic(name, args) => (name, args),
+            Type::Array(elem, _) | Type::Slice(elem) | Type::Ptr(elem) => {
+                return self.check_type(*elem, region)
+            }
+            Type::Func(params, ret) => {
+                for param in params {
+                    self.check_type(*param, region);
+                }
+                return self.check_type(*ret, region);
+            }
+            Type::Tuple(elems) => {
+                for elem in elems {
+                    self.check_type(*elem, region);
                 }
-                _ => self.visit_stmt(stmt),
+                return;
             }
+            _ => return,
+        };
+
+        let expected = match self.structs.get(&name) {
+            Some(def) => def.generics.len(),
+            None if self.enums.contains_key(&name) => 0,
+            None => return self.type_error(UnknownType(name), region),
+        };
+        if expected != args.len() {
+            self.type_error(
+                WrongTypeArgs {
+                    name,
+                    expected,
+                    found: args.len(),
+                },
+                region,
+            );
+        }
+        for arg in args {
+            self.check_type(*arg, region);
         }
     }
 
-    fn visit_block(&mut self, b: &Block) {
-        for stmt in &b.stmts {
-            self.visit_stmt(stmt);
+    fn check_proto(&mut self, proto: &FuncProto) {
+        self.generics.clone_from(&proto.generics);
+        for param in &proto.params {
+            self.check_type(param.ty, param.ty_region);
         }
+        self.check_type(proto.ret, proto.ret_region);
+        self.generics.clear();
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::lexer::{Token, Lexer};
-    use crate::parser::Parser;
+    /// Checks the body of a function, or of an instance of a generic function
+    fn check_body(&mut self, proto: &FuncProto, body: &[Box<Stmt>]) {
+        self.ret_ty = self.resolve(proto.ret);
+        self.scopes.push(HashMap::new());
+        for param in &proto.params {
+            self.define(param.name, self.resolve(param.ty));
+        }
+        self.check_block(body);
+        self.pop_scope();
+        self.ret_ty = Type::Void;
+    }
 
-    use super::*;
+    /// Checks the arguments of a call against the parameters of the function, and finds the
+    /// instance that is called if the function is generic. `expected` is the type the result is
+    /// expected to have, if it's known
+    fn check_call(
+        &mut self,
+        proto: &FuncProto,
+        args: &[Box<Expr>],
+        expected: Option<Type>,
+        region: Region,
+    ) -> Ty {
+        if !proto.variadic {
+            self.check_arg_count(proto.params.len(), args, region);
+        } else if args.len() < proto.params.len() {
+            self.type_error(
+                TooFewArgs {
+                    expected: proto.params.len(),
+                    found: args.len(),
+                },
+                region,
+            );
+        } else {
+            for arg in &args[proto.params.len()..] {
+                self.check_vararg(arg);
+            }
+        }
 
-    #[test]
-    fn typechk_func() {
-        let input = "\
-        func ret_ty_mismatch(): s32 {\n\
-            ret \"str\";\n\
-        }";
+        if proto.generics.is_empty() {
+            for (arg, param) in args.iter().zip(&proto.params) {
+                self.expect(arg, param.ty);
+            }
+            return Ty::Known(proto.ret);
+        }
+
+        let values: Vec<(&Expr, Type)> = args
+            .iter()
+            .zip(&proto.params)
+            .map(|(arg, param)| (&**arg, param.ty))
+            .collect();
+        let expected = expected.map(|expected| (proto.ret, expected));
+        let Some(type_args) =
+            self.infer_type_args(&proto.generics, &values, HashMap::new(), expected, region)
+        else {
+            return Ty::Error;
+        };
+
+        let ret = proto.ret.subst(&ty::bind(&proto.generics, &type_args));
+        let instance = self.instantiate(proto.name, type_args, region);
+        self.table.callees.insert(region, instance);
+        Ty::Known(ret)
+    }
+
+    /// Checks an argument passed to the `...` of a variadic function. It keeps its own type, which
+    /// has to be a type that C passes in a register
+    fn check_vararg(&mut self, arg: &Expr) {
+        let ty = self.infer(arg);
+        // literals that use an inferred variable get its type, whatever decides it
+        if self.defer_lits(&[arg], ty) {
+            return;
+        }
+        match self.default_lit(arg, ty) {
+            Ty::Known(Type::Void) => self.type_error(VoidValue, arg.region),
+            Ty::Known(ty) if ty.is_scalar() => {}
+            Ty::Known(ty) => self.type_error(InvalidVarArg(ty), arg.region),
+            _ => {}
+        }
+    }
+
+    /// Whether the callee is the name of a function rather than a function value
+    fn is_direct_call(&self, callee: &Expr) -> bool {
+        match callee.kind {
+            ExprKind::Variable(name) => {
+                self.lookup(name).is_none() && self.funcs.contains_key(&name)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks a call to a function by its name, see [`TypeChecker::is_direct_call`]
+    fn check_direct_call(
+        &mut self,
+        callee: &Expr,
+        args: &[Box<Expr>],
+        expected: Option<Type>,
+        region: Region,
+    ) -> Ty {
+        let ExprKind::Variable(name) = callee.kind else {
+            unreachable!("the callee of a direct call is a name");
+        };
+        let proto = self.funcs[&name].clone();
+        self.table.funcs.insert(callee.region);
+        self.check_call(&proto, args, expected, region)
+    }
 
-        let source = Source::new(input, "<string literal>");
-        let mut tokens = Lexer::new(&source);
-        assert_eq!(tokens.eat(), Token::Func);
+    /// Checks the arguments of a call to a function value against the types of its parameters
+    fn check_args(&mut self, params: &[Type], args: &[Box<Expr>], region: Region) {
+        self.check_arg_count(params.len(), args, region);
+        for (arg, param) in args.iter().zip(params) {
+            self.expect(arg, *param);
+        }
+    }
 
-        let mut parser = Parser::new(tokens);
-        let f = parser.parse_func().unwrap();
+    fn check_arg_count(&mut self, expected: usize, args: &[Box<Expr>], region: Region) {
+        if args.len() != expected {
+            self.type_error(
+                InvalidArgs {
+                    expected,
+                    found: args.len(),
+                },
+                region,
+            );
+        }
+    }
 
-        let sym_tbl = parser.sym_tbl();
-        let mut typechk = TypeChecker::new(&source, sym_tbl);
-        typechk.visit_func(&f);
+    /// Checks the arguments of a call that has an error, so that they still get types
+    fn infer_args(&mut self, args: &[Box<Expr>]) {
+        for arg in args {
+            let ty = self.infer(arg);
+            self.default_lit(arg, ty);
+        }
+    }
 
-        let e = typechk.errors().last().unwrap();
-        assert!(matches!(e.kind, TypeErrorKind::RetTyMismatch))
+    /// Returns `true` if a value of type `ty` contains a value of the struct or enum `name`,
+    /// without any indirection
+    fn contains_struct(&self, ty: Type, name: Name, visited: &mut Vec<Name>) -> bool {
+        let ty_name = match ty {
+            Type::Name(ty_name) => ty_name,
+            // the type arguments of a generic struct can be stored in it
+            Type::Generic(ty_name, args) => {
+                if args
+                    .iter()
+                    .any(|arg| self.contains_struct(*arg, name, visited))
+                {
+                    return true;
+                }
+                ty_name
+            }
+            // the elements of an array are stored in the array, a slice only points to them
+            Type::Array(elem, _) => return self.contains_struct(*elem, name, visited),
+            Type::Tuple(elems) => {
+                return elems
+                    .iter()
+                    .any(|elem| self.contains_struct(*elem, name, visited))
+            }
+            _ => return false,
+        };
+        if ty_name == name {
+            return true;
+        }
+        if visited.contains(&ty_name) {
+            return false;
+        }
+        visited.push(ty_name);
+
+        let in_struct = self.structs.get(&ty_name).is_some_and(|def| {
+            def.fields
+                .iter()
+                .any(|field| self.contains_struct(field.ty, name, visited))
+        });
+        in_struct
+            || self.enums.get(&ty_name).is_some_and(|def| {
+                def.variants
+                    .iter()
+                    .flat_map(|variant| &variant.fields)
+                    .any(|field| self.contains_struct(*field, name, visited))
+            })
+    }
+
+    /// Checks a struct literal. The type arguments of a generic struct are inferred from the values
+    /// of its fields, starting with `args`, the ones that are already known
+    fn check_struct_lit(
+        &mut self,
+        name: Name,
+        fields: &[(Name, Box<Expr>)],
+        args: &[Type],
+        region: Region,
+    ) -> Ty {
+        let Some(def) = self.structs.get(&name).cloned() else {
+            self.type_error(UnknownType(name), region);
+            for (_, value) in fields {
+                let ty = self.infer(value);
+                self.default_lit(value, ty);
+            }
+            return Ty::Error;
+        };
+
+        // the fields that are given a value once, paired with their declared types
+        let values: Vec<(&Expr, Type)> = fields
+            .iter()
+            .enumerate()
+            .filter(|(idx, (field_name, _))| !fields[..*idx].iter().any(|(f, _)| f == field_name))
+            .filter_map(|(_, (field_name, value))| Some((&**value, def.field(*field_name)?.ty)))
+            .collect();
+
+        let ty = if def.generics.is_empty() {
+            for (value, ty) in &values {
+                self.expect(value, *ty);
+            }
+            Some(Type::Name(name))
+        } else {
+            let bound = ty::bind(&def.generics, args);
+            self.infer_type_args(&def.generics, &values, bound, None, region)
+                .map(|args| Type::Generic(name, ty::intern_list(args)))
+        };
+        // the errors below are about the struct, whatever its type arguments are
+        let err_ty = ty.unwrap_or(Type::Name(name));
+
+        for (idx, (field_name, value)) in fields.iter().enumerate() {
+            let duplicate = fields[..idx].iter().any(|(f, _)| f == field_name);
+            if !duplicate && def.field(*field_name).is_some() {
+                continue;
+            }
+
+            let found = self.infer(value);
+            self.default_lit(value, found);
+            if duplicate {
+                self.type_error(DuplicateField(*field_name), value.region);
+            } else {
+                self.type_error(
+                    NoField {
+                        ty: err_ty,
+                        field: *field_name,
+                    },
+                    value.region,
+                );
+            }
+        }
+
+        for field in &def.fields {
+            if !fields.iter().any(|(f, _)| *f == field.name) {
+                self.type_error(
+                    MissingField {
+                        ty: err_ty,
+                        field: field.name,
+                    },
+                    region,
+                );
+            }
+        }
+
+        ty.map_or(Ty::Error, Ty::Known)
+    }
+
+    /// Checks that a pattern can match a value of type `ty` and defines the variables it binds.
+    /// Returns `false` if the pattern has an error
+    fn check_pattern(&mut self, pattern: &Pattern, ty: Type) -> bool {
+        match &pattern.kind {
+            PatternKind::Wildcard => true,
+            PatternKind::Binding(name) => {
+                self.define(*name, ty);
+                true
+            }
+            PatternKind::Lit { lit, negative } => {
+                let value = match *lit {
+                    Lit::Int(value) => value,
+                    Lit::Bool(_) | Lit::Char(_) => {
+                        let found = match lit {
+                            Lit::Bool(_) => Type::Bool,
+                            _ => Type::Char,
+                        };
+                        if ty != found {
+                            self.type_error(
+                                Mismatch {
+                                    expected: ty,
+                                    found,
+                                },
+                                pattern.region,
+                            );
+                        }
+                        return ty == found;
+                    }
+                    Lit::Float(_) | Lit::Str(_) => {
+                        self.type_error(InvalidLitPattern(*lit), pattern.region);
+                        return false;
+                    }
+                };
+
+                let Type::Int { signed, size } = ty else {
+                    self.type_error(NotAnInt(ty), pattern.region);
+                    return false;
+                };
+                let fits = int_lit_fits(value, *negative, signed, size);
+                if !fits {
+                    self.type_error(
+                        OutOfRange {
+                            value,
+                            negative: *negative,
+                            ty,
+                        },
+                        pattern.region,
+                    );
+                }
+                fits
+            }
+            PatternKind::Variant(enum_name, variant, fields) => {
+                let Some(def) = self.enums.get(enum_name) else {
+                    self.type_error(UnknownType(*enum_name), pattern.region);
+                    return false;
+                };
+                if ty != Type::Name(*enum_name) {
+                    self.type_error(
+                        Mismatch {
+                            expected: ty,
+                            found: Type::Name(*enum_name),
+                        },
+                        pattern.region,
+                    );
+                    return false;
+                }
+                let Some(idx) = def.variant(*variant) else {
+                    self.type_error(
+                        UnknownVariant {
+                            ty: *enum_name,
+                            variant: *variant,
+                        },
+                        pattern.region,
+                    );
+                    return false;
+                };
+
+                let field_tys = def.variants[idx].fields.clone();
+                let mut ok = true;
+                if fields.len() != field_tys.len() {
+                    self.type_error(
+                        InvalidArgs {
+                            expected: field_tys.len(),
+                            found: fields.len(),
+                        },
+                        pattern.region,
+                    );
+                    ok = false;
+                }
+                for (field, ty) in fields.iter().zip(field_tys) {
+                    ok &= self.check_pattern(field, ty);
+                }
+                ok
+            }
+            PatternKind::Tuple(elems) => {
+                let Type::Tuple(elem_tys) = ty else {
+                    self.type_error(NotATuple(ty), pattern.region);
+                    return false;
+                };
+                let mut ok = true;
+                if elems.len() != elem_tys.len() {
+                    self.type_error(
+                        TupleLen {
+                            expected: elem_tys.len(),
+                            found: elems.len(),
+                        },
+                        pattern.region,
+                    );
+                    ok = false;
+                }
+                for (elem, ty) in elems.iter().zip(elem_tys) {
+                    ok &= self.check_pattern(elem, *ty);
+                }
+                ok
+            }
+        }
+    }
+
+    fn lookup(&self, name: Name) -> Option<Local> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name))
+            .copied()
+    }
+
+    fn define(&mut self, name: Name, ty: Type) {
+        self.define_local(name, Local::Known(ty));
+    }
+
+    fn define_local(&mut self, name: Name, local: Local) {
+        // NOTE: this unwrap is fine because there should always be a global scope
+        self.scopes.last_mut().unwrap().insert(name, local);
+    }
+
+    /// Evaluates the initializer of a constant that was type checked, `None` if it has an error
+    fn eval_const(&mut self, value: &Expr) -> Option<Value> {
+        let (kind, region) = match consteval::eval(value, &self.table) {
+            Ok(value) => return Some(value),
+            Err(EvalError::NotConstant(region)) => (NotConstant, region),
+            Err(EvalError::Overflow(ty, region)) => (ConstOverflow(ty), region),
+            Err(EvalError::DivByZero(region)) => (ConstDivByZero, region),
+            Err(EvalError::Reported) => return None,
+        };
+        self.type_error(kind, region);
+        None
+    }
+
+    /// Reports a cast to `char` of a constant that isn't the code point of a character, the code
+    /// points of other values aren't checked
+    fn check_code_point(&mut self, value: &Expr) {
+        if let Ok(Value::Int(code)) = consteval::eval(value, &self.table) {
+            if u32::try_from(code).ok().and_then(char::from_u32).is_none() {
+                self.type_error(InvalidCodePoint(code), value.region);
+            }
+        }
+    }
+
+    /// Records that the lambdas being checked capture a local variable, if it's declared outside of
+    /// them
+    fn capture(&mut self, name: Name, region: Region) {
+        let Some(depth) = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&name))
+        else {
+            return;
+        };
+        for lambda in &mut self.lambdas {
+            if depth < lambda.depth && !lambda.captures.iter().any(|(var, _)| *var == name) {
+                lambda.captures.push((name, region));
+            }
+        }
+    }
+
+    /// Returns `true` if the name is a module-level variable that isn't shadowed by a local
+    fn is_global(&self, name: Name) -> bool {
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&name))
+            == Some(0)
+    }
+
+    /// Returns the name of the constant if the expression is one
+    fn const_name(&self, expr: &Expr) -> Option<Name> {
+        match expr.kind {
+            ExprKind::Variable(name) => match self.lookup(name) {
+                Some(Local::Const(..)) => Some(name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Ends the innermost scope, see [`TypeChecker::default_vars`]
+    fn pop_scope(&mut self) {
+        self.scopes.pop();
+        self.default_vars(self.scopes.len());
+    }
+
+    fn check_block(&mut self, block: &[Box<Stmt>]) {
+        self.scopes.push(HashMap::new());
+        for stmt in block {
+            walk_stmt(self, stmt);
+        }
+        self.pop_scope();
+    }
+}
+
+impl StmtVisitor<()> for TypeChecker {
+    fn visit_expr_stmt(&mut self, expr: &Expr, _: Region) {
+        let ty = self.infer(expr);
+        if !self.defer_lits(&[expr], ty) {
+            self.default_lit(expr, ty);
+        }
+    }
+
+    fn visit_ret_stmt(&mut self, value: Option<&Expr>, region: Region) {
+        match value {
+            Some(value) => self.expect(value, self.ret_ty),
+            None if self.ret_ty != Type::Void => {
+                self.type_error(MissingRetValue(self.ret_ty), region)
+            }
+            None => {}
+        }
+    }
+
+    fn visit_var_stmt(&mut self, name: Name, init: &Expr, _: Region) {
+        let ty = self.infer(init);
+        match ty {
+            Ty::Known(Type::Void) => self.type_error(VoidValue, init.region),
+            Ty::Known(ty) => self.define(name, ty),
+            // the variable's type is decided by how it's used, and its initializer gets the same
+            // type
+            Ty::IntLit | Ty::FloatLit => {
+                let id = self.vars.len();
+                self.vars.push(InferVar {
+                    name,
+                    region: init.region,
+                    depth: self.scopes.len(),
+                    parent: id,
+                    kind: ty,
+                    ty: None,
+                    pending: Vec::new(),
+                });
+                let tree = self.lit_tree(init);
+                self.tie(tree, id);
+                self.define_local(name, Local::Infer(id));
+            }
+            Ty::Error => {}
+        }
+    }
+
+    fn visit_destructure_stmt(&mut self, pattern: &Pattern, init: &Expr, _: Region) {
+        let ty = self.infer(init);
+        if !pattern.is_irrefutable() {
+            self.type_error(RefutablePattern, pattern.region);
+        }
+        if let Ty::Known(ty) = self.default_lit(init, ty) {
+            self.check_pattern(pattern, ty);
+        }
+    }
+
+    fn visit_const(&mut self, name: Name, ty: Type, value: &Expr, region: Region) {
+        self.check_type(ty, region);
+        let ty = self.resolve(ty);
+        let errors = self.errors.len();
+        self.expect(value, ty);
+
+        // a constant with an error is still defined, so that its uses aren't errors too
+        let value = if self.errors.len() == errors {
+            self.eval_const(value)
+        } else {
+            None
+        };
+        self.define_local(name, Local::Const(ty, value));
+    }
+
+    fn visit_block(&mut self, stmts: &[Box<Stmt>]) {
+        self.check_block(stmts);
+    }
+
+    fn visit_func(&mut self, proto: &FuncProto, body: &[Box<Stmt>], _: Region) {
+        self.check_proto(proto);
+        // only the callers of an extern know where the arguments after its parameters are
+        if proto.variadic {
+            self.type_error(VariadicNotExtern(proto.name), proto.region);
+        }
+        // the body of a generic function is checked once for every instance of it
+        if proto.generics.is_empty() {
+            self.check_body(proto, body);
+        }
+    }
+
+    fn visit_extern(&mut self, proto: &FuncProto, _: Region) {
+        self.check_proto(proto);
+    }
+
+    fn visit_type(&mut self, _: Name, ty: Type, region: Region) {
+        let Type::Newtype(_, inner) = ty else {
+            return self.check_type(ty, region);
+        };
+        self.check_type(*inner, region);
+        if !inner.is_scalar() {
+            self.type_error(InvalidNewtype(*inner), region);
+        }
+    }
+
+    fn visit_impl(&mut self, ty: Name, methods: &[Box<Stmt>], region: Region) {
+        if !self.structs.contains_key(&ty) && !self.enums.contains_key(&ty) {
+            let kind = if builtin_type(ty).is_some() || self.type_decls.contains(&ty) {
+                InvalidImpl(ty)
+            } else {
+                UnknownType(ty)
+            };
+            self.type_error(kind, region);
+            return;
+        }
+
+        for method in methods {
+            if let StmtKind::Func(proto, _) = &method.kind {
+                let self_ty = proto.params.first().filter(|param| param.name == *SELF);
+                match self_ty.map(|param| param.ty) {
+                    Some(Type::Name(name)) if name == ty => {}
+                    Some(Type::Ptr(pointee)) if *pointee == Type::Name(ty) => {}
+                    Some(found) => self.type_error(InvalidSelf { ty, found }, proto.region),
+                    // associated functions don't take a `self`
+                    None => {}
+                }
+            }
+            walk_stmt(self, method);
+        }
+    }
+
+    fn visit_struct(&mut self, def: &StructDef, region: Region) {
+        self.generics.clone_from(&def.generics);
+        for (idx, field) in def.fields.iter().enumerate() {
+            if def.fields[..idx].iter().any(|f| f.name == field.name) {
+                self.type_error(DuplicateField(field.name), region);
+            }
+            self.check_type(field.ty, region);
+        }
+        self.generics.clear();
+
+        let contains_itself = def
+            .fields
+            .iter()
+            .any(|field| self.contains_struct(field.ty, def.name, &mut Vec::new()));
+        if contains_itself {
+            self.type_error(RecursiveStruct(def.name), region);
+        }
+    }
+
+    fn visit_enum(&mut self, def: &EnumDef, region: Region) {
+        for (idx, variant) in def.variants.iter().enumerate() {
+            if def.variants[..idx].iter().any(|v| v.name == variant.name) {
+                self.type_error(DuplicateVariant(variant.name), region);
+            }
+            for field in &variant.fields {
+                self.check_type(*field, region);
+            }
+        }
+
+        let contains_itself = def
+            .variants
+            .iter()
+            .flat_map(|variant| &variant.fields)
+            .any(|field| self.contains_struct(*field, def.name, &mut Vec::new()));
+        if contains_itself {
+            self.type_error(RecursiveStruct(def.name), region);
+        }
+    }
+
+    fn visit_impt(&mut self, _: Name, _: Region) {}
+
+    // the parser has already reported the error
+    fn visit_error_stmt(&mut self, _: Region) {}
+}
+
+impl ExprVisitor<Ty> for TypeChecker {
+    fn visit_lit_expr(&mut self, lit: Lit, _: Region) -> Ty {
+        match lit {
+            Lit::Int(_) => Ty::IntLit,
+            Lit::Float(_) => Ty::FloatLit,
+            Lit::Bool(_) => Ty::Known(Type::Bool),
+            Lit::Char(_) => Ty::Known(Type::Char),
+            Lit::Str(_) => Ty::Known(Type::Str),
+        }
+    }
+
+    fn visit_variable_expr(&mut self, name: Name, region: Region) -> Ty {
+        let local = self.lookup(name);
+        if matches!(local, Some(Local::Known(_) | Local::Infer(_))) {
+            if self.is_global(name) {
+                self.table.globals.insert(region);
+            } else if !self.lambdas.is_empty() {
+                self.capture(name, region);
+            }
+        }
+        match local {
+            Some(Local::Known(ty)) => {
+                self.table.types.insert(region, ty);
+                Ty::Known(ty)
+            }
+            Some(Local::Const(ty, Some(value))) => {
+                self.table.consts.insert(region, value);
+                Ty::Known(ty)
+            }
+            Some(Local::Const(_, None)) => Ty::Error,
+            Some(Local::Infer(id)) => {
+                // a captured variable is copied into the lambda, so its use has to get the type
+                // of the variable even if nothing else uses it
+                if !self.lambdas.is_empty() {
+                    self.tie(LitTree::Var { id, name, region }, id);
+                }
+                let var = &self.vars[self.find(id)];
+                var.ty.map_or(var.kind, |(ty, _)| Ty::Known(ty))
+            }
+            // a function that isn't called is a value
+            None => match self.funcs.get(&name) {
+                Some(proto) if !proto.generics.is_empty() => {
+                    self.type_error(GenericFuncValue(name), region);
+                    Ty::Error
+                }
+                Some(proto) if proto.variadic => {
+                    self.type_error(VariadicFuncValue(name), region);
+                    Ty::Error
+                }
+                Some(proto) => {
+                    let params = proto.params.iter().map(|param| param.ty).collect();
+                    let ty = Type::Func(ty::intern_list(params), ty::intern(proto.ret));
+                    self.table.funcs.insert(region);
+                    Ty::Known(ty)
+                }
+                None => {
+                    self.type_error(UndefinedName(name), region);
+                    Ty::Error
+                }
+            },
+        }
+    }
+
+    fn visit_unary_expr(&mut self, op: UnOp, rhs: &Expr, region: Region) -> Ty {
+        if op == UnOp::Not {
+            self.check_condition(rhs);
+            return Ty::Known(Type::Bool);
+        }
+        // moving or cloning a value doesn't change its type, the ownership checker makes sure
+        // moved variables aren't used again
+        if let UnOp::Move | UnOp::Clone = op {
+            return self.infer(rhs);
+        }
+
+        let mut ty = self.infer(rhs);
+        if op == UnOp::Ref && rhs.is_place() {
+            // nothing else decides the type of a variable whose address is taken
+            ty = self.default_lit(rhs, ty);
+        }
+        if let (UnOp::Ref, Some(name)) = (op, self.const_name(rhs)) {
+            self.type_error(ConstNotPlace(name), region);
+            return Ty::Error;
+        }
+        match (op, ty) {
+            (UnOp::Ref, _) if !rhs.is_place() => {
+                self.default_lit(rhs, ty);
+                self.type_error(NotAPlace, region);
+                return Ty::Error;
+            }
+            (UnOp::Ref, Ty::Known(ty)) => return Ty::Known(Type::Ptr(ty::intern(ty))),
+            (UnOp::Deref, Ty::Known(Type::Ptr(pointee))) => return Ty::Known(*pointee),
+            (UnOp::Deref, Ty::Known(ty)) => {
+                self.type_error(InvalidUnaryOp(op, ty), region);
+                return Ty::Error;
+            }
+            (UnOp::Deref, Ty::IntLit | Ty::FloatLit) => {
+                if let Ty::Known(ty) = self.default_lit(rhs, ty) {
+                    self.type_error(InvalidUnaryOp(op, ty), region);
+                }
+                return Ty::Error;
+            }
+            _ => {}
+        }
+
+        match ty {
+            Ty::Known(Type::Int { signed: true, .. } | Type::Float(_))
+            | Ty::IntLit
+            | Ty::FloatLit
+            | Ty::Error => ty,
+            Ty::Known(ty) => {
+                self.type_error(InvalidUnaryOp(op, ty), region);
+                Ty::Error
+            }
+        }
+    }
+
+    fn visit_binary_expr(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr, region: Region) -> Ty {
+        use BinOp::*;
+
+        if let And | Or = op {
+            self.check_condition(lhs);
+            self.check_condition(rhs);
+            return Ty::Known(Type::Bool);
+        }
+
+        let lty = self.infer(lhs);
+        let rty = self.infer(rhs);
+
+        if let (Eq | Ne, Ty::Known(Type::Bool), Ty::Known(Type::Bool)) = (op, lty, rty) {
+            return Ty::Known(Type::Bool);
+        }
+
+        // chars are ordered by their code points, but arithmetic on them needs a conversion
+        if let (Ge | Gt | Lt | Le | Eq | Ne, Ty::Known(Type::Char), Ty::Known(Type::Char)) =
+            (op, lty, rty)
+        {
+            return Ty::Known(Type::Bool);
+        }
+
+        // pointers and newtypes can only be compared for equality, with values of the same type
+        if let (Eq | Ne, Ty::Known(l @ (Type::Ptr(_) | Type::Newtype(..))), Ty::Known(r)) =
+            (op, lty, rty)
+        {
+            if l != r {
+                self.type_error(
+                    Mismatch {
+                        expected: l,
+                        found: r,
+                    },
+                    rhs.region,
+                );
+            }
+            return Ty::Known(Type::Bool);
+        }
+
+        let ty = self.unify_numbers(op, (lhs, lty), (rhs, rty), region);
+        match op {
+            Mul | Div | Mod | Add | Sub => ty,
+            Ge | Gt | Lt | Le | Eq | Ne => {
+                // the operands of a comparison don't get their type from the result
+                if !self.defer_lits(&[lhs, rhs], ty) {
+                    self.default_lit(lhs, ty);
+                    self.default_lit(rhs, ty);
+                }
+                Ty::Known(Type::Bool)
+            }
+            And | Or => unreachable!(),
+        }
+    }
+
+    fn visit_call_expr(&mut self, callee: &Expr, args: &[Box<Expr>], region: Region) -> Ty {
+        // a function called by its name is called directly, which is how generic functions are
+        // called
+        if self.is_direct_call(callee) {
+            return self.check_direct_call(callee, args, None, region);
+        }
+
+        // anything else is a function value
+        let ty = self.infer(callee);
+        match self.default_lit(callee, ty) {
+            Ty::Known(Type::Func(params, ret)) => {
+                self.check_args(params, args, region);
+                Ty::Known(*ret)
+            }
+            Ty::Known(ty) => {
+                self.type_error(NotCallable(ty), callee.region);
+                self.infer_args(args);
+                Ty::Error
+            }
+            _ => {
+                self.infer_args(args);
+                Ty::Error
+            }
+        }
+    }
+
+    fn visit_method_call_expr(
+        &mut self,
+        receiver: &Expr,
+        method: Name,
+        args: &[Box<Expr>],
+        region: Region,
+    ) -> Ty {
+        let ty = self.infer(receiver);
+        let Ty::Known(ty) = self.default_lit(receiver, ty) else {
+            self.infer_args(args);
+            return Ty::Error;
+        };
+
+        // a method can also be called on a pointer to its type
+        let proto = match ty {
+            Type::Name(name) | Type::Ptr(&Type::Name(name)) => {
+                self.funcs.get(&method_name(name, method)).cloned()
+            }
+            _ => None,
+        };
+        let Some(proto) = proto else {
+            self.type_error(NoMethod { ty, method }, region);
+            self.infer_args(args);
+            return Ty::Error;
+        };
+        let Some((self_param, params)) = proto
+            .params
+            .split_first()
+            .filter(|(param, _)| param.name == *SELF)
+        else {
+            self.type_error(NotAMethod(proto.name), region);
+            self.infer_args(args);
+            return Ty::Error;
+        };
+
+        match (self_param.ty, ty) {
+            (self_ty, _) if self_ty == ty => {}
+            // the method gets a copy of what the receiver points to
+            (self_ty, Type::Ptr(pointee)) if *pointee == self_ty => {}
+            // the method gets the address of the receiver
+            (Type::Ptr(pointee), _) if *pointee == ty => {
+                if let Some(name) = self.const_name(receiver) {
+                    self.type_error(ConstNotPlace(name), receiver.region);
+                } else if !receiver.is_place() {
+                    self.type_error(NotAPlace, receiver.region);
+                }
+            }
+            (expected, _) => self.type_error(
+                Mismatch {
+                    expected,
+                    found: ty,
+                },
+                receiver.region,
+            ),
+        }
+
+        // the rest of the arguments are checked like the arguments of any call
+        let proto = FuncProto {
+            params: params.to_vec(),
+            ..proto
+        };
+        self.check_call(&proto, args, None, region)
+    }
+
+    fn visit_cond_expr(
+        &mut self,
+        condition: &Expr,
+        then_branch: &Block,
+        else_branch: Option<&Block>,
+        _: Region,
+    ) -> Ty {
+        self.check_condition(condition);
+        self.check_block(then_branch);
+        if let Some(else_branch) = else_branch {
+            self.check_block(else_branch);
+        }
+
+        Ty::Known(Type::Void)
+    }
+
+    fn visit_assign_expr(&mut self, target: &Expr, value: &Expr, _: Region) -> Ty {
+        let target_ty = self.infer(target);
+        if let Some(name) = self.const_name(target) {
+            self.type_error(ConstNotPlace(name), target.region);
+        }
+        if let ExprKind::Field(base, _) = &target.kind {
+            if let Some(Type::Array(..) | Type::Slice(_)) = self.table.types.get(&base.region) {
+                self.type_error(LenIsReadOnly, target.region);
+            }
+        }
+
+        match target_ty {
+            Ty::Known(target_ty) => {
+                self.expect(value, target_ty);
+                Ty::Known(target_ty)
+            }
+            // a variable whose type isn't known yet gets the type of the value
+            Ty::IntLit | Ty::FloatLit => {
+                let ty = self.infer(value);
+                if ty == target_ty {
+                    self.defer_lits(&[target, value], ty);
+                    return ty;
+                }
+                let ty = self.default_lit(value, ty);
+                if let Ty::Known(ty) = ty {
+                    self.coerce(target, target_ty, ty);
+                }
+                ty
+            }
+            Ty::Error => {
+                let ty = self.infer(value);
+                self.default_lit(value, ty);
+                Ty::Error
+            }
+        }
+    }
+
+    fn visit_cast_expr(&mut self, value: &Expr, ty: Type, region: Region) -> Ty {
+        let ty = self.resolve(ty);
+        // `5 as UserId` is checked like `5 as int64` for `newtype UserId = int64;`
+        let lit_ty = match ty {
+            Type::Newtype(_, inner) => *inner,
+            _ => ty,
+        };
+        let from = match self.infer(value) {
+            // `300 as uint8` is checked like `300` being used as a `uint8`
+            Ty::IntLit if lit_ty.is_int() => {
+                self.type_lits(value, lit_ty);
+                return Ty::Known(ty);
+            }
+            Ty::FloatLit if lit_ty.is_float() => {
+                self.type_lits(value, lit_ty);
+                return Ty::Known(ty);
+            }
+            // chars are converted from their code points, so `65 as char` is checked like a
+            // `uint32`
+            Ty::IntLit if lit_ty == Type::Char => {
+                self.type_lits(value, Type::UINT32);
+                self.check_code_point(value);
+                return Ty::Known(ty);
+            }
+            from => self.default_lit(value, from),
+        };
+
+        match from {
+            Ty::Known(from) if !from.can_cast_to(&ty) => {
+                self.type_error(InvalidCast { from, to: ty }, region);
+                Ty::Error
+            }
+            Ty::Known(from) if from.is_int() && ty == Type::Char => {
+                self.check_code_point(value);
+                Ty::Known(ty)
+            }
+            _ => Ty::Known(ty),
+        }
+    }
+
+    fn visit_struct_lit_expr(
+        &mut self,
+        name: Name,
+        fields: &[(Name, Box<Expr>)],
+        region: Region,
+    ) -> Ty {
+        self.check_struct_lit(name, fields, &[], region)
+    }
+
+    fn visit_field_expr(&mut self, base: &Expr, field: Name, region: Region) -> Ty {
+        let ty = self.infer(base);
+        let ty = match self.default_lit(base, ty) {
+            Ty::Known(ty) => ty,
+            _ => return Ty::Error,
+        };
+
+        let field_ty = match ty {
+            Type::Array(..) | Type::Slice(_) if field.as_str() == "len" => Some(Type::UINT64),
+            // the elements of a tuple are fields named by their index
+            Type::Tuple(elems) => field
+                .parse::<usize>()
+                .ok()
+                .and_then(|idx| elems.get(idx).copied()),
+            _ => self
+                .struct_def(ty)
+                .and_then(|def| def.field(field).map(|field| field.ty)),
+        };
+
+        match field_ty {
+            Some(field_ty) => Ty::Known(field_ty),
+            None => {
+                self.type_error(NoField { ty, field }, region);
+                Ty::Error
+            }
+        }
+    }
+
+    fn visit_tuple_expr(&mut self, elems: &[Box<Expr>], _: Region) -> Ty {
+        let mut tys = Vec::new();
+        for elem in elems {
+            let ty = self.infer(elem);
+            // nothing else decides the types of literals in a tuple
+            match self.default_lit(elem, ty) {
+                Ty::Known(Type::Void) => self.type_error(VoidValue, elem.region),
+                Ty::Known(ty) => tys.push(ty),
+                _ => {}
+            }
+        }
+
+        if tys.len() < elems.len() {
+            return Ty::Error;
+        }
+        Ty::Known(Type::Tuple(ty::intern_list(tys)))
+    }
+
+    fn visit_array_expr(&mut self, elems: &[Box<Expr>], region: Region) -> Ty {
+        let tys: Vec<_> = elems.iter().map(|elem| self.infer(elem)).collect();
+        if tys.contains(&Ty::Error) {
+            return Ty::Error;
+        }
+
+        // the elements have the type of the first element whose type is known, otherwise they are
+        // all literals and get the default type (float if any of them is a float)
+        let elem_ty = match tys.iter().find_map(|ty| match ty {
+            Ty::Known(ty) => Some(*ty),
+            _ => None,
+        }) {
+            Some(ty) => ty,
+            None if tys.contains(&Ty::FloatLit) => Type::DEFAULT_FLOAT,
+            None if !tys.is_empty() => Type::DEFAULT_INT,
+            None => {
+                self.type_error(EmptyArray, region);
+                return Ty::Error;
+            }
+        };
+
+        for (elem, ty) in elems.iter().zip(tys) {
+            self.coerce(elem, ty, elem_ty);
+        }
+
+        Ty::Known(Type::Array(ty::intern(elem_ty), elems.len() as u64))
+    }
+
+    fn visit_index_expr(&mut self, base: &Expr, index: &Expr, region: Region) -> Ty {
+        let base_ty = self.infer(base);
+        let base_ty = self.default_lit(base, base_ty);
+
+        let index_ty = self.infer(index);
+        match index_ty {
+            // a literal index is a `uint64`, so any index that fits in memory can be written
+            Ty::IntLit => self.type_lits(index, Type::UINT64),
+            Ty::Known(Type::Int { .. }) | Ty::Error => {}
+            Ty::Known(found) => self.type_error(
+                Mismatch {
+                    expected: Type::UINT64,
+                    found,
+                },
+                index.region,
+            ),
+            Ty::FloatLit => self.type_error(NotAFloat(Type::UINT64), index.region),
+        }
+
+        match base_ty {
+            Ty::Known(Type::Array(elem, len)) => {
+                if let ExprKind::Lit(Lit::Int(index)) = index.kind {
+                    if index >= len {
+                        self.type_error(IndexOutOfBounds { index, len }, region);
+                    }
+                }
+                Ty::Known(*elem)
+            }
+            Ty::Known(Type::Slice(elem)) => Ty::Known(*elem),
+            Ty::Known(ty) => {
+                self.type_error(NotIndexable(ty), base.region);
+                Ty::Error
+            }
+            _ => Ty::Error,
+        }
+    }
+
+    fn visit_variant_expr(
+        &mut self,
+        enum_name: Name,
+        variant: Name,
+        args: &[Box<Expr>],
+        region: Region,
+    ) -> Ty {
+        // `Point::new()` calls a function in `impl Point`
+        if !self.enums.contains_key(&enum_name) {
+            if let Some(proto) = self.funcs.get(&method_name(enum_name, variant)).cloned() {
+                return self.check_call(&proto, args, None, region);
+            }
+            if self.structs.contains_key(&enum_name) {
+                self.type_error(
+                    NoMethod {
+                        ty: Type::Name(enum_name),
+                        method: variant,
+                    },
+                    region,
+                );
+                self.infer_args(args);
+                return Ty::Error;
+            }
+        }
+
+        let field_tys = match self.enums.get(&enum_name) {
+            Some(def) => match def.variant(variant) {
+                Some(idx) => Some(def.variants[idx].fields.clone()),
+                None => {
+                    self.type_error(
+                        UnknownVariant {
+                            ty: enum_name,
+                            variant,
+                        },
+                        region,
+                    );
+                    None
+                }
+            },
+            None => {
+                self.type_error(UnknownType(enum_name), region);
+                None
+            }
+        };
+        let Some(field_tys) = field_tys else {
+            self.infer_args(args);
+            return Ty::Error;
+        };
+
+        if args.len() != field_tys.len() {
+            self.type_error(
+                InvalidArgs {
+                    expected: field_tys.len(),
+                    found: args.len(),
+                },
+                region,
+            );
+        }
+        for (arg, ty) in args.iter().zip(field_tys) {
+            self.expect(arg, ty);
+        }
+
+        Ty::Known(Type::Name(enum_name))
+    }
+
+    fn visit_match_expr(&mut self, scrutinee: &Expr, arms: &[Arm], region: Region) -> Ty {
+        let ty = self.infer(scrutinee);
+        let ty = match self.default_lit(scrutinee, ty) {
+            Ty::Known(Type::Void) => {
+                self.type_error(VoidValue, scrutinee.region);
+                None
+            }
+            Ty::Known(ty) => Some(ty),
+            _ => None,
+        };
+
+        let mut patterns_ok = ty.is_some();
+        for arm in arms {
+            self.scopes.push(HashMap::new());
+            if let Some(ty) = ty {
+                patterns_ok &= self.check_pattern(&arm.pattern, ty);
+            }
+            self.check_block(&arm.body);
+            self.pop_scope();
+        }
+
+        // the analysis needs every pattern to be valid
+        if let (Some(ty), true) = (ty, patterns_ok) {
+            let patterns: Vec<&Pattern> = arms.iter().map(|arm| &arm.pattern).collect();
+            let report = exhaustive::Checker::new(&self.enums).check(&patterns, ty);
+            for idx in report.unreachable {
+                self.type_error(UnreachableArm, arms[idx].pattern.region);
+            }
+            if let Some(missing) = report.missing {
+                self.type_error(NonExhaustive(missing), region);
+            }
+        }
+
+        Ty::Known(Type::Void)
+    }
+
+    fn visit_lambda_expr(
+        &mut self,
+        params: &[Param],
+        ret: Type,
+        body: &Block,
+        region: Region,
+    ) -> Ty {
+        for param in params {
+            self.check_type(self.resolve(param.ty), param.ty_region);
+        }
+        let ret = self.resolve(ret);
+        self.check_type(ret, region);
+
+        // the body is checked like the body of a function, in the scopes around the lambda so
+        // that it can use their variables
+        self.lambdas.push(LambdaScope {
+            depth: self.scopes.len(),
+            captures: Vec::new(),
+        });
+        let outer_ret = std::mem::replace(&mut self.ret_ty, ret);
+        self.scopes.push(HashMap::new());
+        for param in params {
+            self.define(param.name, self.resolve(param.ty));
+        }
+        self.check_block(body);
+        self.pop_scope();
+        self.ret_ty = outer_ret;
+
+        let lambda = self.lambdas.pop().unwrap();
+        self.table.captures.insert(region, lambda.captures);
+
+        let params = params.iter().map(|param| self.resolve(param.ty)).collect();
+        Ty::Known(Type::Func(ty::intern_list(params), ty::intern(ret)))
+    }
+
+    // the parser has already reported the error
+    fn visit_error_expr(&mut self, _: Region) -> Ty {
+        Ty::Error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::source::Source;
+
+    fn check(source_code: &'static str) -> Vec<TypeErrorKind> {
+        let source = Source::new(source_code, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+        assert!(parser.errors().is_empty(), "failed to parse");
+
+        let mut typechk = TypeChecker::new();
+        typechk.check(&module);
+        typechk.errors().iter().map(|e| e.kind.clone()).collect()
+    }
+
+    /// Type checks the module and returns the types of the expressions that are exactly the given
+    /// snippets of it, each snippet appears once in the module
+    fn types_of(source_code: &'static str, snippets: &[&str]) -> Vec<Type> {
+        let source = Source::new(source_code, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+        assert!(parser.errors().is_empty(), "failed to parse");
+
+        let mut typechk = TypeChecker::new();
+        typechk.check(&module);
+        assert!(typechk.errors().is_empty(), "failed to type check");
+        snippets
+            .iter()
+            .map(|snippet| {
+                let start = source_code
+                    .find(snippet)
+                    .expect("the snippet is in the module");
+                typechk.table.types[&Region::new(start, start + snippet.len())]
+            })
+            .collect()
+    }
+
+    fn int(signed: bool, bits: u8) -> Type {
+        Type::Int {
+            signed,
+            size: crate::ast::ty::IntSize::new(bits).unwrap(),
+        }
+    }
+
+    #[test]
+    fn literals_take_their_type_from_context() {
+        assert!(check(
+            "func f(a: uint8, b: int64): uint64 {
+                var x = a + 200;
+                var y = b * -9223372036854775808;
+                ret 18446744073709551615;
+            }"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn literal_out_of_range() {
+        assert_eq!(
+            check("func f(a: uint8) { a = 256; a = -1; var x = 3000000000; }"),
+            [
+                OutOfRange {
+                    value: 256,
+                    negative: false,
+                    ty: int(false, 8)
+                },
+                OutOfRange {
+                    value: 1,
+                    negative: true,
+                    ty: int(false, 8)
+                },
+                OutOfRange {
+                    value: 3000000000,
+                    negative: false,
+                    ty: Type::DEFAULT_INT
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_implicit_mixing() {
+        assert_eq!(
+            check("func f(a: int32, b: uint32, c: int64) { a + b; a < c; }"),
+            [
+                MixedSignedness(int(true, 32), int(false, 32)),
+                MixedSizes(int(true, 32), int(true, 64)),
+            ]
+        );
+    }
+
+    #[test]
+    fn chars() {
+        assert!(check(
+            "const SMILE: uint32 = 128512;
+            func f(c: char, b: uint8, u: uint32): uint32 {
+                var d = b as char;
+                var e = 65 as char;
+                var g = u as char;
+                var h = 1114111 as char;
+                if g == SMILE as char || h == 233 as char { ret 0; }
+                if c >= 'a' && c != d && e == '\u{e9}' { ret c as uint32 + 1; }
+                ret '0' as uint32;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "extern func g(a: int32);
+                const SURROGATE: uint32 = 55296;
+                func f(c: char, i: int32) {
+                    c + 1; c < 65; i as char; 1114112 as char; SURROGATE as char; g('a');
+                    -1 as char;
+                }"
+            ),
+            [
+                InvalidOp(BinOp::Add, Type::Char),
+                InvalidOp(BinOp::Lt, Type::Char),
+                InvalidCast {
+                    from: int(true, 32),
+                    to: Type::Char
+                },
+                InvalidCodePoint(1114112),
+                InvalidCodePoint(55296),
+                Mismatch {
+                    expected: int(true, 32),
+                    found: Type::Char
+                },
+                OutOfRange {
+                    value: 1,
+                    negative: true,
+                    ty: int(false, 32)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn inferred_variables() {
+        assert!(check(
+            "extern func add(a: int32, b: int32): int32;
+            extern func small(a: uint8);
+            extern func big(a: int64);
+            extern func scan(out: *uint16): bool;
+            func f(w: int32): int32 {
+                var x = add(w, 1);
+                var n = 200;
+                small(n);
+                var i = 0;
+                var j = i;
+                if i < 10 { j = j + 1; }
+                big(j);
+                var k = -3000000000;
+                k;
+                big(k);
+                var m = 60000;
+                scan(&m);
+                ret x;
+            }"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn unknown_types_point_at_the_type() {
+        let source_code = "func f(a: int32, b: int3): [Foo] { }";
+        let source = Source::new(source_code, "<string literal>");
+        let mut lexer = Lexer::new(&source);
+        let mut parser = Parser::new(lexer.lex(), &source);
+        let module = parser.parse();
+
+        let mut typechk = TypeChecker::new();
+        typechk.check(&module);
+        let errors: Vec<_> = typechk
+            .errors()
+            .iter()
+            .map(|err| (err.kind.clone(), err.region.start()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (
+                    UnknownType(Name::from("int3")),
+                    source_code.find("int3)").unwrap()
+                ),
+                (
+                    UnknownType(Name::from("Foo")),
+                    source_code.find("[Foo]").unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn inferred_literal_types() {
+        let types = types_of(
+            "extern func wide(a: uint64);
+            func narrow(): uint16 { var r = 11; ret r; }
+            func f(b: int8, c: uint32): int8 {
+                var p = 22;
+                wide(p);
+                var q = 33;
+                var s = b - q;
+                var t = 44;
+                var u = t * c;
+                var v = 55;
+                var w = v + 66;
+                ret w;
+            }",
+            &["11", "22", "33", "44", "t * c", "55", "66"],
+        );
+        assert_eq!(
+            types,
+            [
+                // from the return type
+                int(false, 16),
+                // from the parameter the variable is passed to
+                int(false, 64),
+                // from the other operand, on either side
+                int(true, 8),
+                int(false, 32),
+                int(false, 32),
+                // through another variable to the return type
+                int(true, 8),
+                int(true, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn inferred_conflicts() {
+        let errors: Vec<String> = check(
+            "extern func small(a: uint8);
+            extern func big(a: int64);
+            func f() {
+                var x = 1;
+                small(x);
+                big(x);
+                var y = 300;
+                small(y);
+                var z = 1;
+                var w = z + 1;
+                small(w);
+                big(z);
+                var d = 3000000000;
+            }
+            func g(): uint8 {
+                var r = 1;
+                big(r);
+                ret r;
+            }",
+        )
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(
+            errors,
+            [
+                "type mismatch, expected `int64` but `x` has type `uint8`",
+                "the literal `300` doesn't fit in `uint8`",
+                "type mismatch, expected `int64` but `z` has type `uint8`",
+                "the literal `3000000000` doesn't fit in `int32`",
+                "type mismatch, expected `uint8` but `r` has type `int64`",
+            ]
+        );
+    }
+
+    #[test]
+    fn casts() {
+        assert!(check(
+            "func f(a: int32, b: uint32, c: int64): int64 {
+                var x = a + b as int32;
+                ret (a as int64) + c + (a < 0) as int64;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check("func f() { 300 as uint8; }"),
+            [OutOfRange {
+                value: 300,
+                negative: false,
+                ty: int(false, 8)
+            }]
+        );
+    }
+
+    #[test]
+    fn booleans() {
+        assert!(check(
+            "func f(a: int32, b: bool): bool {
+                var c = true;
+                if !b && (a < 0 || c == false) {
+                    ret b != c;
+                }
+                ret false;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check("func f(a: int32, b: bool): int32 { if a {} !b; b + 1; ret true; }"),
+            [
+                IntCondition(int(true, 32)),
+                InvalidOp(BinOp::Add, Type::Bool),
+                Mismatch {
+                    expected: int(true, 32),
+                    found: Type::Bool
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            check(
+                "extern func puts(s: str): int32;
+                func f(s: str) { puts(s); puts(\"hi\"); puts(1); s + s; }"
+            ),
+            [NotAnInt(Type::Str), InvalidOp(BinOp::Add, Type::Str)]
+        );
+    }
+
+    #[test]
+    fn floats() {
+        assert!(check(
+            "func f(a: float32, b: float64, c: int32): float64 {
+                var x = a * 2.5 - -1e10;
+                var y = 0.5 < 1.5;
+                var z = (c as float64) / b + (x as float64);
+                ret -b % 3.0;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "extern func g(x: float32);
+                func f(a: float64, b: float32) { a + 1; a + b; 1 + 1.5; g(1e40); }"
+            ),
+            [
+                NotAnInt(Type::DEFAULT_FLOAT),
+                Mismatch {
+                    expected: Type::DEFAULT_FLOAT,
+                    found: Type::Float(FloatSize::F32)
+                },
+                Mismatch {
+                    expected: Type::DEFAULT_INT,
+                    found: Type::DEFAULT_FLOAT
+                },
+                FloatOutOfRange(1e40),
+            ]
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let point = Type::Name(Name::from("Point"));
+        assert!(check(
+            "struct Point { x: int32, y: uint8 }
+            struct Line { from: Point, to: Point }
+            func f(l: Line): Point {
+                var p = Point { y: 1, x: l.to.x };
+                l.from = p;
+                l.from.y = 255;
+                ret l.to;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "struct Point { x: int32, y: int32 }
+                func f(p: Point) {
+                    Point { x: 1 };
+                    Point { x: 1, y: 2, x: 3 };
+                    Point { x: 1, y: 2, z: 3 };
+                    p.z;
+                    p.x = true;
+                    Foo { };
+                }"
+            ),
+            [
+                MissingField {
+                    ty: point,
+                    field: Name::from("y")
+                },
+                DuplicateField(Name::from("x")),
+                NoField {
+                    ty: point,
+                    field: Name::from("z")
+                },
+                NoField {
+                    ty: point,
+                    field: Name::from("z")
+                },
+                Mismatch {
+                    expected: int(true, 32),
+                    found: Type::Bool
+                },
+                UnknownType(Name::from("Foo")),
+            ]
+        );
+    }
+
+    #[test]
+    fn recursive_structs() {
+        assert_eq!(
+            check(
+                "struct A { b: B }
+                struct B { a: A, c: C }"
+            ),
+            [
+                RecursiveStruct(Name::from("A")),
+                UnknownType(Name::from("C")),
+                RecursiveStruct(Name::from("B")),
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        let array = |elem, len| Type::Array(ty::intern(elem), len);
+        assert!(check(
+            "struct P { xs: [uint8; 2] }
+            func sum(xs: [int64]): int64 { ret xs[0] + xs[xs.len - 1]; }
+            func f(i: int32): int64 {
+                var a = [1, 2, 3];
+                var p = P { xs: [1, 255] };
+                p.xs[i] = 7;
+                a[2] = a[i] + (p.xs.len as int32);
+                ret sum([1, 2]);
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "func f(a: [int32; 2], s: [int32], x: int32) {
+                    a[2];
+                    a[true];
+                    x[0];
+                    a = [1, 2, 3];
+                    var e = [];
+                    var m = [1, x, false];
+                    s.len = 1;
+                }"
+            ),
+            [
+                IndexOutOfBounds { index: 2, len: 2 },
+                Mismatch {
+                    expected: Type::UINT64,
+                    found: Type::Bool
+                },
+                NotIndexable(Type::DEFAULT_INT),
+                Mismatch {
+                    expected: array(Type::DEFAULT_INT, 2),
+                    found: array(Type::DEFAULT_INT, 3)
+                },
+                EmptyArray,
+                Mismatch {
+                    expected: Type::DEFAULT_INT,
+                    found: Type::Bool
+                },
+                LenIsReadOnly,
+            ]
+        );
+    }
+
+    #[test]
+    fn pointers() {
+        let ptr = |ty| Type::Ptr(ty::intern(ty));
+        assert!(check(
+            "struct P { x: int32 }
+            extern func scan(out: *int32): bool;
+            func f(p: P, a: [uint8; 2]): *uint8 {
+                var n = 0;
+                scan(&n);
+                scan(&p.x);
+                var q = &p;
+                (*q).x = *&n + 1;
+                var pp = &q;
+                (**pp).x = 2;
+                var same = q == &p;
+                ret &a[1];
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "func f(x: int32, p: *int32, q: *uint8) {
+                    &1;
+                    &(x + 1);
+                    *x;
+                    *p = true;
+                    p == q;
+                }"
+            ),
+            [
+                NotAPlace,
+                NotAPlace,
+                InvalidUnaryOp(UnOp::Deref, Type::DEFAULT_INT),
+                Mismatch {
+                    expected: Type::DEFAULT_INT,
+                    found: Type::Bool
+                },
+                Mismatch {
+                    expected: ptr(Type::DEFAULT_INT),
+                    found: ptr(int(false, 8))
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn generics() {
+        assert!(check(
+            "struct Pair<A, B> { first: A, second: B }
+            func max<T>(a: T, b: T): T { if a > b { ret a; } ret b; }
+            func first<A, B>(p: Pair<A, B>): A { ret p.first; }
+            func sum<T>(xs: [T]): T { ret xs[0] + xs[1]; }
+            func flag(): Pair<uint8, bool> { ret Pair { first: 255, second: true }; }
+            func f(x: uint8): uint8 {
+                var p = Pair { first: x, second: [1, 2] };
+                var s = sum([1.5, 2.5]) * 2.0;
+                ret max(first(p), 255) + flag().first;
+            }"
+        )
+        .is_empty());
+
+        let boxed = Name::from("Box");
+        assert_eq!(
+            check(
+                "struct Box<T> { value: T }
+                func id<T>(x: T): T { ret x; }
+                func both<T>(a: T, b: T) { }
+                func make<T>(): T { ret make(); }
+                func neg<T>(x: T): T { ret -x; }
+                func f(a: int32, b: uint8, c: Box, d: Box<int32, bool>) {
+                    id(a) + b;
+                    both(a, b);
+                    make();
+                    neg(b);
+                    neg(b);
+                    Box { value: 1, value: 2 };
+                }"
+            ),
+            [
+                WrongTypeArgs {
+                    name: boxed,
+                    expected: 1,
+                    found: 0
+                },
+                WrongTypeArgs {
+                    name: boxed,
+                    expected: 1,
+                    found: 2
+                },
+                MixedSignedness(int(true, 32), int(false, 8)),
+                Mismatch {
+                    expected: int(true, 32),
+                    found: int(false, 8)
+                },
+                CannotInfer(Name::from("T")),
+                DuplicateField(Name::from("value")),
+                InvalidUnaryOp(UnOp::Neg, int(false, 8)),
+            ]
+        );
+
+        assert_eq!(
+            check("func deep<T>(x: T) { deep([x]); } func f() { deep(1); }"),
+            [TooManyInstances(Name::from("deep"))]
+        );
+    }
+
+    #[test]
+    fn generic_results_take_their_type_from_context() {
+        // the expected type decides the type parameters that only literals would
+        assert!(check(
+            "func id<T>(x: T): T { ret x; }
+            func make<T>(): T { ret make(); }
+            func g(a: uint64) { }
+            func f(): int8 {
+                g(id(3));
+                g(make());
+                ret id(5);
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "func id<T>(x: T): T { ret x; }
+                func f(a: int32): int8 { id(300) as int8; ret id(a); }
+                func h(): int8 { ret id(300); }"
+            ),
+            [
+                Mismatch {
+                    expected: int(true, 8),
+                    found: int(true, 32)
+                },
+                OutOfRange {
+                    value: 300,
+                    negative: false,
+                    ty: int(true, 8)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn enums() {
+        let shape = Type::Name(Name::from("Shape"));
+        assert!(check(
+            "enum Shape { Circle(uint8), Rect(int32, int32), Empty }
+            struct Canvas { shapes: [Shape; 2] }
+            func f(r: uint8): Canvas {
+                var c = Shape::Circle(r);
+                ret Canvas { shapes: [c, Shape::Rect(-1, 2)] };
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "enum Shape { Circle(uint8), Empty, Empty }
+                enum List { Nil, Cons(int32, List) }
+                func f(s: Shape) {
+                    Shape::Circle(256);
+                    Shape::Circle();
+                    Shape::Square;
+                    Color::Red;
+                    s = 1;
+                }"
+            ),
+            [
+                DuplicateVariant(Name::from("Empty")),
+                RecursiveStruct(Name::from("List")),
+                OutOfRange {
+                    value: 256,
+                    negative: false,
+                    ty: int(false, 8)
+                },
+                InvalidArgs {
+                    expected: 1,
+                    found: 0
+                },
+                UnknownVariant {
+                    ty: Name::from("Shape"),
+                    variant: Name::from("Square")
+                },
+                UnknownType(Name::from("Color")),
+                NotAnInt(shape),
+            ]
+        );
+    }
+
+    #[test]
+    fn match_patterns() {
+        assert!(check(
+            "enum Shape { Circle(uint8), Rect(int32, int32), Empty }
+            func area(s: Shape, x: int8, b: bool, c: char): int32 {
+                match b { true => { } false => { } }
+                match x { -128 => { } 97 => { } n => { x = n; } }
+                match c { 'a' => { } '\u{e9}' => { } _ => { } }
+                match s {
+                    Shape::Circle(r) => { ret (r * r) as int32; }
+                    Shape::Rect(0, _) => { ret 0; }
+                    Shape::Rect(w, h) => { ret w * h; }
+                    Shape::Empty => { ret 0; }
+                }
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "enum Shape { Circle(uint8), Rect(int32, int32), Empty }
+                func f(s: Shape, x: uint8, b: bool) {
+                    match x { 1.5 => { } \"a\" => { } 300 => { } true => { } 'a' => { } _ => { } }
+                    match s { Shape::Rect(a) => { } Shape::Square => { } Foo::Bar => { } _ => { } }
+                    match x { y => { y = false; } }
+                    match b { 1 => { } _ => { } }
+                }"
+            ),
+            [
+                InvalidLitPattern(Lit::Float(1.5)),
+                InvalidLitPattern(Lit::Str(Name::from("a"))),
+                OutOfRange {
+                    value: 300,
+                    negative: false,
+                    ty: int(false, 8)
+                },
+                Mismatch {
+                    expected: int(false, 8),
+                    found: Type::Bool
+                },
+                Mismatch {
+                    expected: int(false, 8),
+                    found: Type::Char
+                },
+                InvalidArgs {
+                    expected: 2,
+                    found: 1
+                },
+                UnknownVariant {
+                    ty: Name::from("Shape"),
+                    variant: Name::from("Square")
+                },
+                UnknownType(Name::from("Foo")),
+                Mismatch {
+                    expected: int(false, 8),
+                    found: Type::Bool
+                },
+                NotAnInt(Type::Bool),
+            ]
+        );
+    }
+
+    #[test]
+    fn exhaustiveness() {
+        let errors = |source| {
+            check(source)
+                .into_iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(check(
+            "enum Shape { Circle(bool), Rect(Shape2), Empty }
+            enum Shape2 { A, B }
+            func f(s: Shape) {
+                match s {
+                    Shape::Circle(true) => { }
+                    Shape::Rect(Shape2::A) => { }
+                    Shape::Circle(false) => { }
+                    Shape::Rect(_) => { }
+                    Shape::Empty => { }
+                }
+            }"
+        )
+        .is_empty());
+
+        assert_eq!(
+            check(
+                "enum Shape { Circle(bool), Empty }
+                func f(s: Shape, x: int32) {
+                    match s { Shape::Circle(true) => { } Shape::Empty => { } }
+                    match x { 1 => { } 2 => { } }
+                    match s { other => { } Shape::Empty => { } }
+                    match s { Shape::Circle(_) => { } Shape::Circle(true) => { } _ => { } }
+                }"
+            ),
+            [
+                NonExhaustive("Shape::Circle(false)".to_string()),
+                NonExhaustive("_".to_string()),
+                UnreachableArm,
+                UnreachableArm,
+            ]
+        );
+        assert_eq!(
+            errors("enum E { A(E2, int32), B } enum E2 { X, Y } func f(e: E) { match e { E::B => { } E::A(E2::X, 1) => { } } }"),
+            ["the match doesn't handle every value, `E::A(E2::Y, _)` isn't matched; hint: add an arm with `_`"]
+        );
+        assert_eq!(
+            errors("func f(t: (bool, (bool, int32))) { match t { (true, _) => { } (_, (true, 0)) => { } } }"),
+            ["the match doesn't handle every value, `(false, (false, _))` isn't matched; hint: add an arm with `_`"]
+        );
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(
+            check(
+                "func add(a: int32, b: int32): int32 { ret a + b; }
+                func f(c: uint8) { add(1, c); add(1); }"
+            ),
+            [
+                Mismatch {
+                    expected: int(true, 32),
+                    found: int(false, 8)
+                },
+                InvalidArgs {
+                    expected: 2,
+                    found: 1
+                },
+            ]
+        );
+        let one = InvalidArgs {
+            expected: 1,
+            found: 2,
+        };
+        assert_eq!(one.to_string(), "expected 1 argument but found 2");
+    }
+
+    #[test]
+    fn constants() {
+        assert!(check(
+            "func f(): int64 { ret BIG * 2 + MIN as int64; }
+            const BASE: int32 = 10;
+            const BIG: int64 = BASE as int64 * 1000000000;
+            const MIN: int8 = -128;
+            const WRAPPED: uint8 = 300 as uint16 as uint8;
+            const HALF: float32 = 1.0 / 2.0;
+            const DEBUG: bool = BASE > 5 && !(HALF == 0.5);
+            const NAME: str = \"kip\";
+            func g(c: uint8) {
+                const LOCAL: uint8 = WRAPPED + 1;
+                c = LOCAL;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "extern func g(): int32;
+                const A: int32 = g();
+                const B: uint8 = 200 + 100;
+                const C: uint8 = 100 * 3 / 2;
+                const D: int32 = 1 / (2 - 2);
+                const E: int32 = A + 1;
+                func f(x: int32) {
+                    const F: int32 = x;
+                    E = 2;
+                    &E;
+                }"
+            ),
+            [
+                NotConstant,
+                ConstOverflow(int(false, 8)),
+                ConstOverflow(int(false, 8)),
+                ConstDivByZero,
+                NotConstant,
+                ConstNotPlace(Name::from("E")),
+                ConstNotPlace(Name::from("E")),
+            ]
+        );
+    }
+
+    #[test]
+    fn undefined_names() {
+        assert_eq!(
+            check(
+                "func f(): int32 { ret Q; }
+                const A: int32 = B + 1;
+                const B: int32 = 2;
+                func g() { h(); }"
+            ),
+            [
+                UndefinedName(Name::from("B")),
+                UndefinedName(Name::from("Q")),
+                UndefinedName(Name::from("h")),
+            ]
+        );
+    }
+
+    #[test]
+    fn methods() {
+        assert!(check(
+            "struct Counter { n: int32 }
+            impl Counter {
+                func new(): Counter { ret Counter { n: 0 }; }
+                func get(self): int32 { ret self.n; }
+                func add(self: *Counter, by: int32) { (*self).n = self.get() + by; }
+                func pick<T>(self, a: T, b: T): T { ret a; }
+            }
+            func f(p: *Counter): int64 {
+                var c = Counter::new();
+                c.add(1);
+                p.add(c.get());
+                ret c.pick(1, 2) as int64;
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "struct Counter { n: int32 }
+                impl Counter {
+                    func new(): Counter { ret Counter { n: 0 }; }
+                    func add(self: *Counter, by: int32) { }
+                    func bad(self: int32) { }
+                }
+                impl Missing { }
+                impl int32 { }
+                type Meters = int32;
+                impl Meters { }
+                func f(p: *Counter) {
+                    var c = Counter::new();
+                    c.sub();
+                    c.new();
+                    c.add(true);
+                    Counter::new().add(1);
+                    Counter::zero();
+                    var n = 1;
+                    n.add(1);
+                }"
+            ),
+            [
+                InvalidSelf {
+                    ty: Name::from("Counter"),
+                    found: int(true, 32)
+                },
+                UnknownType(Name::from("Missing")),
+                InvalidImpl(Name::from("int32")),
+                InvalidImpl(Name::from("Meters")),
+                NoMethod {
+                    ty: Type::Name(Name::from("Counter")),
+                    method: Name::from("sub")
+                },
+                NotAMethod(Name::from("Counter::new")),
+                Mismatch {
+                    expected: int(true, 32),
+                    found: Type::Bool
+                },
+                NotAPlace,
+                NoMethod {
+                    ty: Type::Name(Name::from("Counter")),
+                    method: Name::from("zero")
+                },
+                NoMethod {
+                    ty: int(true, 32),
+                    method: Name::from("add")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn closures() {
+        assert!(check(
+            "func add(a: int32, b: int32): int32 { ret a + b; }
+            func apply(f: func(int32, int32): int32, a: int32): int32 { ret f(a, 1); }
+            func adder(n: uint8): func(uint8): uint8 {
+                ret func(x: uint8): uint8 { ret x + n; };
+            }
+            func f(): uint8 {
+                var k = 1;
+                var g = func(): uint8 { ret k; };
+                apply(add, 2);
+                apply(func(a: int32, b: int32): int32 { ret a - b; }, 3);
+                ret adder(1)(2) + g();
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "func id<T>(x: T): T { ret x; }
+                func f() {
+                    var g = id;
+                    var h = func(x: int32): bool { ret x; };
+                    h(true);
+                    h(1, 2);
+                    3(1);
+                }"
+            ),
+            [
+                GenericFuncValue(Name::from("id")),
+                Mismatch {
+                    expected: Type::Bool,
+                    found: int(true, 32)
+                },
+                Mismatch {
+                    expected: int(true, 32),
+                    found: Type::Bool
+                },
+                InvalidArgs {
+                    expected: 1,
+                    found: 2
+                },
+                NotCallable(int(true, 32)),
+            ]
+        );
+    }
+
+    #[test]
+    fn variadic() {
+        assert!(check(
+            "extern func printf(fmt: str, ...): int32;
+            func f(p: *uint8) { printf(\"%d %f %s %p\", 1, 2.5, \"s\", p); printf(\"\"); }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "extern func printf(fmt: str, ...): int32;
+                struct P { x: int32 }
+                func f(a: int32, ...) { }
+                func g() {
+                    printf();
+                    printf(1);
+                    printf(\"\", P { x: 1 }, f(1));
+                    var h = printf;
+                }"
+            ),
+            [
+                VariadicNotExtern(Name::from("f")),
+                TooFewArgs {
+                    expected: 1,
+                    found: 0
+                },
+                NotAnInt(Type::Str),
+                InvalidVarArg(Type::Name(Name::from("P"))),
+                VoidValue,
+                VariadicFuncValue(Name::from("printf")),
+            ]
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        assert!(check(
+            "func divmod(a: int32, b: int32): (int32, int32) { ret (a / b, a % b); }
+            var (q, r) = divmod(7, 2);
+            func g(s: (int64, bool)): bool { ret s.1; }
+            func f(t: (uint8, (bool, float64))): float64 {
+                var (x, (b, y)) = t;
+                match (g((1, b)), t.1.0) {
+                    (true, _) => { ret y; }
+                    (false, c) => { ret t.1.1; }
+                }
+            }"
+        )
+        .is_empty());
+        assert_eq!(
+            check(
+                "func f(t: (int32, bool)) {
+                    var (a, b) = 3;
+                    var (c, d, e) = t;
+                    var (1, g) = t;
+                    f((1, 2));
+                    var i = t.2;
+                }"
+            ),
+            [
+                NotATuple(int(true, 32)),
+                TupleLen {
+                    expected: 2,
+                    found: 3
+                },
+                RefutablePattern,
+                NotAnInt(Type::Bool),
+                NoField {
+                    ty: Type::Tuple(crate::ast::ty::intern_list(vec![int(true, 32), Type::Bool])),
+                    field: Name::from("2")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn type_decls() {
+        assert!(check(
+            "type Meters = int32;
+            newtype UserId = int64;
+            newtype Flag = bool;
+            const ADMIN: UserId = 1 as UserId;
+            func next(id: UserId): UserId { ret (id as int64 + 1) as UserId; }
+            func f(d: Meters, id: UserId, on: Flag): Meters {
+                if id == ADMIN && on as bool { ret d * 2; }
+                next(id);
+                ret 1;
+            }"
+        )
+        .is_empty());
+
+        let id = Type::Newtype(Name::from("Id"), crate::ast::ty::intern(int(true, 64)));
+        assert_eq!(
+            check(
+                "func g(x: Later): Later { ret x; }
+                type Later = int32;
+                newtype Id = int64;
+                newtype Ids = [Id];
+                func f(a: Id, b: int64) {
+                    var c = a + a;
+                    var d = a == b;
+                    f(1, 2);
+                    var e = a as int32;
+                }"
+            ),
+            [
+                InvalidNewtype(Type::Slice(crate::ast::ty::intern(id))),
+                InvalidOp(BinOp::Add, id),
+                Mismatch {
+                    expected: id,
+                    found: int(true, 64)
+                },
+                NotAnInt(id),
+                InvalidCast {
+                    from: id,
+                    to: int(true, 32)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ret_values() {
+        assert!(
+            check("func f(c: bool) { if c { ret; } } func g(): int32 { f(true); ret 1; }")
+                .is_empty()
+        );
+        assert_eq!(
+            check("func f(): int32 { ret; } func g() { ret 1; }"),
+            [MissingRetValue(int(true, 32)), NotAnInt(Type::Void)]
+        );
     }
 }
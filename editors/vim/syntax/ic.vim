@@ -9,7 +9,7 @@ if exists("b:current_syntax")
 endif
 
 syntax case match
-syntax keyword icStatement goto call arg ret
+syntax keyword icStatement goto call arg ret tailcall local trap jumptable
 syntax keyword icConditional ifnz ifz
 syntax match icNumber /\<\d\+\>/
 syntax match icIdentifier /\<\h\w*\>/
@@ -29,7 +29,7 @@ syntax match icOperator /<=/
 " }}}
 
 syntax region icString start=/'/ end=/'/
-syntax region icString start=/"/ end=/"/
+syntax region icString start=/"/ skip=/\\./ end=/"/
 
 syntax keyword icTodo TODO FIXME XXX contained
 syntax match icComment /;.*/ contains=icTodo
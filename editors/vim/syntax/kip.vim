@@ -9,12 +9,16 @@ if exists("b:current_syntax")
 endif
 
 syntax case match
-syntax keyword kipKeyword extern func var
-syntax keyword kipStatement if else ret
-syntax keyword kipType s32 s64 u32 u64
+syntax keyword kipKeyword extern func var const struct enum impl match move clone as
+syntax keyword kipKeyword type newtype
+syntax keyword kipStatement if else while ret
+syntax keyword kipBoolean true false
+syntax keyword kipType int8 int16 int32 int64 uint8 uint16 uint32 uint64
+syntax keyword kipType float32 float64 bool char
 syntax keyword kipType str
 
 syntax match kipNumber /\<\d\+\>/
+syntax match kipNumber /\<\d\+\(\.\d\+\)\=\([eE][+-]\=\d\+\)\=\>/
 syntax match kipIdentifier /\<\h\w*\>/
 syntax match kipPreProc /@\<\h\w*\>/
 " Section: Operators --- {{{
@@ -34,8 +38,9 @@ syntax match kipOperator /<=/
 syntax match kipFunction /\<\h\w*\>(/he=e-1,me=e-1
 " type annotations
 syntax region kipType start=/:\s*/ms=s+1 end=/\<\h\w*\>/
-syntax region kipString start=/"/ end=/"/
-syntax region kipString start=/'/ end=/'/
+syntax match kipEscape /\\\([ntr0\\"']\|x\x\x\|u{\x\+}\)/ contained
+syntax region kipString start=/"/ skip=/\\./ end=/"/ contains=kipEscape
+syntax region kipString start=/'/ skip=/\\./ end=/'/ contains=kipEscape
 
 syntax keyword kipTodo TODO FIXME XXX contained
 syntax match kipLineComment /\/\/.*/ contains=kipTodo
@@ -44,8 +49,10 @@ syntax region kipBlockComment start="/\*" end="\*/" contains=kipTodo
 let b:current_syntax = "kip"
 
 highlight def link kipNumber Number
+highlight def link kipBoolean Boolean
 highlight def link kipTodo Todo
 highlight def link kipString String
+highlight def link kipEscape SpecialChar
 highlight def link kipKeyword Keyword
 highlight def link kipStatement Statement
 highlight def link kipType Type